@@ -1,5 +1,11 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant}
+};
+
 use winit::{
     application::ApplicationHandler, 
+    dpi::PhysicalSize,
     event::{ElementState, KeyEvent, WindowEvent}, 
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop}, 
     keyboard::{KeyCode, PhysicalKey}, 
@@ -7,53 +13,68 @@ use winit::{
 };
 
 use glium::{
+    backend::Facade,
     glutin::surface::WindowSurface,
     Display
 };
 
-use crate::app::{mesh::Mesh, parser::Obj};
-
 use super::{
-    ctx::Ctx,
-    rendering::Renderer,
+    backend::GliumBackend,
+    cli::Args,
+    hot_reload::POLL_INTERVAL,
     utils,
-    parser,
-    parser::Images
+    viewer::Viewer
 };
 
+// Redraw interval of the loading progress.
+const LOAD_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 pub struct App {
     pub window: Window,
     pub display: Display<WindowSurface>,
-    pub renderer: Renderer,
-    pub ctx: Ctx,
+    // Scene, input and drawing, through the OpenGL backend of the window.
+    pub viewer: Viewer<GliumBackend>,
+    // Saved after the first frame, then the application exits.
+    pub screenshot: Option<PathBuf>,
+    // Error that stopped the event loop, returned by run once the window is closed.
+    pub error: Option<String>,
 }
 
 impl App {
-    pub fn run() {
+    pub fn run(args: Args) -> Result<(), String> {
         utils::print_help();
-        match EventLoop::new() {
-            Ok(ev) => {
-                ev.set_control_flow(ControlFlow::Wait);
-                ev.set_control_flow(ControlFlow::Poll);
-                let mut ctx = Ctx::new();
-                let (window, display) = glium::backend::glutin::SimpleWindowBuilder::new()
-                    .with_inner_size(ctx.width, ctx.height)
-                    .with_title("Super Scop :O")
-                    .build(&ev);
-                let renderer = Renderer::new(&display, & mut ctx);
-                let mut app = Self {
-                    window: window,
-                    display: display,
-                    renderer: renderer,
-                    ctx: ctx,
-                };
-                let _ = ev.run_app(&mut app);
-            },
-            Err(e) => {
-                println!("Error: Impossible to init eventloop. {}", e);
-                std::process::exit(1);
-            }
+        let ev = EventLoop::new().map_err(|e| format!("Error: Impossible to init eventloop. {}", e))?;
+        ev.set_control_flow(ControlFlow::Wait);
+        let ctx = args.ctx()?;
+        let (window, display) = glium::backend::glutin::SimpleWindowBuilder::new()
+            .with_inner_size(ctx.width, ctx.height)
+            .with_title("Super Scop :O")
+            .build(&ev);
+        let backend = GliumBackend::new(display.get_context().clone(), &ctx)?;
+        let mut viewer = Viewer::new(ctx, backend)?;
+        viewer.scene_file = args.scene;
+        let mut app = Self {
+            window,
+            display,
+            viewer,
+            screenshot: args.screenshot,
+            error: None,
+        };
+        let _ = ev.run_app(&mut app);
+        app.error.map_or(Ok(()), Err)
+    }
+
+    // The viewer may ask for another size, an opened scene has its own.
+    // The framebuffer keeps its size until the window is resized.
+    fn fit_window(&mut self) {
+        let ctx = &mut self.viewer.ctx;
+        if (ctx.width, ctx.height) == self.display.get_framebuffer_dimensions() {
+            return;
+        }
+        if let Some(size) = self.window.request_inner_size(PhysicalSize::new(ctx.width, ctx.height)) {
+            self.display.resize(size.into());
         }
+        (ctx.width, ctx.height) = self.display.get_framebuffer_dimensions();
     }
 }
 
@@ -61,8 +82,22 @@ impl ApplicationHandler for App {
     fn resumed(&mut self, _event_loop: &ActiveEventLoop) {
         println!("The Application is starting !");
     }
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
-        self.window.request_redraw();
+    // Redraws continuously only while something moves, otherwise waits for events.
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if self.viewer.poll() {
+            self.window.request_redraw();
+        }
+        if self.viewer.is_animating() {
+            event_loop.set_control_flow(ControlFlow::Poll);
+            self.window.request_redraw();
+        } else if self.viewer.loader.is_busy() {
+            event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + LOAD_POLL_INTERVAL));
+            self.window.request_redraw();
+        } else {
+            // Wakes up to notice the shader files changes.
+            event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + POLL_INTERVAL));
+            self.viewer.idle();
+        }
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
@@ -72,8 +107,14 @@ impl ApplicationHandler for App {
                 event_loop.exit();
             },
             WindowEvent::RedrawRequested => {
-                self.renderer.draw_obj(&self.display, & mut self.ctx);
-                self.window.request_redraw();
+                if let Err(err) = self.viewer.redraw() {
+                    println!("{}", err);
+                }
+                if let Some(filepath) = self.screenshot.take() {
+                    // The resources are still released when the capture fails.
+                    self.error = self.viewer.screenshot(&filepath).err();
+                    event_loop.exit();
+                }
             },
             WindowEvent::KeyboardInput { device_id: _device_id, event, is_synthetic } => {
                 if is_synthetic {
@@ -84,137 +125,28 @@ impl ApplicationHandler for App {
                     state: ElementState::Pressed,
                     ..
                 } = event {
-                    match key_code {
-                        KeyCode::Escape => {
-                            println!("Escape key pressed - closing the application.");
-                            event_loop.exit();
-                        },
-                        KeyCode::Space => {
-                            self.ctx.rotation = !self.ctx.rotation;
-                        },
-                        KeyCode::KeyA => {
-                            if self.ctx.light_move {
-                                self.ctx.light[0] -= 0.25;
-                            } else {
-                                self.ctx.x_factor += 0.1;
-                            }
-                        },
-                        KeyCode::KeyD => {
-                            if self.ctx.light_move {
-                                self.ctx.light[0] += 0.25;
-                            } else {
-                                self.ctx.x_factor -= 0.1;
-                            }
-                        },
-                        KeyCode::KeyS => {
-                            if self.ctx.light_move {
-                                self.ctx.light[2] -= 0.25;
-                            } else {
-                                self.ctx.z_factor += 0.5;
-                            }
-                        },
-                        KeyCode::KeyW => {
-                            if self.ctx.light_move {
-                                self.ctx.light[2] += 0.25;
-                            } else {
-                                self.ctx.z_factor -= 0.5;
-                            }
-                        },
-                        KeyCode::ArrowDown => {
-                            if self.ctx.light_move {
-                                self.ctx.light[1] -= 0.25;
-                            } else {
-                                self.ctx.y_factor += 0.1;
-                            }
-                        },
-                        KeyCode::ArrowUp => {
-                            if self.ctx.light_move {
-                                self.ctx.light[1] += 0.25;
-                            } else {
-                                self.ctx.y_factor -= 0.1;
-                            }
-                        },
-                        KeyCode::ArrowLeft => {
-                            let speed: f32 = self.ctx.speed_factor;
-                            if speed < 0.4 {
-                                self.ctx.speed_factor += 0.005;
-                            } else {
-                                self.ctx.speed_factor = 0.4;
-                            }
-                        }
-                        KeyCode::ArrowRight => {
-                            let speed: f32 = self.ctx.speed_factor;
-                            if speed > -0.4 {
-                                self.ctx.speed_factor -= 0.005;
-                            } else {
-                                self.ctx.speed_factor = -0.4;
-                            }
-                        }
-                        KeyCode::KeyP => {
-                            self.renderer.shaders_switch(& mut self.ctx);             
-                        },
-                        KeyCode::KeyL => {
-                            self.ctx.light_move = !self.ctx.light_move
-                        },
-                        KeyCode::KeyB => {
-                            self.ctx.backface = !self.ctx.backface;
-                        }
-                        KeyCode::KeyV => {
-                            if self.ctx.polmode == 2 {
-                                self.ctx.polmode = 0;
-                            } else {
-                                self.ctx.polmode += 1;
-                            }
-                        },
-                        KeyCode::KeyH => {
-                            utils::print_help();
-                        }
-                        KeyCode::KeyX => {
-                            self.ctx.speed_factor *= -1.0;
-                        },
-                        KeyCode::KeyT => {
-                            self.ctx.texture = !self.ctx.texture;
-                        },
-                        _ => {}
+                    if key_code == KeyCode::Escape {
+                        println!("Escape key pressed - closing the application.");
+                        event_loop.exit();
+                        return;
                     }
+                    self.viewer.key(key_code);
+                    self.fit_window();
+                    self.window.request_redraw();
                 }
             },
             WindowEvent::Resized(window_size) => {
                 self.display.resize(window_size.into());
-                (self.ctx.width, self.ctx.height) = self.display.get_framebuffer_dimensions();
+                (self.viewer.ctx.width, self.viewer.ctx.height) = self.display.get_framebuffer_dimensions();
+                self.window.request_redraw();
+            },
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.viewer.modifiers = modifiers.state();
             },
             WindowEvent::DroppedFile(path_buf) => {
-                enum ParsedResult {
-                    Obj(Obj),
-                    Images(Images),
-                }
-                if let Some(filepath) = path_buf.to_str() {
-                    let filepath_lower = filepath.to_lowercase();
-                    if !filepath_lower.ends_with(".obj") && !filepath_lower.ends_with(".ppm") {
-                        println!("Error: Unsupported file extension.");
-                    } else {
-                        let ret: Result<ParsedResult, _> = if filepath_lower.ends_with(".obj") {
-                            parser::obj_parser(filepath).map(ParsedResult::Obj)
-                        } else {
-                            Images::new(&self.display, filepath).map(ParsedResult::Images)
-                        };
-                        match ret {
-                            Ok(ParsedResult::Obj(obj)) => {
-                                self.ctx.obj = obj;
-                                self.renderer.mesh = Mesh::get_mesh_vector(&mut self.ctx);
-                            },
-                            Ok(ParsedResult::Images(img)) => {
-                                self.renderer.img = img;
-                                self.renderer.mesh = Mesh::get_mesh_vector(&mut self.ctx);
-                            },
-                            Err(err) => {
-                                println!("Error while parsing: {:?}", err);
-                            }
-                        }
-                    }
-                } else {
-                    println!("Error: Invalid file path.");
-                }
+                self.viewer.drop_file(&path_buf);
+                self.fit_window();
+                self.window.request_redraw();
             },
             _ => {}
         }
@@ -0,0 +1,137 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant}
+};
+
+use super::{
+    parser::Obj,
+    scene::Scene,
+    watcher::FileWatcher
+};
+
+// Time a changed file must stay the same before it is read again, a file still being written is left alone.
+pub const DEBOUNCE: Duration = Duration::from_millis(500);
+
+// Files read with a model: the .obj, its .mtl and the maps of its materials.
+pub fn model_files(obj: &Obj) -> Vec<&str> {
+    let maps = obj.materials.iter()
+        .flat_map(|material| [&material.map_kd, &material.map_pr, &material.map_pm, &material.map_ao, &material.map_bump])
+        .flatten();
+    [&obj.path].into_iter()
+        .chain(&obj.mtlpath)
+        .chain(maps)
+        .map(String::as_str)
+        .filter(|path| !path.is_empty())
+        .collect()
+}
+
+// Files the scene is made of, the models with their materials and the textures of the objects.
+pub fn scene_files(scene: &Scene) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for object in &scene.objects {
+        for file in model_files(&object.obj).into_iter().chain([object.texture.as_str()]) {
+            let path = PathBuf::from(file);
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
+
+// Watches the files of the scene and gives the changed ones once they stopped changing for DEBOUNCE.
+#[derive(Default)]
+pub struct AssetWatcher {
+    watcher: FileWatcher,
+    paths: Vec<PathBuf>,
+    // Changed files and the time of their last change.
+    settling: Vec<(PathBuf, Instant)>,
+}
+
+impl AssetWatcher {
+    pub fn new() -> Self {
+        Self {
+            watcher: FileWatcher::new(),
+            paths: Vec::new(),
+            settling: Vec::new(),
+        }
+    }
+
+    // Follows the files of the scene, the new ones are watched from their current state.
+    pub fn update(&mut self, scene: &Scene) {
+        let paths = scene_files(scene);
+        if paths != self.paths {
+            self.watcher.retain(&paths);
+            for path in &paths {
+                self.watcher.watch(path);
+            }
+            self.settling.retain(|(path, _)| paths.contains(path));
+            self.paths = paths;
+        }
+    }
+
+    // Files changed at least DEBOUNCE before now and not since.
+    pub fn poll(&mut self, now: Instant) -> Vec<PathBuf> {
+        for path in self.watcher.changed() {
+            match self.settling.iter_mut().find(|(settling, _)| *settling == path) {
+                Some((_, time)) => *time = now,
+                None => self.settling.push((path, now))
+            }
+        }
+        let (ready, settling): (Vec<_>, Vec<_>) = std::mem::take(&mut self.settling)
+            .into_iter()
+            .partition(|(_, time)| now.duration_since(*time) >= DEBOUNCE);
+        self.settling = settling;
+        ready.into_iter().map(|(path, _)| path).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+    use crate::app::{parser::obj_parser, scene::{SceneObject, Transform}};
+
+    const MODEL: &str = "mtllib box.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl paint\nf 1 2 3\n";
+
+    // Model with a material and its map, in a folder of its own.
+    fn model(name: &str) -> (PathBuf, Obj) {
+        let dir = std::env::temp_dir().join(name);
+        create_dir_all(&dir).unwrap();
+        write(dir.join("box.mtl"), "newmtl paint\nmap_Kd paint.ppm\n").unwrap();
+        let filepath = dir.join("box.obj");
+        write(&filepath, MODEL).unwrap();
+        let obj = obj_parser(filepath.to_str().unwrap()).unwrap();
+        (dir, obj)
+    }
+
+    #[test]
+    fn models_bring_their_materials() {
+        let (dir, obj) = model("scop_model_files");
+        let files: Vec<PathBuf> = model_files(&obj).into_iter().map(PathBuf::from).collect();
+        assert_eq!(files, [dir.join("box.obj"), dir.join("box.mtl"), dir.join("paint.ppm")]);
+        let mut scene = Scene::new(SceneObject::new(obj.clone(), Transform::new([0.0; 3], 0.0)));
+        scene.add(obj);
+        scene.objects[1].texture = "./obj/Texture/metal.ppm".to_string();
+        assert_eq!(scene_files(&scene).len(), 5);
+    }
+
+    #[test]
+    fn changes_wait_until_the_file_is_left_alone() {
+        let (dir, obj) = model("scop_debounce");
+        let filepath = dir.join("box.obj");
+        let mut assets = AssetWatcher::new();
+        assets.update(&Scene::new(SceneObject::new(obj, Transform::new([0.0; 3], 0.0))));
+        let start = Instant::now();
+        assert!(assets.poll(start).is_empty());
+
+        write(&filepath, &MODEL[..20]).unwrap();
+        assert!(assets.poll(start).is_empty());
+        // Still being written, the wait starts again.
+        write(&filepath, MODEL).unwrap();
+        assert!(assets.poll(start + DEBOUNCE / 2).is_empty());
+        assert!(assets.poll(start + DEBOUNCE).is_empty());
+        assert_eq!(assets.poll(start + DEBOUNCE / 2 + DEBOUNCE), [filepath]);
+        assert!(assets.poll(start + DEBOUNCE * 3).is_empty());
+    }
+}
@@ -0,0 +1,378 @@
+use std::{collections::HashMap, rc::Rc};
+
+use glium::{backend::Context, texture::RawImage2d};
+
+use super::{
+    ctx::Ctx,
+    hud::Hud,
+    image::Image,
+    mesh::{DrawRange, Mesh},
+    rendering::{Renderer, DEFAULT_MATCAP},
+    shading::ShadingRegistry,
+    software::{self, CpuTexture, SoftwareObject, SoftwareShading}
+};
+
+// State of the model pass, set once before drawing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipelineState {
+    pub shading: String,
+    pub backface_culling: bool,
+    // 0 fill, 1 line, 2 point.
+    pub polygon_mode: i32,
+    // No background: skybox, ground and grid are skipped and the clear color is transparent.
+    pub transparent: bool,
+}
+
+impl PipelineState {
+    pub fn from_ctx(ctx: &Ctx, transparent: bool) -> Self {
+        Self {
+            shading: ctx.shading.clone(),
+            backface_culling: ctx.backface,
+            polygon_mode: ctx.polmode,
+            transparent,
+        }
+    }
+}
+
+fn unsupported(feature: &str) -> Result<(), String> {
+    Err(format!("Error: {} need the OpenGL renderer.", feature))
+}
+
+// What the viewer needs from a renderer, on the GPU through glium or on the CPU.
+// The optional features do nothing by default.
+pub trait RenderBackend {
+    // Builds the mesh of every object of ctx.scene and loads their textures.
+    fn upload_mesh(&mut self, ctx: &mut Ctx) -> Result<(), String>;
+    // Mesh built away from the backend for the object at index, the texture is kept.
+    fn set_mesh(&mut self, ctx: &Ctx, index: usize, mesh: Vec<Mesh>, ranges: Vec<DrawRange>);
+    // Object last added to ctx.scene, with the mesh built for it.
+    fn add_object(&mut self, ctx: &Ctx, mesh: Vec<Mesh>, ranges: Vec<DrawRange>) -> Result<(), String>;
+    fn remove_object(&mut self, index: usize);
+    // Texture of the active object.
+    fn upload_texture(&mut self, ctx: &mut Ctx, filepath: &str) -> Result<(), String>;
+    // Texture of an object from an image already read.
+    fn set_texture(&mut self, ctx: &mut Ctx, index: usize, filepath: &str, img: RawImage2d<'static, u8>, dim: (u32, u32)) -> Result<(), String>;
+    // Shading modes, the current one is drawn once loaded.
+    fn registry(&mut self) -> &mut ShadingRegistry;
+    fn load_shading(&mut self) {}
+    // Fails when the backend cannot draw the shading mode.
+    fn set_pipeline(&mut self, state: &PipelineState) -> Result<(), String>;
+    // Draws the scene scale times larger than ctx.width by ctx.height.
+    fn draw(&mut self, ctx: &mut Ctx, scale: u32) -> Result<(), String>;
+    // Image of the last draw.
+    fn read_back(&mut self) -> Result<Image, String>;
+    // Draws the scene and the interface to the window.
+    fn present(&mut self, ctx: &mut Ctx, hud: &mut Hud) -> Result<(), String>;
+
+    fn animate(&mut self, _dt: f32) {}
+    fn is_animating(&self) -> bool {
+        false
+    }
+    // Shading files edited on disk, true when the mode was reloaded.
+    fn poll_shaders(&mut self, _ctx: &mut Ctx) -> bool {
+        false
+    }
+    fn export_shaders(&self) -> Result<(), String> {
+        unsupported("Shader files")
+    }
+    // Next texture of the folder of the active one.
+    fn cycle_texture(&mut self, _ctx: &mut Ctx, _step: i32) -> Result<(), String> {
+        unsupported("Texture folders")
+    }
+    // Texture changes show at once after this.
+    fn skip_fade(&mut self, _index: usize) {}
+    fn fade_duration(&self) -> f32 {
+        0.0
+    }
+    fn set_fade_duration(&mut self, _duration: f32) {}
+    fn load_environment(&mut self, _filepath: &str) -> Result<(), String> {
+        unsupported("Environment maps")
+    }
+    fn load_matcap(&mut self, _filepath: &str) -> Result<(), String> {
+        unsupported("Matcaps")
+    }
+    fn load_normal_map(&mut self, _filepath: &str) -> Result<(), String> {
+        unsupported("Normal maps")
+    }
+}
+
+// OpenGL renderer drawing offscreen, the context may be a window or a headless one.
+pub struct GliumBackend {
+    pub context: Rc<Context>,
+    pub renderer: Renderer,
+    state: PipelineState,
+    image: Option<Image>,
+}
+
+impl GliumBackend {
+    pub fn new(context: Rc<Context>, ctx: &Ctx) -> Result<Self, String> {
+        let renderer = Renderer::new(&context, ctx)?;
+        if let Some(error) = &renderer.reloader.error {
+            return Err(error.clone());
+        }
+        Ok(Self {
+            context,
+            renderer,
+            state: PipelineState::from_ctx(ctx, false),
+            image: None,
+        })
+    }
+}
+
+impl RenderBackend for GliumBackend {
+    fn upload_mesh(&mut self, ctx: &mut Ctx) -> Result<(), String> {
+        self.renderer.load_scene(&self.context, ctx)
+    }
+
+    fn set_mesh(&mut self, ctx: &Ctx, index: usize, mesh: Vec<Mesh>, ranges: Vec<DrawRange>) {
+        self.renderer.load_mesh(&self.context, ctx, index, mesh, ranges);
+    }
+
+    fn add_object(&mut self, ctx: &Ctx, mesh: Vec<Mesh>, ranges: Vec<DrawRange>) -> Result<(), String> {
+        self.renderer.add_object(&self.context, ctx, mesh, ranges)
+    }
+
+    fn remove_object(&mut self, index: usize) {
+        self.renderer.remove_object(index);
+    }
+
+    // Texture changes of the window fade, see skip_fade.
+    fn upload_texture(&mut self, ctx: &mut Ctx, filepath: &str) -> Result<(), String> {
+        self.renderer.load_texture(&self.context, ctx, filepath)
+    }
+
+    fn set_texture(&mut self, ctx: &mut Ctx, index: usize, filepath: &str, img: RawImage2d<'static, u8>, dim: (u32, u32)) -> Result<(), String> {
+        self.renderer.set_texture(&self.context, ctx, index, filepath, img, dim)
+    }
+
+    fn registry(&mut self) -> &mut ShadingRegistry {
+        &mut self.renderer.shading
+    }
+
+    fn load_shading(&mut self) {
+        self.renderer.reloader.load(&self.context, &self.renderer.shading);
+    }
+
+    fn set_pipeline(&mut self, state: &PipelineState) -> Result<(), String> {
+        if self.renderer.shading.current().name != state.shading {
+            self.renderer.shading.select(&state.shading)?;
+            self.renderer.reloader.load(&self.context, &self.renderer.shading);
+        }
+        if let Some(error) = &self.renderer.reloader.error {
+            return Err(error.clone());
+        }
+        self.state = state.clone();
+        Ok(())
+    }
+
+    // The CPU rasterizer of the window draws instead when ctx.software is set.
+    fn draw(&mut self, ctx: &mut Ctx, scale: u32) -> Result<(), String> {
+        self.image = Some(if ctx.software {
+            self.renderer.render_software(ctx, scale, &self.state)
+        } else {
+            self.renderer.capture(&self.context, ctx, scale, &self.state)?
+        });
+        Ok(())
+    }
+
+    fn read_back(&mut self) -> Result<Image, String> {
+        self.image.take().ok_or("Error: Nothing was drawn.".to_string())
+    }
+
+    fn present(&mut self, ctx: &mut Ctx, hud: &mut Hud) -> Result<(), String> {
+        self.renderer.draw_obj(&self.context, ctx, hud)
+    }
+
+    fn animate(&mut self, dt: f32) {
+        self.renderer.animate(dt);
+    }
+
+    fn is_animating(&self) -> bool {
+        self.renderer.is_animating()
+    }
+
+    fn poll_shaders(&mut self, ctx: &mut Ctx) -> bool {
+        self.renderer.poll_shaders(&self.context, ctx)
+    }
+
+    fn export_shaders(&self) -> Result<(), String> {
+        self.renderer.export_shaders()
+    }
+
+    fn cycle_texture(&mut self, ctx: &mut Ctx, step: i32) -> Result<(), String> {
+        self.renderer.cycle_texture(&self.context, ctx, step)
+    }
+
+    fn skip_fade(&mut self, index: usize) {
+        self.renderer.objects[index].textures.skip_fade();
+    }
+
+    fn fade_duration(&self) -> f32 {
+        self.renderer.fade_duration()
+    }
+
+    fn set_fade_duration(&mut self, duration: f32) {
+        self.renderer.set_fade_duration(duration);
+    }
+
+    fn load_environment(&mut self, filepath: &str) -> Result<(), String> {
+        self.renderer.ibl.load(&self.context, filepath)
+    }
+
+    fn load_matcap(&mut self, filepath: &str) -> Result<(), String> {
+        self.renderer.load_matcap(&self.context, filepath)
+    }
+
+    fn load_normal_map(&mut self, filepath: &str) -> Result<(), String> {
+        self.renderer.load_normal_map(&self.context, filepath)
+    }
+}
+
+// CPU rasterizer, needs no OpenGL context at all.
+pub struct CpuBackend {
+    // One per object of ctx.scene, in the same order.
+    pub meshes: Vec<Vec<Mesh>>,
+    pub ranges: Vec<Vec<DrawRange>>,
+    // Read once per file.
+    pub textures: HashMap<String, CpuTexture>,
+    pub matcap: Option<CpuTexture>,
+    pub registry: ShadingRegistry,
+    shading: SoftwareShading,
+    state: Option<PipelineState>,
+    image: Option<Image>,
+}
+
+impl CpuBackend {
+    pub fn new() -> Self {
+        Self {
+            meshes: Vec::new(),
+            ranges: Vec::new(),
+            textures: HashMap::new(),
+            matcap: match CpuTexture::load(DEFAULT_MATCAP) {
+                Ok(matcap) => Some(matcap),
+                Err(err) => {
+                    println!("Warning: {} ignored. {}", DEFAULT_MATCAP, err);
+                    None
+                }
+            },
+            registry: ShadingRegistry::new(),
+            shading: SoftwareShading::Gray,
+            state: None,
+            image: None,
+        }
+    }
+}
+
+impl Default for CpuBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderBackend for CpuBackend {
+    fn upload_mesh(&mut self, ctx: &mut Ctx) -> Result<(), String> {
+        for object in &ctx.scene.objects {
+            if !self.textures.contains_key(&object.texture) {
+                self.textures.insert(object.texture.clone(), CpuTexture::load(&object.texture)?);
+            }
+        }
+        self.meshes = ctx.scene.objects.iter_mut().map(|object| Mesh::get_mesh_vector(&mut object.obj)).collect();
+        self.ranges = ctx.scene.objects.iter().map(|object| Mesh::get_draw_ranges(&object.obj)).collect();
+        Ok(())
+    }
+
+    fn set_mesh(&mut self, _ctx: &Ctx, index: usize, mesh: Vec<Mesh>, ranges: Vec<DrawRange>) {
+        self.meshes[index] = mesh;
+        self.ranges[index] = ranges;
+    }
+
+    fn add_object(&mut self, ctx: &Ctx, mesh: Vec<Mesh>, ranges: Vec<DrawRange>) -> Result<(), String> {
+        if let Some(object) = ctx.scene.objects.last().filter(|object| !self.textures.contains_key(&object.texture)) {
+            self.textures.insert(object.texture.clone(), CpuTexture::load(&object.texture)?);
+        }
+        self.meshes.push(mesh);
+        self.ranges.push(ranges);
+        Ok(())
+    }
+
+    fn remove_object(&mut self, index: usize) {
+        self.meshes.remove(index);
+        self.ranges.remove(index);
+    }
+
+    fn upload_texture(&mut self, ctx: &mut Ctx, filepath: &str) -> Result<(), String> {
+        if !self.textures.contains_key(filepath) {
+            self.textures.insert(filepath.to_string(), CpuTexture::load(filepath)?);
+        }
+        ctx.scene.active_mut().texture = filepath.to_string();
+        Ok(())
+    }
+
+    // Replaces the copy of the file read before, for every object using it.
+    fn set_texture(&mut self, ctx: &mut Ctx, index: usize, filepath: &str, img: RawImage2d<'static, u8>, dim: (u32, u32)) -> Result<(), String> {
+        self.textures.insert(filepath.to_string(), CpuTexture::from_raw(filepath, img, dim));
+        ctx.scene.objects[index].texture = filepath.to_string();
+        Ok(())
+    }
+
+    fn registry(&mut self) -> &mut ShadingRegistry {
+        &mut self.registry
+    }
+
+    fn set_pipeline(&mut self, state: &PipelineState) -> Result<(), String> {
+        self.shading = SoftwareShading::from_name(&state.shading)?;
+        if self.registry.current().name != state.shading {
+            self.registry.select(&state.shading)?;
+        }
+        self.state = Some(state.clone());
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Ctx, scale: u32) -> Result<(), String> {
+        let state = self.state.as_ref().ok_or("Error: No pipeline state set before drawing.")?;
+        let (width, height) = (ctx.width * scale, ctx.height * scale);
+        let objects: Vec<SoftwareObject> = self.meshes.iter().zip(&self.ranges).zip(&ctx.scene.objects)
+            .map(|((mesh, ranges), object)| SoftwareObject {
+                mesh,
+                ranges,
+                texture: self.textures.get(&object.texture),
+            })
+            .collect();
+        self.image = Some(software::render(&objects, ctx, self.shading, state, (width, height), self.matcap.as_ref()));
+        Ok(())
+    }
+
+    fn read_back(&mut self) -> Result<Image, String> {
+        self.image.take().ok_or("Error: Nothing was drawn.".to_string())
+    }
+
+    fn load_matcap(&mut self, filepath: &str) -> Result<(), String> {
+        self.matcap = Some(CpuTexture::load(filepath)?);
+        Ok(())
+    }
+
+    // There is no window, the frame is kept for read_back and the interface is left out.
+    fn present(&mut self, ctx: &mut Ctx, hud: &mut Hud) -> Result<(), String> {
+        hud.frame();
+        self.set_pipeline(&PipelineState::from_ctx(ctx, false))?;
+        self.draw(ctx, 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{headless::create_context, parser::obj_parser};
+
+    #[test]
+    fn renderer_errors_reach_the_caller() {
+        let Ok(context) = create_context() else {
+            println!("No EGL context, skipped.");
+            return;
+        };
+        let mut ctx = Ctx::with_obj(obj_parser("./obj/42.obj").unwrap());
+        ctx.shading = "sepia".to_string();
+        let err = GliumBackend::new(context, &ctx).err().unwrap();
+        assert!(err.starts_with("Error: Unknown shading mode sepia"), "{}", err);
+    }
+}
@@ -0,0 +1,211 @@
+use std::path::PathBuf;
+
+use super::{
+    ctx::{Ctx, DEFAULT_OBJ},
+    parser::obj_parser,
+    scene_file::SceneFile,
+    shading::ShadingRegistry
+};
+
+pub fn print_usage() {
+    println!("Usage: scop [options]");
+    println!("  --shading <name>     Start with the named shading mode");
+    println!("  --list-shading       Print the available shading modes");
+    println!("  --screenshot <file>  Save the first frame to a .png or .ppm file and exit");
+    println!("  --supersample <n>    Screenshot n times larger than the window (1 to 8)");
+    println!("  --transparent        Screenshot without background");
+    println!("  --scene <file>       Open a saved scene, the other options change it");
+    println!("  --model <file>       Load this .obj instead of the default one, repeat to add objects side by side");
+    println!("  --texture <file>     Load this .ppm texture on every object instead of the default one");
+    println!("  --size <w>x<h>       Window or image size in pixels");
+    println!("  --position <x,y,z>   Object position in front of the camera, several models are centered on it");
+    println!("  --angle <degrees>    Starting rotation of the objects");
+    println!("  --headless           Render without window, to the --screenshot or --gif file, then exit");
+    println!("  --frames <n>         Headless turntable of n images numbered after the file name");
+    println!("  --path <file>        Headless recording along the keyframes of a camera path file");
+    println!("  --gif <file>         Headless recording saved as an animated GIF too");
+    println!("  --fps <n>            Frames per second of the recording (default 24)");
+    println!("  --software           Draw with the CPU rasterizer, headless without OpenGL at all");
+    println!("  -h, --help           Print this message");
+}
+
+#[derive(Default, Debug)]
+pub struct Args {
+    pub shading: Option<String>,
+    pub screenshot: Option<PathBuf>,
+    pub supersample: Option<u32>,
+    pub transparent: bool,
+    pub scene: Option<PathBuf>,
+    pub models: Vec<String>,
+    pub texture: Option<String>,
+    pub size: Option<(u32, u32)>,
+    pub position: Option<[f32; 3]>,
+    pub angle: Option<f32>,
+    pub headless: bool,
+    pub frames: Option<u32>,
+    pub path: Option<String>,
+    pub gif: Option<PathBuf>,
+    pub fps: Option<u32>,
+    pub software: bool,
+}
+
+fn parse_floats(value: &str, separator: char, count: usize) -> Option<Vec<f32>> {
+    let values = value.split(separator)
+        .map(|s| s.trim().parse::<f32>())
+        .collect::<Result<Vec<f32>, _>>()
+        .ok()?;
+    (values.len() == count && values.iter().all(|v| v.is_finite())).then_some(values)
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    // Informational flags print and exit right away.
+    pub fn parse_from<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--shading" => {
+                    parsed.shading = Some(args.next().ok_or("Error: --shading needs a mode name.")?);
+                },
+                "--screenshot" => {
+                    parsed.screenshot = Some(PathBuf::from(args.next().ok_or("Error: --screenshot needs a file path.")?));
+                },
+                "--supersample" => {
+                    let value = args.next().ok_or("Error: --supersample needs a factor.")?;
+                    match value.parse::<u32>() {
+                        Ok(factor) if (1..=8).contains(&factor) => parsed.supersample = Some(factor),
+                        _ => return Err(format!("Error: Invalid supersample factor {}, expected 1 to 8.", value))
+                    }
+                },
+                "--transparent" => {
+                    parsed.transparent = true;
+                },
+                "--scene" => {
+                    parsed.scene = Some(PathBuf::from(args.next().ok_or("Error: --scene needs a file path.")?));
+                },
+                "--model" => {
+                    parsed.models.push(args.next().ok_or("Error: --model needs a file path.")?);
+                },
+                "--texture" => {
+                    parsed.texture = Some(args.next().ok_or("Error: --texture needs a file path.")?);
+                },
+                "--size" => {
+                    let value = args.next().ok_or("Error: --size needs a size like 800x600.")?;
+                    match parse_floats(&value, 'x', 2).as_deref() {
+                        Some(&[width, height]) if width >= 1.0 && height >= 1.0 && width.fract() == 0.0 && height.fract() == 0.0 => {
+                            parsed.size = Some((width as u32, height as u32));
+                        },
+                        _ => return Err(format!("Error: Invalid size {}, expected <width>x<height>.", value))
+                    }
+                },
+                "--position" => {
+                    let value = args.next().ok_or("Error: --position needs x,y,z.")?;
+                    match parse_floats(&value, ',', 3).as_deref() {
+                        Some(&[x, y, z]) => parsed.position = Some([x, y, z]),
+                        _ => return Err(format!("Error: Invalid position {}, expected x,y,z.", value))
+                    }
+                },
+                "--angle" => {
+                    let value = args.next().ok_or("Error: --angle needs degrees.")?;
+                    match value.parse::<f32>() {
+                        Ok(angle) if angle.is_finite() => parsed.angle = Some(angle),
+                        _ => return Err(format!("Error: Invalid angle {}.", value))
+                    }
+                },
+                "--software" => {
+                    parsed.software = true;
+                },
+                "--headless" => {
+                    parsed.headless = true;
+                },
+                "--frames" => {
+                    let value = args.next().ok_or("Error: --frames needs a count.")?;
+                    match value.parse::<u32>() {
+                        Ok(frames) if frames >= 1 => parsed.frames = Some(frames),
+                        _ => return Err(format!("Error: Invalid frame count {}.", value))
+                    }
+                },
+                "--path" => {
+                    parsed.path = Some(args.next().ok_or("Error: --path needs a file path.")?);
+                },
+                "--gif" => {
+                    parsed.gif = Some(PathBuf::from(args.next().ok_or("Error: --gif needs a file path.")?));
+                },
+                "--fps" => {
+                    let value = args.next().ok_or("Error: --fps needs a rate.")?;
+                    match value.parse::<u32>() {
+                        Ok(fps) if (1..=100).contains(&fps) => parsed.fps = Some(fps),
+                        _ => return Err(format!("Error: Invalid frame rate {}, expected 1 to 100.", value))
+                    }
+                },
+                "--list-shading" => {
+                    for name in ShadingRegistry::new().names() {
+                        println!("{}", name);
+                    }
+                    std::process::exit(0);
+                },
+                "-h" | "--help" => {
+                    print_usage();
+                    std::process::exit(0);
+                },
+                _ => return Err(format!("Error: Unknown argument {}.", arg))
+            }
+        }
+        if parsed.scene.is_some() && !parsed.models.is_empty() {
+            return Err("Error: --model cannot be used with --scene.".to_string());
+        }
+        if parsed.headless && parsed.screenshot.is_none() && parsed.gif.is_none() {
+            return Err("Error: --headless needs --screenshot <file> or --gif <file>.".to_string());
+        }
+        let recording = [("--frames", parsed.frames.is_some()), ("--path", parsed.path.is_some()), ("--gif", parsed.gif.is_some()), ("--fps", parsed.fps.is_some())];
+        if let Some((flag, _)) = recording.iter().find(|(_, used)| *used && !parsed.headless) {
+            return Err(format!("Error: {} is only used with --headless.", flag));
+        }
+        Ok(parsed)
+    }
+
+    // Scene described by the arguments, with the models loaded.
+    pub fn ctx(&self) -> Result<Ctx, String> {
+        let (first, others) = match self.models.split_first() {
+            Some((first, others)) => (first.as_str(), others),
+            None => (DEFAULT_OBJ, &[][..])
+        };
+        let mut ctx = match &self.scene {
+            Some(filepath) => SceneFile::parse(filepath)?.ctx()?,
+            None => Ctx::with_obj(obj_parser(first)?)
+        };
+        if let Some(shading) = &self.shading {
+            ctx.shading = shading.clone();
+        }
+        if let Some(scale) = self.supersample {
+            ctx.screenshot_scale = scale;
+        }
+        ctx.screenshot_transparent = self.transparent;
+        ctx.software = self.software;
+        if let Some((width, height)) = self.size {
+            ctx.width = width;
+            ctx.height = height;
+        }
+        let object = ctx.scene.active_mut();
+        if let Some(position) = self.position {
+            object.transform.position = position;
+        }
+        if let Some(angle) = self.angle {
+            object.transform.angle = angle.to_radians().rem_euclid(std::f32::consts::TAU);
+        }
+        if let Some(texture) = &self.texture {
+            object.texture = texture.clone();
+        }
+        // The others take the angle and the texture of the first one.
+        if !others.is_empty() {
+            for model in others {
+                ctx.scene.add(obj_parser(model)?);
+            }
+            ctx.scene.active = 0;
+        }
+        Ok(ctx)
+    }
+}
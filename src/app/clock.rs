@@ -0,0 +1,160 @@
+use std::time::Instant;
+
+// Longest delta accepted in one tick, avoids a jump after the app stayed idle.
+pub const MAX_DELTA: f32 = 0.1;
+pub const DEFAULT_FIXED_STEP: f32 = 1.0 / 60.0;
+
+pub struct Clock {
+    last: Instant,
+    pub delta: f32,
+    pub elapsed: f32,
+    pub paused: bool,
+    pub time_scale: f32,
+    pub fixed_step: Option<f32>,
+    accumulator: f32,
+    idle: bool,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            last: Instant::now(),
+            delta: 0.0,
+            elapsed: 0.0,
+            paused: false,
+            time_scale: 1.0,
+            fixed_step: None,
+            accumulator: 0.0,
+            idle: false,
+        }
+    }
+
+    // Measures the real time since the last tick, scaled by time_scale.
+    pub fn tick(&mut self) {
+        self.tick_at(Instant::now());
+    }
+
+    pub fn tick_at(&mut self, now: Instant) {
+        if self.idle {
+            self.last = now;
+            self.idle = false;
+        }
+        let real = now.duration_since(self.last).as_secs_f32().min(MAX_DELTA);
+        self.last = now;
+        self.delta = if self.paused { 0.0 } else { real * self.time_scale };
+        self.elapsed += self.delta;
+        self.accumulator += self.delta;
+    }
+
+    // Returns the next simulation step of the current tick, in seconds.
+    // Without fixed step the whole delta is consumed at once.
+    pub fn step(&mut self) -> Option<f32> {
+        match self.fixed_step {
+            Some(step) => {
+                if self.accumulator >= step {
+                    self.accumulator -= step;
+                    Some(step)
+                } else {
+                    None
+                }
+            },
+            None => {
+                if self.accumulator > 0.0 {
+                    let dt = self.accumulator;
+                    self.accumulator = 0.0;
+                    Some(dt)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    // Nothing is animated until the next tick, the time spent waiting is not counted.
+    pub fn idle(&mut self) {
+        self.idle = true;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        println!("Clock {}", if self.paused { "paused" } else { "resumed" });
+    }
+
+    pub fn set_time_scale(&mut self, scale: f32) {
+        self.time_scale = scale.clamp(0.125, 8.0);
+        println!("Time scale: x{}", self.time_scale);
+    }
+
+    pub fn toggle_fixed_step(&mut self) {
+        self.fixed_step = match self.fixed_step {
+            Some(_) => None,
+            None => Some(DEFAULT_FIXED_STEP)
+        };
+        self.accumulator = 0.0;
+        match self.fixed_step {
+            Some(step) => println!("Fixed time step: {:.4}s", step),
+            None => println!("Variable time step")
+        }
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // Ticks the given milliseconds after the previous tick and returns the steps it gives.
+    fn advance(clock: &mut Clock, millis: u64) -> Vec<f32> {
+        let now = clock.last + Duration::from_millis(millis);
+        clock.tick_at(now);
+        std::iter::from_fn(|| clock.step()).collect()
+    }
+
+    #[test]
+    fn long_frames_are_clamped_and_scaled() {
+        let mut clock = Clock::new();
+        advance(&mut clock, 5000);
+        assert_eq!(clock.delta, MAX_DELTA);
+        clock.set_time_scale(2.0);
+        advance(&mut clock, 40);
+        assert!((clock.delta - 0.08).abs() < 1e-6);
+        clock.set_time_scale(100.0);
+        assert_eq!(clock.time_scale, 8.0);
+    }
+
+    #[test]
+    fn pause_stops_the_time_and_idle_skips_the_wait() {
+        let mut clock = Clock::new();
+        advance(&mut clock, 50);
+        let elapsed = clock.elapsed;
+        clock.toggle_pause();
+        assert!(advance(&mut clock, 50).is_empty());
+        assert_eq!((clock.delta, clock.elapsed), (0.0, elapsed));
+        // Resuming counts from the last tick, not from the pause.
+        clock.toggle_pause();
+        advance(&mut clock, 20);
+        assert!((clock.delta - 0.02).abs() < 1e-6);
+        clock.idle();
+        advance(&mut clock, 3000);
+        assert_eq!(clock.delta, 0.0);
+    }
+
+    #[test]
+    fn fixed_steps_carry_the_remainder() {
+        let mut clock = Clock::new();
+        assert_eq!(advance(&mut clock, 30).len(), 1);
+        clock.fixed_step = Some(0.02);
+        assert_eq!(advance(&mut clock, 50), [0.02, 0.02]);
+        // 10ms were left, with 35ms more there is room for two steps again.
+        assert_eq!(advance(&mut clock, 35), [0.02, 0.02]);
+        assert!(advance(&mut clock, 10).is_empty());
+        assert_eq!(advance(&mut clock, 10).len(), 1);
+        assert!(advance(&mut clock, 0).is_empty());
+    }
+}
@@ -1,57 +1,195 @@
-use super::parser::{
-    obj_parser,
-    Obj
+use std::path::{Path, PathBuf};
+
+use super::{
+    clock::Clock,
+    debug::DebugView,
+    light::{default_lights, Light},
+    parser::{
+        obj_parser,
+        Obj
+    },
+    scene::{Scene, SceneObject, Transform}
 };
 
+pub const DEFAULT_OBJ: &str = "./obj/boat.obj";
+
+// Clear color behind the scene, linear.
+pub const BACKGROUND_COLOR: [f32; 3] = [0.0, 5.0 / 255.0, 16.0 / 255.0];
+
+// Texture on/off transition speed, in mix units per second.
+pub const MIX_SPEED: f32 = 3.0;
+// Rotation speed limit, in radians per second.
+pub const MAX_SPEED: f32 = 24.0;
+
+// Supersampling factors cycled by key, the captures are this many times larger than the window.
+pub const SCREENSHOT_SCALES: [u32; 3] = [1, 2, 4];
+pub const SCREENSHOT_DIR: &str = "./screenshots";
+
+// Colors of the wireframe overlay, cycled by key.
+pub const WIREFRAME_COLORS: [[f32; 3]; 4] = [
+    [0.0, 0.0, 0.0],
+    [1.0, 1.0, 1.0],
+    [1.0, 0.5, 0.0],
+    [0.0, 1.0, 0.3]
+];
+
 pub struct Ctx {
     pub width: u32,
     pub height: u32,
+    // Vertical field of view of the camera, in radians.
+    pub fov: f32,
+    pub background: [f32; 3],
     pub rotation: bool,
-    pub x_factor: f32,
-    pub y_factor: f32,
-    pub z_factor: f32,
-    pub rot_speed: f32,
-    pub shading: u32,
+    pub shading: String,
     pub backface: bool,
     pub polmode: i32,
     pub speed_factor: f32,
-    pub obj: Obj,
+    pub scene: Scene,
     pub light_move: bool,
-    pub light: [f32; 3],
+    pub lights: Vec<Light>,
+    pub selected_light: usize,
+    pub gizmos: bool,
+    pub shadows: bool,
+    pub shadow_bias: f32,
+    pub shadow_resolution: u32,
+    pub ground: bool,
+    pub environment: bool,
+    pub env_rotation: f32,
+    pub exposure: f32,
+    pub normal_mapping: bool,
+    pub toon_bands: f32,
+    pub outline_width: f32,
+    pub tangent_frames: bool,
+    pub normal_lines: bool,
+    pub normal_length: f32,
+    pub debug_view: DebugView,
+    pub axis_gizmo: bool,
+    pub grid: bool,
+    pub bbox: bool,
+    pub software: bool,
+    pub screenshot_scale: u32,
+    pub screenshot_transparent: bool,
+    pub wireframe: bool,
+    pub wireframe_polygons: bool,
+    pub wireframe_width: f32,
+    pub wireframe_color: usize,
     pub texture: bool,
-    pub mix_factor: f32
+    pub mix_factor: f32,
+    pub clock: Clock
 }
 
 impl Ctx {
     pub fn new() -> Self {
+        match obj_parser(DEFAULT_OBJ) {
+            Ok(obj) => Self::with_obj(obj),
+            Err(err) => {
+                println!("{:?}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    pub fn with_obj(obj: Obj) -> Self {
+        Self::with_scene(Scene::new(SceneObject::new(obj, Transform::new([0.0, 0.0, 8.0], 0.0))))
+    }
+
+    pub fn with_scene(scene: Scene) -> Self {
         Self {
             width: 1080,
             height: 1080,
+            fov: std::f32::consts::FRAC_PI_2,
+            background: BACKGROUND_COLOR,
             rotation: true,
-            x_factor: 0.0,
-            y_factor: 0.0,
-            z_factor: 8.0,
-            rot_speed: 0.0,
-            shading: 1,
+            shading: "gray".to_string(),
             backface: true,
             polmode: 0,
-            speed_factor: 0.015,
-            obj: {
-                let obj_ret = obj_parser("./obj/boat.obj");
-                match obj_ret {
-                    Ok(obj) => obj,
-                    Err(err) => {
-                        println!("{:?}", err);
-                        std::process::exit(1);
-                    }
-                }
-            },
-            light: [0.0, 0.0, -0.5],
+            speed_factor: 0.9,
+            scene,
+            lights: default_lights(),
+            selected_light: 0,
+            gizmos: true,
+            shadows: true,
+            shadow_bias: 0.005,
+            shadow_resolution: 2048,
+            ground: true,
+            environment: true,
+            env_rotation: 0.0,
+            exposure: 1.0,
+            normal_mapping: true,
+            toon_bands: 4.0,
+            outline_width: 0.015,
+            tangent_frames: false,
+            normal_lines: false,
+            normal_length: 0.05,
+            debug_view: DebugView::Off,
+            axis_gizmo: true,
+            grid: true,
+            bbox: false,
+            software: false,
+            screenshot_scale: 1,
+            screenshot_transparent: false,
+            wireframe: false,
+            wireframe_polygons: true,
+            wireframe_width: 1.5,
+            wireframe_color: 0,
             light_move: false,
             mix_factor: 1.0,
-            texture: true
+            texture: true,
+            clock: Clock::new()
         }
     }
+
+    // Unused name in the screenshot folder, numbered after the existing captures.
+    pub fn screenshot_path(extension: &str) -> PathBuf {
+        let dir = Path::new(SCREENSHOT_DIR);
+        (1..).map(|index| dir.join(format!("capture_{}.{}", index, extension)))
+            .find(|path| !path.exists())
+            .unwrap()
+    }
+
+    // Lights without a direction of their own point at the center of the scene.
+    pub fn aim_lights(&mut self) {
+        let target = self.scene.center();
+        for light in &mut self.lights {
+            light.aim(target);
+        }
+    }
+
+    pub fn toggle_culling(&mut self) {
+        self.backface = !self.backface;
+    }
+
+    // Fill, line then point.
+    pub fn next_polygon_mode(&mut self) {
+        self.polmode = (self.polmode + 1) % 3;
+    }
+
+    pub fn selected_light(&mut self) -> &mut Light {
+        if self.selected_light >= self.lights.len() {
+            self.selected_light = 0;
+        }
+        &mut self.lights[self.selected_light]
+    }
+
+    // Advances every animated value by dt seconds.
+    pub fn animate(&mut self, dt: f32) {
+        if self.rotation {
+            for object in &mut self.scene.objects {
+                object.transform.rotate(self.speed_factor * dt);
+            }
+        }
+        if self.texture && self.mix_factor < 1.0 {
+            self.mix_factor = (self.mix_factor + MIX_SPEED * dt).min(1.0);
+        } else if !self.texture && self.mix_factor > 0.0 {
+            self.mix_factor = (self.mix_factor - MIX_SPEED * dt).max(0.0);
+        }
+    }
+
+    pub fn is_animating(&self) -> bool {
+        (self.rotation && self.speed_factor != 0.0)
+            || (self.texture && self.mix_factor < 1.0)
+            || (!self.texture && self.mix_factor > 0.0)
+    }
 }
 
 impl Default for Ctx {
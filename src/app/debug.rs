@@ -0,0 +1,34 @@
+// Views replacing the shading of the model to inspect the mesh.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DebugView {
+    Off,
+    Normals,
+    UvChecker,
+    Depth,
+    FaceId,
+    Density,
+}
+
+impl DebugView {
+    pub fn next(self) -> Self {
+        match self {
+            DebugView::Off => DebugView::Normals,
+            DebugView::Normals => DebugView::UvChecker,
+            DebugView::UvChecker => DebugView::Depth,
+            DebugView::Depth => DebugView::FaceId,
+            DebugView::FaceId => DebugView::Density,
+            DebugView::Density => DebugView::Off,
+        }
+    }
+    // Must match the VIEW_* defines of DEBUG_FRAGMENT_SHADER.
+    pub fn as_int(self) -> i32 {
+        match self {
+            DebugView::Off => 0,
+            DebugView::Normals => 1,
+            DebugView::UvChecker => 2,
+            DebugView::Depth => 3,
+            DebugView::FaceId => 4,
+            DebugView::Density => 5,
+        }
+    }
+}
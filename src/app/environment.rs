@@ -0,0 +1,251 @@
+use std::borrow::Cow;
+
+use glium::{
+    backend::Facade,
+    framebuffer::SimpleFrameBuffer,
+    texture::{ClientFormat, CubeLayer, Cubemap, MipmapsOption, RawImage2d, UncompressedFloatFormat},
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction},
+    vertex::EmptyVertexAttributes,
+    Program,
+    Surface,
+    Texture2d,
+    uniform
+};
+
+use super::{
+    parser::{hdr_parser, ppm_parser},
+    shaders::{
+        BRDF_FRAGMENT_SHADER,
+        EQUIRECT_FRAGMENT_SHADER,
+        IRRADIANCE_FRAGMENT_SHADER,
+        PREFILTER_FRAGMENT_SHADER,
+        SCREEN_VERTEX_SHADER,
+        SKYBOX_FRAGMENT_SHADER
+    }
+};
+
+pub const ENVIRONMENT_SIZE: u32 = 512;
+pub const IRRADIANCE_SIZE: u32 = 32;
+pub const PREFILTERED_SIZE: u32 = 128;
+pub const PREFILTERED_LEVELS: u32 = 6;
+pub const BRDF_LUT_SIZE: u32 = 256;
+
+const LAYERS: [CubeLayer; 6] = [
+    CubeLayer::PositiveX,
+    CubeLayer::NegativeX,
+    CubeLayer::PositiveY,
+    CubeLayer::NegativeY,
+    CubeLayer::PositiveZ,
+    CubeLayer::NegativeZ
+];
+
+fn create_program(display: &dyn Facade, fragment_shader: &str) -> Program {
+    Program::from_source(display, SCREEN_VERTEX_SHADER, fragment_shader, None)
+        .expect("Error: \"glium::Program::from_source\" Fail")
+}
+
+fn create_cubemap(display: &dyn Facade, size: u32, mipmaps: MipmapsOption) -> Result<Cubemap, String> {
+    Cubemap::empty_with_format(display, UncompressedFloatFormat::F16F16F16, mipmaps, size)
+        .map_err(|e| format!("Error: Failed to create cubemap: {:?}", e))
+}
+
+fn cube_sampler(cubemap: &Cubemap) -> Sampler<'_, Cubemap> {
+    cubemap.sampled()
+        .magnify_filter(MagnifySamplerFilter::Linear)
+        .minify_filter(MinifySamplerFilter::LinearMipmapLinear)
+        .wrap_function(SamplerWrapFunction::Clamp)
+}
+
+// Equirectangular image as linear floats, .hdr files or sRGB encoded .ppm files.
+pub fn load_equirect(filepath: &str) -> Result<(Vec<f32>, (u32, u32)), String> {
+    if filepath.to_lowercase().ends_with(".hdr") {
+        return hdr_parser(filepath);
+    }
+    let (img, dim) = ppm_parser(filepath)?;
+    let linear = img.data.iter()
+        .map(|&c| {
+            let c = c as f32 / 255.0;
+            if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+        })
+        .collect();
+    Ok((linear, dim))
+}
+
+// Environment converted to a cubemap and prefiltered for the diffuse and specular lighting.
+pub struct Environment {
+    pub path: String,
+    pub cubemap: Cubemap,
+    pub irradiance: Cubemap,
+    pub prefiltered: Cubemap,
+}
+
+impl Environment {
+    pub fn skybox_sampler(&self) -> Sampler<'_, Cubemap> {
+        cube_sampler(&self.cubemap)
+    }
+    pub fn irradiance_sampler(&self) -> Sampler<'_, Cubemap> {
+        cube_sampler(&self.irradiance)
+    }
+    pub fn prefiltered_sampler(&self) -> Sampler<'_, Cubemap> {
+        cube_sampler(&self.prefiltered)
+    }
+}
+
+// Programs of the environment passes and the BRDF lookup table, which does not depend on the image.
+pub struct Ibl {
+    pub environment: Option<Environment>,
+    pub brdf_lut: Texture2d,
+    // Bound when no environment is loaded, a sampler cannot stay on the unit of another type.
+    pub black: Cubemap,
+    equirect_program: Program,
+    irradiance_program: Program,
+    prefilter_program: Program,
+    skybox_program: Program,
+}
+
+impl Ibl {
+    pub fn new(display: &dyn Facade) -> Self {
+        let brdf_lut = Texture2d::empty_with_format(
+            display,
+            UncompressedFloatFormat::F16F16,
+            MipmapsOption::NoMipmap,
+            BRDF_LUT_SIZE,
+            BRDF_LUT_SIZE
+        ).expect("Error: Failed to create the BRDF lookup table");
+        let brdf_program = create_program(display, BRDF_FRAGMENT_SHADER);
+        brdf_lut.as_surface().draw(
+            EmptyVertexAttributes { len: 3 },
+            glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+            &brdf_program,
+            &glium::uniforms::EmptyUniforms,
+            &Default::default()
+        ).unwrap();
+        let black = create_cubemap(display, 1, MipmapsOption::NoMipmap).expect("Error: Failed to create cubemap");
+        for layer in LAYERS {
+            SimpleFrameBuffer::new(display, black.main_level().image(layer))
+                .expect("Error: Failed to bind cubemap")
+                .clear_color(0.0, 0.0, 0.0, 1.0);
+        }
+        Self {
+            environment: None,
+            brdf_lut,
+            black,
+            equirect_program: create_program(display, EQUIRECT_FRAGMENT_SHADER),
+            irradiance_program: create_program(display, IRRADIANCE_FRAGMENT_SHADER),
+            prefilter_program: create_program(display, PREFILTER_FRAGMENT_SHADER),
+            skybox_program: create_program(display, SKYBOX_FRAGMENT_SHADER),
+        }
+    }
+
+    pub fn brdf_sampler(&self) -> Sampler<'_, Texture2d> {
+        self.brdf_lut.sampled()
+            .magnify_filter(MagnifySamplerFilter::Linear)
+            .minify_filter(MinifySamplerFilter::Linear)
+            .wrap_function(SamplerWrapFunction::Clamp)
+    }
+
+    pub fn black_sampler(&self) -> Sampler<'_, Cubemap> {
+        cube_sampler(&self.black)
+    }
+
+    fn render_faces<U: glium::uniforms::Uniforms>(
+        display: &dyn Facade,
+        cubemap: &Cubemap,
+        level: u32,
+        program: &Program,
+        uniforms: impl Fn(i32) -> U
+    ) -> Result<(), String> {
+        let mipmap = cubemap.mipmap(level).ok_or("Error: Missing cubemap level.")?;
+        for (face, layer) in LAYERS.iter().enumerate() {
+            let mut target = SimpleFrameBuffer::new(display, mipmap.image(*layer))
+                .map_err(|e| format!("Error: Failed to bind cubemap: {:?}", e))?;
+            target.draw(
+                EmptyVertexAttributes { len: 3 },
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                program,
+                &uniforms(face as i32),
+                &Default::default()
+            ).map_err(|e| format!("Error: Failed to render cubemap: {:?}", e))?;
+        }
+        Ok(())
+    }
+
+    pub fn load(&mut self, display: &dyn Facade, filepath: &str) -> Result<(), String> {
+        let (data, (width, height)) = load_equirect(filepath)?;
+        let image = RawImage2d {
+            data: Cow::Owned(data),
+            width,
+            height,
+            format: ClientFormat::F32F32F32
+        };
+        let equirect = Texture2d::with_format(display, image, UncompressedFloatFormat::F16F16F16, MipmapsOption::NoMipmap)
+            .map_err(|e| format!("Error: Failed to create texture: {:?}", e))?;
+        let equirect = equirect.sampled()
+            .magnify_filter(MagnifySamplerFilter::Linear)
+            .minify_filter(MinifySamplerFilter::Linear);
+
+        let cubemap = create_cubemap(display, ENVIRONMENT_SIZE, MipmapsOption::EmptyMipmaps)?;
+        Self::render_faces(display, &cubemap, 0, &self.equirect_program, |face| uniform! {
+            face: face,
+            equirect: equirect
+        })?;
+        // The convolutions read the blurred levels instead of thousands of texels.
+        unsafe {
+            cubemap.generate_mipmaps();
+        }
+
+        let irradiance = create_cubemap(display, IRRADIANCE_SIZE, MipmapsOption::NoMipmap)?;
+        Self::render_faces(display, &irradiance, 0, &self.irradiance_program, |face| uniform! {
+            face: face,
+            environment: cube_sampler(&cubemap)
+        })?;
+
+        let prefiltered = create_cubemap(display, PREFILTERED_SIZE, MipmapsOption::EmptyMipmapsMax(PREFILTERED_LEVELS - 1))?;
+        for level in 0..PREFILTERED_LEVELS {
+            let roughness = level as f32 / (PREFILTERED_LEVELS - 1) as f32;
+            Self::render_faces(display, &prefiltered, level, &self.prefilter_program, |face| uniform! {
+                face: face,
+                environment: cube_sampler(&cubemap),
+                roughness: roughness,
+                resolution: ENVIRONMENT_SIZE as f32
+            })?;
+        }
+
+        println!("Environment: {} ({}x{})", filepath, width, height);
+        self.environment = Some(Environment {
+            path: filepath.to_string(),
+            cubemap,
+            irradiance,
+            prefiltered,
+        });
+        Ok(())
+    }
+
+    pub fn draw_skybox<S: Surface>(&self, frame: &mut S, perspective_matrix: [[f32; 4]; 4], rotation: [[f32; 3]; 3], exposure: f32) {
+        if let Some(environment) = &self.environment {
+            let uniforms = uniform! {
+                environment: environment.skybox_sampler(),
+                perspective_matrix: perspective_matrix,
+                environment_rotation: rotation,
+                exposure: exposure
+            };
+            frame.draw(
+                EmptyVertexAttributes { len: 3 },
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &self.skybox_program,
+                &uniforms,
+                &Default::default()
+            ).unwrap();
+        }
+    }
+}
+
+// Rotation of the environment around the vertical axis.
+pub fn environment_rotation(angle: f32) -> [[f32; 3]; 3] {
+    let (sin, cos) = angle.sin_cos();
+    [
+        [cos, 0.0, -sin],
+        [0.0, 1.0, 0.0],
+        [sin, 0.0, cos]
+    ]
+}
@@ -0,0 +1,318 @@
+use std::{collections::HashMap, fs::write, path::Path};
+
+use super::image::Image;
+
+const MAX_COLORS: usize = 256;
+// Largest LZW code, the table is cleared once it is full.
+const MAX_CODES: u16 = 4096;
+// Pixels read to build the palette, the frames are sampled evenly above this.
+const PALETTE_SAMPLES: usize = 1 << 20;
+
+// Colors of the frames reduced by median cut: the box of colors with the widest channel
+// range is split at its median until there are enough boxes, each box gives its mean color.
+pub fn palette(frames: &[Image], size: usize) -> Vec<[u8; 3]> {
+    let total: usize = frames.iter().map(|frame| frame.pixels.len() / 4).sum();
+    let stride = (total / PALETTE_SAMPLES).max(1);
+    let mut histogram: HashMap<[u8; 3], u32> = HashMap::new();
+    let pixels = frames.iter().flat_map(|frame| frame.pixels.chunks_exact(4));
+    for pixel in pixels.step_by(stride).filter(|pixel| pixel[3] >= 128) {
+        *histogram.entry([pixel[0], pixel[1], pixel[2]]).or_insert(0) += 1;
+    }
+    let mut colors: Vec<([u8; 3], u32)> = histogram.into_iter().collect();
+    if colors.is_empty() {
+        return vec![[0, 0, 0]];
+    }
+    colors.sort();
+    let mut boxes = vec![colors];
+    while boxes.len() < size {
+        let widest = boxes.iter().enumerate()
+            .map(|(index, colors)| (index, widest_channel(colors)))
+            .filter(|(_, (_, range))| *range > 0)
+            .max_by_key(|(_, (_, range))| *range);
+        let Some((index, (channel, _))) = widest else {
+            break;
+        };
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_by_key(|(color, _)| color[channel]);
+        let half = colors.iter().map(|(_, count)| *count as u64).sum::<u64>() / 2;
+        let mut seen = 0;
+        let median = colors.iter()
+            .position(|(_, count)| {
+                seen += *count as u64;
+                seen > half
+            })
+            .unwrap_or(0)
+            .clamp(1, colors.len() - 1);
+        let upper = colors.split_off(median);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+    boxes.iter()
+        .map(|colors| {
+            let count: u64 = colors.iter().map(|(_, count)| *count as u64).sum();
+            [0, 1, 2].map(|channel| {
+                let sum: u64 = colors.iter().map(|(color, n)| color[channel] as u64 * *n as u64).sum();
+                ((sum + count / 2) / count) as u8
+            })
+        })
+        .collect()
+}
+
+// Channel with the largest spread of the colors and that spread.
+fn widest_channel(colors: &[([u8; 3], u32)]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let min = colors.iter().map(|(color, _)| color[channel]).min().unwrap_or(0);
+            let max = colors.iter().map(|(color, _)| color[channel]).max().unwrap_or(0);
+            (channel, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap()
+}
+
+fn nearest(palette: &[[u8; 3]], color: [u8; 3]) -> u8 {
+    let distance = |entry: &[u8; 3]| -> i32 {
+        (0..3).map(|i| (entry[i] as i32 - color[i] as i32).pow(2)).sum()
+    };
+    (0..palette.len()).min_by_key(|&index| distance(&palette[index])).unwrap_or(0) as u8
+}
+
+// Bits written from the least significant one, as GIF wants them.
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= (code as u32) << self.count;
+        self.count += size;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+// Variable length LZW of 8 bit indices, the codes grow from 9 to 12 bits.
+pub fn lzw(indices: &[u8]) -> Vec<u8> {
+    let clear: u16 = 256;
+    let end: u16 = 257;
+    let mut writer = BitWriter { bytes: Vec::new(), buffer: 0, count: 0 };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut size = 9;
+    let mut next = end + 1;
+    writer.write(clear, size);
+    let Some((&first, rest)) = indices.split_first() else {
+        writer.write(end, size);
+        return writer.finish();
+    };
+    let mut prefix = first as u16;
+    for &index in rest {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        writer.write(prefix, size);
+        if next < MAX_CODES {
+            table.insert((prefix, index), next);
+            if next == 1 << size {
+                size += 1;
+            }
+            next += 1;
+        } else {
+            writer.write(clear, size);
+            table.clear();
+            size = 9;
+            next = end + 1;
+        }
+        prefix = index as u16;
+    }
+    writer.write(prefix, size);
+    writer.write(end, size);
+    writer.finish()
+}
+
+// Looping animation sharing one palette, a frame shows for 1/fps seconds.
+pub fn encode(frames: &[Image], fps: u32) -> Result<Vec<u8>, String> {
+    let first = frames.first().ok_or("Error: No frame to encode.")?;
+    let (width, height) = (first.width, first.height);
+    if frames.iter().any(|frame| (frame.width, frame.height) != (width, height)) {
+        return Err("Error: The frames of an animation must have the same size.".to_string());
+    }
+    if width > u16::MAX as u32 || height > u16::MAX as u32 {
+        return Err(format!("Error: {}x{} is too large for a GIF.", width, height));
+    }
+    let transparent = frames.iter().any(|frame| !frame.is_opaque());
+    // The last entry is kept for the transparent pixels.
+    let mut colors = palette(frames, if transparent { MAX_COLORS - 1 } else { MAX_COLORS });
+    let used = colors.len();
+    colors.resize(MAX_COLORS, [0, 0, 0]);
+    let delay = (100 / fps.max(1)).max(2) as u16;
+
+    let mut data = b"GIF89a".to_vec();
+    data.extend_from_slice(&(width as u16).to_le_bytes());
+    data.extend_from_slice(&(height as u16).to_le_bytes());
+    // Global color table of 256 entries, background 0, square pixels.
+    data.extend_from_slice(&[0xF7, 0, 0]);
+    data.extend(colors.iter().flatten());
+    // Repeats forever.
+    data.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+    data.extend_from_slice(b"NETSCAPE2.0");
+    data.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+    let mut cache: HashMap<[u8; 3], u8> = HashMap::new();
+    for frame in frames {
+        let indices: Vec<u8> = frame.pixels.chunks_exact(4)
+            .map(|pixel| {
+                if transparent && pixel[3] < 128 {
+                    return used as u8;
+                }
+                let color = [pixel[0], pixel[1], pixel[2]];
+                *cache.entry(color).or_insert_with(|| nearest(&colors[..used], color))
+            })
+            .collect();
+        // Graphic control: a transparent frame clears the previous one instead of drawing over it.
+        let flags = if transparent { (2 << 2) | 1 } else { 1 << 2 };
+        data.extend_from_slice(&[0x21, 0xF9, 0x04, flags]);
+        data.extend_from_slice(&delay.to_le_bytes());
+        data.extend_from_slice(&[if transparent { used as u8 } else { 0 }, 0]);
+        data.push(0x2C);
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend_from_slice(&(width as u16).to_le_bytes());
+        data.extend_from_slice(&(height as u16).to_le_bytes());
+        data.extend_from_slice(&[0, 8]);
+        for block in lzw(&indices).chunks(255) {
+            data.push(block.len() as u8);
+            data.extend_from_slice(block);
+        }
+        data.push(0);
+    }
+    data.push(0x3B);
+    Ok(data)
+}
+
+pub fn save(filepath: &Path, frames: &[Image], fps: u32) -> Result<(), String> {
+    let data = encode(frames, fps)?;
+    if let Some(parent) = filepath.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Error: Cannot create {}. {}", parent.display(), e))?;
+    }
+    write(filepath, data).map_err(|e| format!("Error: Cannot write {}. {}", filepath.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reference decoder: the table is rebuilt from the codes, one entry behind the encoder.
+    fn unlzw(data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let reset = |table: &mut Vec<Vec<u8>>| {
+            *table = (0..=255u8).map(|i| vec![i]).collect();
+            table.push(Vec::new());
+            table.push(Vec::new());
+        };
+        reset(&mut table);
+        let (mut size, mut position) = (9, 0usize);
+        let mut previous: Option<Vec<u8>> = None;
+        loop {
+            let code = (0..size).fold(0usize, |code, bit| {
+                let at = position + bit;
+                code | (((data[at / 8] >> (at % 8)) & 1) as usize) << bit
+            });
+            position += size;
+            match code {
+                256 => {
+                    reset(&mut table);
+                    size = 9;
+                    previous = None;
+                    continue;
+                },
+                257 => return output,
+                _ => {}
+            }
+            let entry = match (&previous, table.get(code)) {
+                (_, Some(entry)) => entry.clone(),
+                (Some(previous), None) => [previous.clone(), vec![previous[0]]].concat(),
+                (None, None) => panic!("code {} before any entry", code)
+            };
+            if let Some(previous) = previous {
+                if table.len() < 4096 {
+                    table.push([previous, vec![entry[0]]].concat());
+                }
+            }
+            if table.len() == 1 << size && size < 12 {
+                size += 1;
+            }
+            output.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let mut state = 7u32;
+        let noise: Vec<u8> = (0..20000).map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        }).collect();
+        let runs: Vec<u8> = (0..50000).map(|i| (i / 300 % 7) as u8).collect();
+        for indices in [Vec::new(), vec![3], noise, runs] {
+            assert_eq!(unlzw(&lzw(&indices)), indices);
+        }
+    }
+
+    #[test]
+    fn palette_keeps_few_colors_exact() {
+        let mut image = Image::new(4, 1);
+        for (x, color) in [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255], [10, 20, 30, 255]].into_iter().enumerate() {
+            image.set_pixel(x as u32, 0, color);
+        }
+        let mut colors = palette(&[image], 256);
+        colors.sort();
+        assert_eq!(colors, [[0, 0, 255], [0, 255, 0], [10, 20, 30], [255, 0, 0]]);
+    }
+
+    #[test]
+    fn opaque_frames_use_the_whole_palette() {
+        let mut image = Image::new(20, 20);
+        for y in 0..20 {
+            for x in 0..20 {
+                image.set_pixel(x, y, [(x * 12) as u8, (y * 12) as u8, 128, 255]);
+            }
+        }
+        let data = encode(&[image], 24).unwrap();
+        // Header, color table, loop extension, graphic control and image descriptor.
+        let mut position = 13 + 768 + 19 + 8 + 10 + 1;
+        let mut compressed = Vec::new();
+        while data[position] != 0 {
+            let len = data[position] as usize;
+            compressed.extend_from_slice(&data[position + 1..position + 1 + len]);
+            position += 1 + len;
+        }
+        let mut indices = unlzw(&compressed);
+        assert_eq!(indices.len(), 400);
+        indices.sort();
+        indices.dedup();
+        assert!(indices.len() > 200);
+    }
+
+    #[test]
+    fn frames_must_share_a_size() {
+        assert!(encode(&[], 24).is_err());
+        assert!(encode(&[Image::new(2, 2), Image::new(2, 3)], 24).is_err());
+        let data = encode(&[Image::new(2, 2), Image::new(2, 2)], 24).unwrap();
+        assert!(data.starts_with(b"GIF89a") && data.ends_with(&[0x3B]));
+    }
+}
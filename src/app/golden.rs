@@ -0,0 +1,348 @@
+// Golden image regression suite: every model of obj/ drawn by the CPU rasterizer in each of its
+// shading and polygon modes, and by OpenGL in the modes only the shaders have, compared with the
+// references of tests/golden. The OpenGL references come from llvmpipe, they are skipped without EGL.
+// SCOP_BLESS=1 cargo test golden writes the references again after an intended change.
+use std::{fs, path::{Path, PathBuf}};
+
+use super::{
+    backend::{CpuBackend, GliumBackend, RenderBackend},
+    ctx::Ctx,
+    headless::create_context,
+    image::Image,
+    parser::obj_parser,
+    software::SOFTWARE_SHADINGS,
+    viewer::Viewer
+};
+
+const MODEL_DIR: &str = "./obj";
+const GOLDEN_DIR: &str = "./tests/golden";
+// Actual and diff images of the failed comparisons.
+const FAILURE_DIR: &str = "./target/golden";
+const TEXTURE: &str = "./obj/Texture/texture.ppm";
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+const POLYGON_MODES: [(i32, &str); 3] = [(0, "fill"), (1, "line"), (2, "point")];
+// Shading modes the CPU rasterizer does not have.
+const GPU_SHADINGS: [&str; 1] = ["pbr"];
+
+// Object turn and elevation in degrees, and distance to the camera in object radii.
+// The elevation leans the model around its X axis before the turn, the camera sees it from above or below.
+struct Camera {
+    name: &'static str,
+    angle: f32,
+    elevation: f32,
+    distance: f32,
+}
+
+// The close one has the camera inside the bounding sphere, the near plane cuts the model.
+const CAMERAS: [Camera; 3] = [
+    Camera { name: "front", angle: 30.0, elevation: 0.0, distance: 1.1 },
+    Camera { name: "above", angle: 210.0, elevation: 40.0, distance: 2.5 },
+    Camera { name: "close", angle: 300.0, elevation: -30.0, distance: 0.8 },
+];
+
+// Redmean distance above which two pixels look different, out of about 765.
+const PIXEL_TOLERANCE: f32 = 40.0;
+// Share of the pixels covered by the model that may differ before an image fails,
+// so a small model on a large background is held to the same standard as a large one.
+const IMAGE_TOLERANCE: f32 = 0.02;
+
+// Low cost approximation of the perceived distance between two sRGB colors, alpha counted like a channel.
+fn color_distance(a: [u8; 4], b: [u8; 4]) -> f32 {
+    let mean = (a[0] as f32 + b[0] as f32) / 2.0;
+    let [dr, dg, db, da] = [0, 1, 2, 3].map(|i| a[i] as f32 - b[i] as f32);
+    ((2.0 + mean / 256.0) * dr * dr + 4.0 * dg * dg + (2.0 + (255.0 - mean) / 256.0) * db * db + 3.0 * da * da).sqrt()
+}
+
+struct Comparison {
+    different: usize,
+    // Pixels away from the background in the reference or in the actual image.
+    covered: usize,
+    // Faded reference with the different pixels in red.
+    diff: Image,
+}
+
+impl Comparison {
+    fn ratio(&self) -> f32 {
+        self.different as f32 / self.covered.max(1) as f32
+    }
+
+    fn passes(&self) -> bool {
+        self.ratio() <= IMAGE_TOLERANCE
+    }
+}
+
+fn compare(reference: &Image, actual: &Image, background: [u8; 4]) -> Result<Comparison, String> {
+    if (reference.width, reference.height) != (actual.width, actual.height) {
+        return Err(format!("size {}x{} instead of {}x{}", actual.width, actual.height, reference.width, reference.height));
+    }
+    let mut diff = Image::new(reference.width, reference.height);
+    let (mut different, mut covered) = (0, 0);
+    for y in 0..reference.height {
+        for x in 0..reference.width {
+            let (expected, found) = (reference.pixel(x, y), actual.pixel(x, y));
+            if color_distance(expected, background) > PIXEL_TOLERANCE || color_distance(found, background) > PIXEL_TOLERANCE {
+                covered += 1;
+            }
+            if color_distance(expected, found) > PIXEL_TOLERANCE {
+                different += 1;
+                diff.set_pixel(x, y, [255, 0, 0, 255]);
+            } else {
+                let luma = (expected[0] as u32 * 3 + expected[1] as u32 * 6 + expected[2] as u32) / 10;
+                let faded = (170 + luma / 3) as u8;
+                diff.set_pixel(x, y, [faded, faded, faded, 255]);
+            }
+        }
+    }
+    Ok(Comparison { different, covered, diff })
+}
+
+// Decodes the PNG files written by Image::encode_png: 8 bit RGB or RGBA in stored deflate blocks.
+fn decode_png(filepath: &Path) -> Result<Image, String> {
+    let data = fs::read(filepath).map_err(|e| format!("Error: Cannot read {}. {}", filepath.display(), e))?;
+    let invalid = || format!("Error: {} is not a PNG written by scop.", filepath.display());
+    if data.len() < 8 || data[..8] != [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'] {
+        return Err(invalid());
+    }
+    let (mut offset, mut header, mut zlib) = (8, None, Vec::new());
+    while offset + 8 <= data.len() {
+        let len = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        let kind = &data[offset + 4..offset + 8];
+        let chunk = data.get(offset + 8..offset + 8 + len).ok_or_else(invalid)?;
+        match kind {
+            b"IHDR" => header = Some(chunk.to_vec()),
+            b"IDAT" => zlib.extend_from_slice(chunk),
+            _ => {}
+        }
+        offset += 12 + len;
+    }
+    let header = header.ok_or_else(invalid)?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    let channels = match (header[8], header[9]) {
+        (8, 2) => 3,
+        (8, 6) => 4,
+        _ => return Err(invalid())
+    };
+    // Zlib header, then blocks of a final flag, a length and its complement.
+    let mut raw = Vec::new();
+    let mut position = 2;
+    loop {
+        let block = zlib.get(position..position + 5).ok_or_else(invalid)?;
+        if block[0] & 0b110 != 0 {
+            return Err(invalid());
+        }
+        let len = u16::from_le_bytes([block[1], block[2]]) as usize;
+        raw.extend_from_slice(zlib.get(position + 5..position + 5 + len).ok_or_else(invalid)?);
+        position += 5 + len;
+        if block[0] & 1 == 1 {
+            break;
+        }
+    }
+    let stride = width as usize * channels + 1;
+    if raw.len() != stride * height as usize {
+        return Err(invalid());
+    }
+    let mut image = Image::new(width, height);
+    for (y, row) in raw.chunks_exact(stride).enumerate() {
+        if row[0] != 0 {
+            return Err(invalid());
+        }
+        for (x, pixel) in row[1..].chunks_exact(channels).enumerate() {
+            let alpha = if channels == 4 { pixel[3] } else { 255 };
+            image.set_pixel(x as u32, y as u32, [pixel[0], pixel[1], pixel[2], alpha]);
+        }
+    }
+    Ok(image)
+}
+
+// Scene with only the model in view, without ground nor grid.
+fn framed_ctx(model: &str) -> Ctx {
+    let mut ctx = Ctx::with_obj(obj_parser(&format!("{}/{}", MODEL_DIR, model)).unwrap());
+    ctx.width = WIDTH;
+    ctx.height = HEIGHT;
+    ctx.ground = false;
+    ctx.grid = false;
+    ctx.gizmos = false;
+    ctx.scene.active_mut().texture = TEXTURE.to_string();
+    ctx
+}
+
+// The model center in front of the camera, seen as the camera asks.
+fn place(ctx: &mut Ctx, camera: &Camera) {
+    let object = ctx.scene.active_mut();
+    let (center, radius) = (object.obj.centroid, object.obj.radius());
+    object.transform.position = [-center[0], -center[1], radius * camera.distance - center[2]];
+    object.transform.angle = camera.angle.to_radians();
+    object.nodes[0].rotation[0] = camera.elevation.to_radians();
+}
+
+fn golden_name(model: &str, shading: &str, polygon_mode: &str, camera: &str) -> String {
+    format!("{}_{}_{}_{}", model.trim_end_matches(".obj"), shading, polygon_mode, camera)
+}
+
+// Color of the pixels the model does not cover.
+fn background<B: RenderBackend>(viewer: &mut Viewer<B>) -> [u8; 4] {
+    let object = viewer.ctx.scene.active_mut();
+    object.nodes[0].visible = false;
+    let image = viewer.render(1, false).unwrap();
+    viewer.ctx.scene.active_mut().nodes[0].visible = true;
+    image.pixel(0, 0)
+}
+
+// Renders every combination of the model, checks them all then fails with the list of differences.
+fn check_model<B: RenderBackend>(model: &str, backend: B, shadings: &[&str]) {
+    let bless = std::env::var_os("SCOP_BLESS").is_some();
+    let mut viewer = Viewer::new(framed_ctx(model), backend).unwrap();
+    let background = background(&mut viewer);
+    let mut failures = Vec::new();
+    for shading in shadings {
+        viewer.select_shading(shading).unwrap();
+        for (polygon_mode, polygon_name) in POLYGON_MODES {
+            viewer.ctx.polmode = polygon_mode;
+            for camera in &CAMERAS {
+                place(&mut viewer.ctx, camera);
+                let name = golden_name(model, shading, polygon_name, camera.name);
+                let actual = viewer.render(1, false).unwrap();
+                let reference_path = Path::new(GOLDEN_DIR).join(format!("{}.png", name));
+                if bless {
+                    actual.save(&reference_path).unwrap();
+                    continue;
+                }
+                if let Err(err) = check_image(&name, &reference_path, &actual, background) {
+                    failures.push(err);
+                }
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{} golden images differ:\n{}", failures.len(), failures.join("\n"));
+}
+
+fn check_image(name: &str, reference_path: &Path, actual: &Image, background: [u8; 4]) -> Result<(), String> {
+    let failure = |suffix: &str| PathBuf::from(FAILURE_DIR).join(format!("{}_{}.png", name, suffix));
+    let reference = match decode_png(reference_path) {
+        Ok(reference) => reference,
+        Err(err) => {
+            actual.save(&failure("actual"))?;
+            return Err(format!("{}: {} Run with SCOP_BLESS=1 to create it.", name, err));
+        }
+    };
+    let comparison = compare(&reference, actual, background).map_err(|err| format!("{}: {}", name, err))?;
+    if comparison.passes() {
+        return Ok(());
+    }
+    actual.save(&failure("actual"))?;
+    comparison.diff.save(&failure("diff"))?;
+    Err(format!(
+        "{}: {:.2}% of the covered pixels differ, see {}",
+        name,
+        comparison.ratio() * 100.0,
+        failure("diff").display()
+    ))
+}
+
+// One test per model so they run in parallel.
+macro_rules! golden_tests {
+    ($($test:ident: $model:expr),*) => {
+        const MODELS: &[&str] = &[$($model),*];
+        $(
+            #[test]
+            fn $test() {
+                check_model($model, CpuBackend::new(), &SOFTWARE_SHADINGS);
+            }
+        )*
+    };
+}
+
+golden_tests! {
+    golden_42: "42.obj",
+    golden_monkey: "monkey.obj",
+    golden_monster: "monster.obj",
+    golden_rust: "rust.obj",
+    golden_teapot: "teapot.obj",
+    golden_teapot2: "teapot2.obj"
+}
+
+// One OpenGL context for every model.
+#[test]
+fn golden_gpu() {
+    let Ok(context) = create_context() else {
+        println!("No EGL context, skipped.");
+        return;
+    };
+    for model in MODELS {
+        let backend = GliumBackend::new(context.clone(), &framed_ctx(model)).unwrap();
+        check_model(model, backend, &GPU_SHADINGS);
+    }
+}
+
+#[test]
+fn every_model_and_mode_is_covered() {
+    let mut models: Vec<String> = fs::read_dir(MODEL_DIR).unwrap()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".obj"))
+        .collect();
+    models.sort();
+    for model in models {
+        assert!(MODELS.contains(&model.as_str()), "{} has no golden test", model);
+    }
+    let mut backend = CpuBackend::new();
+    for mode in backend.registry().names() {
+        assert!(SOFTWARE_SHADINGS.contains(&mode) || GPU_SHADINGS.contains(&mode), "{} has no golden test", mode);
+    }
+}
+
+#[test]
+fn png_round_trip() {
+    let mut image = Image::new(5, 3);
+    image.set_pixel(4, 2, [10, 20, 30, 40]);
+    let filepath = PathBuf::from(FAILURE_DIR).join("round_trip.png");
+    image.save(&filepath).unwrap();
+    assert_eq!(decode_png(&filepath).unwrap(), image);
+}
+
+#[test]
+fn tolerance_ignores_small_noise() {
+    let mut reference = Image::new(10, 10);
+    for y in 0..10 {
+        for x in 0..10 {
+            reference.set_pixel(x, y, [200, 200, 200, 255]);
+        }
+    }
+    let mut actual = reference.clone();
+    for x in 0..10 {
+        actual.set_pixel(x, 5, [206, 206, 206, 255]);
+    }
+    actual.set_pixel(0, 0, [0, 0, 0, 0]);
+    let comparison = compare(&reference, &actual, [0, 0, 0, 0]).unwrap();
+    assert_eq!((comparison.different, comparison.covered), (1, 100));
+    assert!(comparison.passes());
+    assert_eq!(comparison.diff.pixel(0, 0), [255, 0, 0, 255]);
+}
+
+#[test]
+fn tolerance_catches_changed_regions() {
+    let reference = Image::new(10, 10);
+    let mut actual = reference.clone();
+    actual.set_pixel(3, 3, [0, 80, 0, 0]);
+    actual.set_pixel(4, 3, [0, 0, 0, 255]);
+    let comparison = compare(&reference, &actual, [0, 0, 0, 0]).unwrap();
+    assert_eq!(comparison.different, 2);
+    assert!(!comparison.passes());
+    assert!(compare(&reference, &Image::new(10, 9), [0, 0, 0, 0]).is_err());
+}
+
+#[test]
+fn tolerance_follows_the_covered_pixels() {
+    // A point of a small model moved by one pixel, 2 of the 100x100 pixels but 2 of the 20 covered ones.
+    let mut reference = Image::new(100, 100);
+    for x in 0..20 {
+        reference.set_pixel(x, 50, [255, 255, 255, 255]);
+    }
+    let mut actual = reference.clone();
+    actual.set_pixel(0, 50, [0, 0, 0, 0]);
+    actual.set_pixel(0, 51, [255, 255, 255, 255]);
+    let comparison = compare(&reference, &actual, [0, 0, 0, 0]).unwrap();
+    assert_eq!((comparison.different, comparison.covered), (2, 21));
+    assert!(!comparison.passes());
+}
@@ -0,0 +1,132 @@
+use std::{ffi::CString, rc::Rc};
+
+use glium::{
+    backend::{Backend, Context},
+    glutin::{
+        api::egl::{context::PossiblyCurrentContext, device::Device, display::Display},
+        config::{ConfigSurfaceTypes, ConfigTemplateBuilder},
+        context::{ContextApi, ContextAttributesBuilder, Version},
+        display::GlDisplay,
+        prelude::PossiblyCurrentGlContext
+    },
+    SwapBuffersError
+};
+
+use super::{
+    backend::{CpuBackend, GliumBackend, RenderBackend},
+    cli::Args,
+    ctx::Ctx,
+    recording::{CameraPath, Recording, DEFAULT_FPS},
+    viewer::Viewer
+};
+
+// OpenGL context without window nor surface, rendering only goes to framebuffer objects.
+struct SurfacelessBackend {
+    context: PossiblyCurrentContext,
+    display: Display,
+}
+
+unsafe impl Backend for SurfacelessBackend {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const core::ffi::c_void {
+        let symbol = CString::new(symbol).unwrap();
+        self.display.get_proc_address(&symbol) as *const _
+    }
+
+    // There is no default framebuffer.
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        (1, 1)
+    }
+
+    fn resize(&self, _: (u32, u32)) {}
+
+    fn is_current(&self) -> bool {
+        self.context.is_current()
+    }
+
+    unsafe fn make_current(&self) {
+        self.context.make_current_surfaceless().unwrap();
+    }
+}
+
+fn device_context(device: &Device) -> Result<Rc<Context>, String> {
+    let display = unsafe { Display::with_device(device, None) }
+        .map_err(|e| format!("EGL display: {}", e))?;
+    let template = ConfigTemplateBuilder::new()
+        .with_surface_type(ConfigSurfaceTypes::empty())
+        .build();
+    let config = unsafe { display.find_configs(template) }
+        .map_err(|e| format!("EGL config: {}", e))?
+        .next()
+        .ok_or("no EGL config")?;
+    let attributes = ContextAttributesBuilder::new()
+        .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
+        .build(None);
+    let context = unsafe { display.create_context(&config, &attributes) }
+        .map_err(|e| format!("EGL context: {}", e))?
+        .make_current_surfaceless()
+        .map_err(|e| format!("EGL surfaceless: {}", e))?;
+    unsafe { Context::new(SurfacelessBackend { context, display }, true, Default::default()) }
+        .map_err(|e| format!("OpenGL: {:?}", e))
+}
+
+// First EGL device able to give an OpenGL 3.3 context, software rasterizers such as llvmpipe included.
+pub fn create_context() -> Result<Rc<Context>, String> {
+    let devices = Device::query_devices()
+        .map_err(|e| format!("Error: No EGL device. {}", e))?;
+    let mut errors = Vec::new();
+    for device in devices {
+        match device_context(&device) {
+            Ok(context) => return Ok(context),
+            Err(err) => errors.push(err)
+        }
+    }
+    Err(format!("Error: No headless OpenGL context. {}", errors.join(", ")))
+}
+
+// Frames asked by the arguments: one image, a turntable or a camera path.
+fn recording(args: &Args, ctx: &Ctx) -> Result<Recording, String> {
+    let fps = args.fps.unwrap_or(DEFAULT_FPS);
+    let (path, frames) = match &args.path {
+        Some(filepath) => {
+            let path = CameraPath::parse(filepath)?;
+            let frames = args.frames.unwrap_or((path.duration() * fps as f32).round() as u32 + 1);
+            (path, frames)
+        },
+        None => {
+            let frames = args.frames.unwrap_or(1);
+            (CameraPath::turntable(ctx, frames as f32 / fps as f32), frames)
+        }
+    };
+    let mut recording = Recording::new(path, frames, fps, ctx);
+    recording.output = args.screenshot.clone();
+    recording.gif = args.gif.clone();
+    Ok(recording)
+}
+
+// Renders the scene of the arguments to the screenshot file, or every frame of a recording.
+pub fn run(args: Args) -> Result<(), String> {
+    let ctx = args.ctx()?;
+    let recording = recording(&args, &ctx)?;
+    if args.software {
+        return record(Viewer::new(ctx, CpuBackend::new())?, recording);
+    }
+    let context = create_context()?;
+    println!("Headless: {}", context.get_opengl_renderer_string());
+    let backend = GliumBackend::new(context, &ctx)?;
+    record(Viewer::new(ctx, backend)?, recording)
+}
+
+// Same images whether drawn by OpenGL or by the CPU rasterizer.
+fn record<B: RenderBackend>(mut viewer: Viewer<B>, mut recording: Recording) -> Result<(), String> {
+    let (scale, transparent) = (viewer.ctx.screenshot_scale, viewer.ctx.screenshot_transparent);
+    while !recording.is_done() {
+        recording.apply(&mut viewer.ctx);
+        let image = viewer.render(scale, transparent)?;
+        recording.add(image)?;
+    }
+    recording.finish(&mut viewer.ctx)
+}
@@ -0,0 +1,239 @@
+use std::{
+    fs::{create_dir_all, read_to_string, write},
+    path::{Path, PathBuf},
+    time::Duration
+};
+
+use glium::{
+    backend::Facade,
+    program::ProgramCreationError,
+    DrawError,
+    Program
+};
+
+use super::{
+    shaders::{GRAY_FRAGMENT_SHADER, MAIN_VERTEX_SHADER, SHADER_SNIPPETS},
+    shading::{ShaderSource, ShadingMode, ShadingRegistry},
+    watcher::FileWatcher
+};
+
+pub const SHADER_DIR: &str = "./shaders";
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_INCLUDE_DEPTH: u32 = 8;
+
+// Every uniform of the program must be given by the mode with the type it is declared with, the draw would fail otherwise.
+fn check_uniforms(program: &Program, mode: &ShadingMode) -> Result<(), String> {
+    for (name, uniform) in program.uniforms() {
+        if !mode.provides(name) {
+            return Err(format!("Error: Uniform {} of {} is not listed in its config.", name, mode.name));
+        }
+        match mode.uniform_type(name) {
+            Some(ty) if ty != uniform.ty => {
+                return Err(format!("Error: Uniform {} of {} is declared as {:?}, it receives {:?}.", name, mode.name, uniform.ty, ty));
+            },
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn describe_error(err: ProgramCreationError) -> String {
+    match err {
+        ProgramCreationError::CompilationError(log, shader_type) => format!("{:?} shader: {}", shader_type, log.trim()),
+        ProgramCreationError::LinkingError(log) => format!("Link: {}", log.trim()),
+        err => format!("{:?}", err)
+    }
+}
+
+// Replaces the `#include <snippet>` and `#include "file"` lines, included files are watched too.
+fn expand_includes(source: &str, dir: &Path, watcher: &mut FileWatcher, depth: u32) -> Result<String, String> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err("Error: Too many nested #include.".to_string());
+    }
+    let mut expanded = String::new();
+    for line in source.lines() {
+        let include = match line.trim().strip_prefix("#include") {
+            Some(include) => include.trim(),
+            None => {
+                expanded.push_str(line);
+                expanded.push('\n');
+                continue;
+            }
+        };
+        if let Some(name) = include.strip_prefix('<').and_then(|name| name.strip_suffix('>')) {
+            let snippet = SHADER_SNIPPETS.iter()
+                .find(|(snippet, _)| *snippet == name)
+                .ok_or(format!("Error: Unknown snippet <{}>.", name))?;
+            expanded.push_str(snippet.1);
+        } else if let Some(file) = include.strip_prefix('"').and_then(|file| file.strip_suffix('"')) {
+            let path = dir.join(file);
+            watcher.watch(&path);
+            let content = read_to_string(&path).map_err(|e| format!("Error: Cannot open {}. {}", path.display(), e))?;
+            expanded.push_str(&expand_includes(&content, dir, watcher, depth + 1)?);
+        } else {
+            return Err(format!("Error: Invalid format : {}.", line.trim()));
+        }
+        expanded.push('\n');
+    }
+    Ok(expanded)
+}
+
+// Program of the current shading mode. Files of the modes declared in the config, and
+// `<name>.vert`/`<name>.frag` in the shader directory replacing the built-in sources, are
+// recompiled when they change. A failing compilation keeps the last good program and leaves
+// the message in `error`.
+pub struct ShaderReloader {
+    pub dir: PathBuf,
+    pub program: Program,
+    pub error: Option<String>,
+    watcher: FileWatcher,
+}
+
+impl ShaderReloader {
+    pub fn new(display: &dyn Facade, registry: &ShadingRegistry) -> Result<Self, String> {
+        let program = Program::from_source(display, MAIN_VERTEX_SHADER, GRAY_FRAGMENT_SHADER, None)
+            .map_err(|err| format!("Error: gray shading does not compile. {}", describe_error(err)))?;
+        let mut reloader = Self {
+            dir: PathBuf::from(SHADER_DIR),
+            program,
+            error: None,
+            watcher: FileWatcher::new(),
+        };
+        reloader.load(display, registry);
+        Ok(reloader)
+    }
+
+    fn source(&mut self, mode: &ShadingMode, source: &ShaderSource, extension: &str) -> Result<String, String> {
+        let path = match source {
+            ShaderSource::Builtin(builtin) => {
+                let path = self.dir.join(format!("{}.{}", mode.name, extension));
+                self.watcher.watch(&path);
+                if !path.exists() {
+                    return Ok(builtin.to_string());
+                }
+                path
+            },
+            ShaderSource::File(path) => {
+                self.watcher.watch(path);
+                path.clone()
+            }
+        };
+        let content = read_to_string(&path).map_err(|e| format!("Error: Cannot open {}. {}", path.display(), e))?;
+        let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        expand_includes(&content, &dir, &mut self.watcher, 0)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn compile(&mut self, display: &dyn Facade, mode: &ShadingMode) -> Result<Program, String> {
+        let vertex = self.source(mode, &mode.vertex, "vert")?;
+        let fragment = self.source(mode, &mode.fragment, "frag")?;
+        let program = Program::from_source(display, &vertex, &fragment, None).map_err(describe_error)?;
+        check_uniforms(&program, mode)?;
+        Ok(program)
+    }
+
+    // Compiles the sources of the current mode, called when the mode changes.
+    pub fn load(&mut self, display: &dyn Facade, registry: &ShadingRegistry) {
+        self.watcher.clear();
+        self.watcher.watch(&registry.config);
+        let mode = registry.current();
+        match self.compile(display, mode) {
+            Ok(program) => {
+                if self.error.take().is_some() {
+                    println!("Shaders: {} compiled.", mode.name);
+                }
+                self.program = program;
+            },
+            Err(err) => {
+                println!("Error: {} shading kept the last good program.\n{}", mode.name, err);
+                self.error = Some(err);
+            }
+        }
+    }
+
+    // A draw refused by the program is shown like a compilation error instead of stopping the application.
+    pub fn draw_failed(&mut self, err: DrawError) {
+        let err = format!("Error: The shading cannot draw. {}", err);
+        if self.error.as_ref() != Some(&err) {
+            println!("{}", err);
+            self.error = Some(err);
+        }
+    }
+
+    // Recompiles when one of the watched files changed, returns true if a redraw is needed.
+    pub fn poll(&mut self, display: &dyn Facade, registry: &mut ShadingRegistry) -> bool {
+        let changed = self.watcher.changed();
+        if changed.is_empty() {
+            return false;
+        }
+        for path in &changed {
+            println!("Shaders: {} changed.", path.display());
+        }
+        if changed.contains(&registry.config) {
+            registry.reload_config();
+        }
+        self.load(display, registry);
+        true
+    }
+
+    // Writes the built-in sources of the mode as a starting point, existing files are kept.
+    pub fn export(&self, mode: &ShadingMode) -> Result<(), String> {
+        create_dir_all(&self.dir).map_err(|e| format!("Error: Cannot create {}. {}", self.dir.display(), e))?;
+        for (extension, source) in [("vert", &mode.vertex), ("frag", &mode.fragment)] {
+            let source = match source {
+                ShaderSource::Builtin(source) => source,
+                ShaderSource::File(path) => {
+                    println!("Shaders: {} is already a file.", path.display());
+                    continue;
+                }
+            };
+            let path = self.dir.join(format!("{}.{}", mode.name, extension));
+            if path.exists() {
+                println!("Shaders: {} already exists.", path.display());
+                continue;
+            }
+            write(&path, source).map_err(|e| format!("Error: Cannot write {}. {}", path.display(), e))?;
+            println!("Shaders: {} written.", path.display());
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::headless::create_context;
+
+    // Reads every varying so that none of the built-in vertex shader uniforms is optimized out.
+    const HEAT_FRAGMENT_SHADER: &str = r#"
+        #version 330
+        in vec3 v_normal;
+        in vec4 v_light_space;
+        out vec4 color;
+        uniform float mix_factor;
+        void main() {
+            color = vec4(abs(v_normal) * mix_factor + v_light_space.xyz * 0.0, 1.0);
+        }
+    "#;
+
+    #[test]
+    fn config_modes_keep_the_builtin_vertex_shader() {
+        let Ok(context) = create_context() else {
+            println!("No EGL context, skipped.");
+            return;
+        };
+        let dir = std::env::temp_dir().join("scop_heat");
+        create_dir_all(&dir).unwrap();
+        write(dir.join("heat.frag"), HEAT_FRAGMENT_SHADER).unwrap();
+        let mut registry = ShadingRegistry::new();
+        registry.config = dir.join("modes.conf");
+        write(&registry.config, "mode heat\nfragment heat.frag\nuniform mix_factor\n").unwrap();
+        registry.reload_config();
+        let mode = registry.select("heat").unwrap().clone();
+        let mut reloader = ShaderReloader::new(&context, &registry).unwrap();
+        reloader.dir = dir;
+        let program = reloader.compile(&context, &mode).unwrap();
+        assert!(program.get_uniform("normal_matrix").is_some());
+    }
+}
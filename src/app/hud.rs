@@ -0,0 +1,162 @@
+use std::{collections::VecDeque, time::Instant};
+
+use glium::{backend::Facade, Surface};
+
+use super::{
+    clock::MAX_DELTA,
+    text::{TextRenderer, GLYPH_HEIGHT, GLYPH_WIDTH}
+};
+
+// Time a notification stays on screen, the last second fades it out.
+pub const NOTIFICATION_DURATION: f32 = 4.0;
+const FADE_DURATION: f32 = 1.0;
+// Frames averaged by the FPS counter.
+const FRAME_SAMPLES: usize = 60;
+const HUD_SCALE: f32 = 2.0;
+const HUD_MARGIN: f32 = 8.0;
+
+pub const INFO_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+pub const ERROR_COLOR: [f32; 3] = [1.0, 0.3, 0.3];
+
+pub struct Notification {
+    pub text: String,
+    pub color: [f32; 3],
+    pub age: f32,
+}
+
+impl Notification {
+    pub fn opacity(&self) -> f32 {
+        ((NOTIFICATION_DURATION - self.age) / FADE_DURATION).clamp(0.0, 1.0)
+    }
+}
+
+// Values shown by the HUD, gathered by the renderer each frame.
+pub struct HudStats {
+    // Active object, "index/count: name".
+    pub object: String,
+    pub vertices: usize,
+    pub triangles: usize,
+    pub shading: String,
+    pub polygon_mode: &'static str,
+    pub culling: bool,
+    pub light: usize,
+    pub light_position: [f32; 3],
+}
+
+// On-screen statistics in the top left corner and fading notifications in the top right one.
+pub struct Hud {
+    pub visible: bool,
+    pub notifications: Vec<Notification>,
+    // Background loading in progress, shown in the bottom left corner even when the HUD is hidden.
+    pub progress: Option<String>,
+    frame_times: VecDeque<f32>,
+    last_frame: Option<Instant>,
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Self {
+            visible: true,
+            notifications: Vec::new(),
+            progress: None,
+            frame_times: VecDeque::with_capacity(FRAME_SAMPLES),
+            last_frame: None,
+        }
+    }
+
+    // Shows a message for a few seconds, it is printed on stdout as well.
+    pub fn notify(&mut self, text: &str, color: [f32; 3]) {
+        println!("{}", text);
+        self.notifications.push(Notification { text: text.to_string(), color, age: 0.0 });
+    }
+
+    pub fn info(&mut self, text: &str) {
+        self.notify(text, INFO_COLOR);
+    }
+
+    pub fn error(&mut self, text: &str) {
+        self.notify(text, ERROR_COLOR);
+    }
+
+    // Measures the real time since the previous frame and ages the notifications,
+    // they fade in real time even when the clock is paused or scaled.
+    pub fn frame(&mut self) {
+        let now = Instant::now();
+        let delta = match self.last_frame {
+            Some(last) => now.duration_since(last).as_secs_f32().min(MAX_DELTA),
+            None => 0.0
+        };
+        self.last_frame = Some(now);
+        if delta > 0.0 {
+            if self.frame_times.len() == FRAME_SAMPLES {
+                self.frame_times.pop_front();
+            }
+            self.frame_times.push_back(delta);
+        }
+        for notification in &mut self.notifications {
+            notification.age += delta;
+        }
+        self.notifications.retain(|notification| notification.age < NOTIFICATION_DURATION);
+    }
+
+    // No frame is drawn until the next redraw, the time spent waiting is not counted.
+    pub fn idle(&mut self) {
+        self.last_frame = None;
+    }
+
+    pub fn is_animating(&self) -> bool {
+        !self.notifications.is_empty()
+    }
+
+    // Average frame time in seconds, over the last frames.
+    pub fn frame_time(&self) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+        self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32
+    }
+
+    pub fn lines(&self, stats: &HudStats) -> Vec<String> {
+        let frame_time = self.frame_time();
+        let fps = if frame_time > 0.0 { 1.0 / frame_time } else { 0.0 };
+        let [x, y, z] = stats.light_position;
+        vec![
+            format!("{:.0} FPS  {:.2} ms", fps, frame_time * 1000.0),
+            format!("Object {}", stats.object),
+            format!("{} vertices  {} triangles", stats.vertices, stats.triangles),
+            format!("Shading: {}", stats.shading),
+            format!("Polygons: {}  Culling: {}", stats.polygon_mode, if stats.culling { "on" } else { "off" }),
+            format!("Light {}: {:.2} {:.2} {:.2}", stats.light, x, y, z),
+        ]
+    }
+
+    // Returns the height used in the top left corner, in pixels.
+    pub fn draw<S: Surface>(&self, display: &dyn Facade, frame: &mut S, text: &TextRenderer, stats: &HudStats) -> f32 {
+        let line_height = GLYPH_HEIGHT as f32 * HUD_SCALE;
+        let mut height = 0.0;
+        if self.visible {
+            let lines = self.lines(stats).join("\n");
+            text.draw(display, frame, &lines, [HUD_MARGIN, HUD_MARGIN], HUD_SCALE, [1.0, 1.0, 1.0, 1.0]);
+            height = HUD_MARGIN + lines.lines().count() as f32 * line_height;
+        }
+        let (width, frame_height) = frame.get_dimensions();
+        if let Some(progress) = &self.progress {
+            let y = (frame_height as f32 - HUD_MARGIN - line_height).max(0.0);
+            text.draw(display, frame, progress, [HUD_MARGIN, y], HUD_SCALE, [1.0, 1.0, 1.0, 1.0]);
+        }
+        for (row, notification) in self.notifications.iter().enumerate() {
+            let text_width = notification.text.chars().count() as f32 * GLYPH_WIDTH as f32 * HUD_SCALE;
+            let x = (width as f32 - text_width - HUD_MARGIN).max(0.0);
+            let y = HUD_MARGIN + row as f32 * line_height;
+            let [r, g, b] = notification.color;
+            text.draw(display, frame, &notification.text, [x, y], HUD_SCALE, [r, g, b, notification.opacity()]);
+        }
+        height
+    }
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,146 @@
+use std::{fs::write, path::Path};
+
+// Largest block of a stored deflate stream.
+const STORED_BLOCK: usize = 65535;
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+// 8 bit RGBA pixels, rows from top to bottom.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    // OpenGL reads rows from the bottom up.
+    pub fn from_gl_rows(rows: Vec<Vec<(u8, u8, u8, u8)>>) -> Self {
+        let height = rows.len() as u32;
+        let width = rows.first().map_or(0, |row| row.len() as u32);
+        let pixels = rows.iter().rev()
+            .flat_map(|row| row.iter().flat_map(|&(r, g, b, a)| [r, g, b, a]))
+            .collect();
+        Self { width, height, pixels }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let index = ((y * self.width + x) * 4) as usize;
+        [self.pixels[index], self.pixels[index + 1], self.pixels[index + 2], self.pixels[index + 3]]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        let index = ((y * self.width + x) * 4) as usize;
+        self.pixels[index..index + 4].copy_from_slice(&color);
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.pixels.chunks_exact(4).all(|pixel| pixel[3] == 255)
+    }
+
+    // Binary PPM, the alpha channel is dropped.
+    pub fn encode_ppm(&self) -> Vec<u8> {
+        let mut data = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for pixel in self.pixels.chunks_exact(4) {
+            data.extend_from_slice(&pixel[..3]);
+        }
+        data
+    }
+
+    // PNG with a stored (uncompressed) deflate stream, RGB when every pixel is opaque.
+    pub fn encode_png(&self) -> Vec<u8> {
+        let opaque = self.is_opaque();
+        let channels = if opaque { 3 } else { 4 };
+        let mut raw = Vec::with_capacity((self.width as usize * channels + 1) * self.height as usize);
+        for row in self.pixels.chunks_exact(self.width.max(1) as usize * 4) {
+            // Filter type None.
+            raw.push(0);
+            for pixel in row.chunks_exact(4) {
+                raw.extend_from_slice(&pixel[..channels]);
+            }
+        }
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        // Bit depth, color type (2 RGB, 6 RGBA), compression, filter, interlace.
+        header.extend_from_slice(&[8, if opaque { 2 } else { 6 }, 0, 0, 0]);
+
+        let mut data = PNG_SIGNATURE.to_vec();
+        png_chunk(&mut data, b"IHDR", &header);
+        png_chunk(&mut data, b"IDAT", &zlib_stored(&raw));
+        png_chunk(&mut data, b"IEND", &[]);
+        data
+    }
+
+    // The format follows the extension, .png or .ppm.
+    pub fn save(&self, filepath: &Path) -> Result<(), String> {
+        let extension = filepath.extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        let data = match extension.as_deref() {
+            Some("png") => self.encode_png(),
+            Some("ppm") => self.encode_ppm(),
+            _ => return Err(format!("Error: Unsupported image extension {}, use .png or .ppm.", filepath.display()))
+        };
+        if let Some(parent) = filepath.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Error: Cannot create {}. {}", parent.display(), e))?;
+        }
+        write(filepath, data).map_err(|e| format!("Error: Cannot write {}. {}", filepath.display(), e))
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// Length, type, data and the CRC of type and data.
+fn png_chunk(output: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = output.len();
+    output.extend_from_slice(kind);
+    output.extend_from_slice(data);
+    let crc = crc32(&output[start..]);
+    output.extend_from_slice(&crc.to_be_bytes());
+}
+
+// Zlib stream made of stored deflate blocks, valid for any reader without compressing anything.
+pub fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0x78, 0x01];
+    let mut blocks = data.chunks(STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        output.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        output.push(if blocks.peek().is_none() { 1 } else { 0 });
+        let len = block.len() as u16;
+        output.extend_from_slice(&len.to_le_bytes());
+        output.extend_from_slice(&(!len).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
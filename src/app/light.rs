@@ -0,0 +1,237 @@
+use glium::uniforms::{UniformValue, Uniforms};
+
+pub const MAX_LIGHTS: usize = 8;
+// Turned lights stop short of the vertical, where the heading is lost.
+const MAX_ELEVATION: f32 = 1.5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    Spot,
+}
+
+impl LightKind {
+    pub fn next(self) -> Self {
+        match self {
+            LightKind::Directional => LightKind::Point,
+            LightKind::Point => LightKind::Spot,
+            LightKind::Spot => LightKind::Directional,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            LightKind::Directional => "directional",
+            LightKind::Point => "point",
+            LightKind::Spot => "spot",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [LightKind::Directional, LightKind::Point, LightKind::Spot].into_iter().find(|kind| kind.name() == name)
+    }
+
+    // Must match the LIGHT_* defines of the shaders.
+    pub fn as_int(self) -> i32 {
+        match self {
+            LightKind::Directional => 0,
+            LightKind::Point => 1,
+            LightKind::Spot => 2,
+        }
+    }
+}
+
+// Positions and directions are in the space where the object is drawn,
+// the camera sits at the origin and looks toward +Z.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: [f32; 3],
+    pub direction: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+    pub inner_cone: f32,
+    pub outer_cone: f32,
+    pub enabled: bool,
+    // Follows the center of the scene until a direction is given with set_direction or turn.
+    pub aimed: bool,
+}
+
+impl Light {
+    pub fn new(kind: LightKind, position: [f32; 3], color: [f32; 3]) -> Self {
+        Self {
+            kind,
+            position,
+            direction: [0.0, 0.0, 1.0],
+            color,
+            intensity: 1.0,
+            range: 20.0,
+            inner_cone: 0.3,
+            outer_cone: 0.5,
+            enabled: true,
+            aimed: true,
+        }
+    }
+
+    pub fn translate(&mut self, delta: [f32; 3]) {
+        self.position[0] += delta[0];
+        self.position[1] += delta[1];
+        self.position[2] += delta[2];
+    }
+
+    // Points the light toward target, a light with its own direction keeps it.
+    pub fn aim(&mut self, target: [f32; 3]) {
+        if self.aimed {
+            self.look_at(target);
+        }
+    }
+
+    // Points the light toward target.
+    pub fn look_at(&mut self, target: [f32; 3]) {
+        let dir = [
+            target[0] - self.position[0],
+            target[1] - self.position[1],
+            target[2] - self.position[2]
+        ];
+        let len = (dir[0] * dir[0] + dir[1] * dir[1] + dir[2] * dir[2]).sqrt();
+        if len > f32::EPSILON {
+            self.direction = [dir[0] / len, dir[1] / len, dir[2] / len];
+        }
+    }
+
+    // The light no longer follows the scene.
+    pub fn set_direction(&mut self, direction: [f32; 3]) {
+        let len = (direction[0] * direction[0] + direction[1] * direction[1] + direction[2] * direction[2]).sqrt();
+        if len > f32::EPSILON {
+            self.direction = [direction[0] / len, direction[1] / len, direction[2] / len];
+            self.aimed = false;
+        }
+    }
+
+    // Turns the direction around the vertical axis then up or down, in radians.
+    pub fn turn(&mut self, yaw: f32, pitch: f32) {
+        let [x, y, z] = self.direction;
+        let heading = x.atan2(z) + yaw;
+        let elevation = (y.clamp(-1.0, 1.0).asin() + pitch).clamp(-MAX_ELEVATION, MAX_ELEVATION);
+        self.set_direction([heading.sin() * elevation.cos(), elevation.sin(), heading.cos() * elevation.cos()]);
+    }
+
+    pub fn describe(&self) -> String {
+        let direction = if self.aimed {
+            String::new()
+        } else {
+            format!(", direction [{:.2}, {:.2}, {:.2}]", self.direction[0], self.direction[1], self.direction[2])
+        };
+        format!(
+            "{:?} at [{:.2}, {:.2}, {:.2}]{}, intensity {:.2}{}",
+            self.kind, self.position[0], self.position[1], self.position[2],
+            direction, self.intensity, if self.enabled { "" } else { " (off)" }
+        )
+    }
+}
+
+pub fn default_lights() -> Vec<Light> {
+    let mut key = Light::new(LightKind::Directional, [1.0, 1.0, 2.0], [1.0, 1.0, 1.0]);
+    key.look_at([0.0, 0.0, 8.0]);
+    vec![key]
+}
+
+// Index of a light in the uploaded array, disabled lights are skipped.
+pub fn uniform_index(lights: &[Light], index: usize) -> Option<usize> {
+    match lights.get(index) {
+        Some(light) if light.enabled => {
+            let position = lights[..index].iter().filter(|light| light.enabled).count();
+            if position < MAX_LIGHTS { Some(position) } else { None }
+        },
+        _ => None
+    }
+}
+
+// Adds the light array to a set of uniforms, as `lights[i].field` and `light_count`.
+pub struct LightUniforms<'l, U: Uniforms> {
+    pub uniforms: U,
+    pub lights: &'l [Light],
+}
+
+impl<U: Uniforms> Uniforms for LightUniforms<'_, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        self.uniforms.visit_values(&mut output);
+        let lights: Vec<&Light> = self.lights.iter()
+            .filter(|light| light.enabled)
+            .take(MAX_LIGHTS)
+            .collect();
+        output("light_count", UniformValue::SignedInt(lights.len() as i32));
+        for (i, light) in lights.iter().enumerate() {
+            output(&format!("lights[{}].kind", i), UniformValue::SignedInt(light.kind.as_int()));
+            output(&format!("lights[{}].position", i), UniformValue::Vec3(light.position));
+            output(&format!("lights[{}].direction", i), UniformValue::Vec3(light.direction));
+            output(&format!("lights[{}].color", i), UniformValue::Vec3(light.color));
+            output(&format!("lights[{}].intensity", i), UniformValue::Float(light.intensity));
+            output(&format!("lights[{}].range", i), UniformValue::Float(light.range));
+            output(&format!("lights[{}].inner_cone", i), UniformValue::Float(light.inner_cone.cos()));
+            output(&format!("lights[{}].outer_cone", i), UniformValue::Float(light.outer_cone.cos()));
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct GizmoVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+glium::implement_vertex!(GizmoVertex, position, color);
+
+// A point per light plus a line showing where directional and spot lights aim.
+pub fn gizmo_vertices(lights: &[Light], selected: usize) -> (Vec<GizmoVertex>, Vec<GizmoVertex>) {
+    let mut points = Vec::new();
+    let mut lines = Vec::new();
+    for (i, light) in lights.iter().enumerate() {
+        let color = if !light.enabled {
+            [0.3, 0.3, 0.3]
+        } else if i == selected {
+            [1.0, 1.0, 0.0]
+        } else {
+            light.color
+        };
+        points.push(GizmoVertex { position: light.position, color });
+        if light.kind != LightKind::Point {
+            let p = light.position;
+            let d = light.direction;
+            lines.push(GizmoVertex { position: p, color });
+            lines.push(GizmoVertex { position: [p[0] + d[0], p[1] + d[1], p[2] + d[2]], color });
+        }
+    }
+    (points, lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turned_lights_keep_their_direction() {
+        let mut light = Light::new(LightKind::Spot, [0.0, 2.0, 0.0], [1.0; 3]);
+        light.aim([0.0, 0.0, 4.0]);
+        let aimed = light.direction;
+        light.turn(0.0, 0.2);
+        assert!(!light.aimed && light.direction[1] > aimed[1]);
+        let turned = light.direction;
+        light.translate([1.0, 0.0, 0.0]);
+        light.aim([0.0, 0.0, 4.0]);
+        assert_eq!(light.direction, turned);
+        light.aimed = true;
+        light.aim([0.0, 2.0, 4.0]);
+        assert_eq!(light.direction, [-1.0 / 17f32.sqrt(), 0.0, 4.0 / 17f32.sqrt()]);
+    }
+
+    #[test]
+    fn turns_stop_short_of_the_vertical() {
+        let mut light = Light::new(LightKind::Directional, [0.0; 3], [1.0; 3]);
+        light.turn(std::f32::consts::FRAC_PI_2, 0.0);
+        assert!((light.direction[0] - 1.0).abs() < 1e-6);
+        light.turn(0.0, -10.0);
+        assert!((light.direction[1] + MAX_ELEVATION.sin()).abs() < 1e-6 && light.direction[0] > 0.0);
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    path::Path,
+    sync::mpsc::{channel, Receiver, Sender},
+    thread
+};
+
+use glium::texture::RawImage2d;
+
+use super::{
+    mesh::{DrawRange, Mesh},
+    parser::{obj_parser_with_progress, ppm_parser, Obj}
+};
+
+// Share of the progress given to parsing, the rest goes to the mesh.
+const PARSE_SHARE: f32 = 0.8;
+
+// What a loaded file becomes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LoadKind {
+    // Model in place of the one of the target object.
+    Replace,
+    // Model of a new object next to the others.
+    Add,
+    // Texture of the target object.
+    Texture,
+    // New version of a model, for every object read from it.
+    ReloadModel,
+    // New version of a texture, for every object using it.
+    ReloadTexture,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadJob {
+    pub kind: LoadKind,
+    pub filepath: String,
+    // Object the file goes to, the active one when the job was sent.
+    // None for a new object and for reloads, or once the target is removed.
+    pub target: Option<usize>,
+}
+
+// Everything the GPU upload needs, built away from the window thread.
+pub enum Loaded {
+    Model {
+        obj: Box<Obj>,
+        mesh: Vec<Mesh>,
+        ranges: Vec<DrawRange>,
+    },
+    Texture {
+        img: RawImage2d<'static, u8>,
+        dim: (u32, u32),
+    },
+}
+
+// RawImage2d is not Clone, an image is uploaded once per object using it.
+pub fn copy_image(img: &RawImage2d<'static, u8>) -> RawImage2d<'static, u8> {
+    RawImage2d {
+        data: Cow::Owned(img.data.to_vec()),
+        width: img.width,
+        height: img.height,
+        format: img.format,
+    }
+}
+
+enum Message {
+    Progress(f32),
+    Done(Result<Loaded, String>),
+}
+
+// Reads a file and builds what it holds, progress gets the fraction done from 0 to 1.
+pub fn load(job: &LoadJob, progress: &dyn Fn(f32)) -> Result<Loaded, String> {
+    match job.kind {
+        LoadKind::Replace | LoadKind::Add | LoadKind::ReloadModel => {
+            let mut obj = obj_parser_with_progress(&job.filepath, &|fraction| progress(fraction * PARSE_SHARE))?;
+            let mesh = Mesh::get_mesh_vector(&mut obj);
+            let ranges = Mesh::get_draw_ranges(&obj);
+            progress(1.0);
+            Ok(Loaded::Model { obj: Box::new(obj), mesh, ranges })
+        },
+        LoadKind::Texture | LoadKind::ReloadTexture => {
+            let (img, dim) = ppm_parser(&job.filepath)?;
+            progress(1.0);
+            Ok(Loaded::Texture { img, dim })
+        }
+    }
+}
+
+// Files loaded one after the other on a worker thread, the window keeps drawing the current scene meanwhile.
+// The thread stops when the loader is dropped.
+pub struct Loader {
+    jobs: Sender<LoadJob>,
+    messages: Receiver<Message>,
+    // Sent and not finished yet, the first one is being loaded.
+    pub pending: VecDeque<LoadJob>,
+    pub progress: f32,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        let (jobs, job_receiver) = channel::<LoadJob>();
+        let (message_sender, messages) = channel();
+        thread::spawn(move || {
+            for job in job_receiver {
+                let result = load(&job, &|fraction| {
+                    let _ = message_sender.send(Message::Progress(fraction));
+                });
+                if message_sender.send(Message::Done(result)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            jobs,
+            messages,
+            pending: VecDeque::new(),
+            progress: 0.0,
+        }
+    }
+
+    pub fn send(&mut self, kind: LoadKind, filepath: &str, target: Option<usize>) {
+        let job = LoadJob { kind, filepath: filepath.to_string(), target };
+        if self.jobs.send(job.clone()).is_ok() {
+            self.pending.push_back(job);
+        }
+    }
+
+    // Keeps the waiting jobs on their object after the object at index left the scene.
+    pub fn remove_target(&mut self, index: usize) {
+        for job in &mut self.pending {
+            job.target = match job.target {
+                Some(target) if target == index => None,
+                Some(target) if target > index => Some(target - 1),
+                target => target
+            };
+        }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    // Jobs finished since the last call, in the order they were sent.
+    pub fn poll(&mut self) -> Vec<(LoadJob, Result<Loaded, String>)> {
+        let mut finished = Vec::new();
+        while let Ok(message) = self.messages.try_recv() {
+            match message {
+                Message::Progress(fraction) => self.progress = fraction,
+                Message::Done(result) => {
+                    self.progress = 0.0;
+                    if let Some(job) = self.pending.pop_front() {
+                        finished.push((job, result));
+                    }
+                }
+            }
+        }
+        finished
+    }
+
+    // "Loading monster.obj 42%", with the number of files waiting after it.
+    pub fn status(&self) -> Option<String> {
+        let job = self.pending.front()?;
+        let name = Path::new(&job.filepath).file_name().map_or(job.filepath.clone(), |name| name.to_string_lossy().to_string());
+        let queued = match self.pending.len() - 1 {
+            0 => String::new(),
+            count => format!(" (+{} queued)", count)
+        };
+        Some(format!("Loading {} {:.0}%{}", name, self.progress * 100.0, queued))
+    }
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, time::{Duration, Instant}};
+
+    // Waits for the jobs sent so far.
+    fn finish(loader: &mut Loader) -> Vec<(LoadJob, Result<Loaded, String>)> {
+        let start = Instant::now();
+        let mut finished = Vec::new();
+        while loader.is_busy() {
+            assert!(start.elapsed() < Duration::from_secs(30), "the loader does not answer");
+            finished.extend(loader.poll());
+            thread::sleep(Duration::from_millis(5));
+        }
+        finished
+    }
+
+    #[test]
+    fn progress_goes_up_to_the_end() {
+        let fractions = RefCell::new(Vec::new());
+        let job = LoadJob { kind: LoadKind::Replace, filepath: "./obj/monster.obj".to_string(), target: Some(0) };
+        let Ok(Loaded::Model { obj, mesh, ranges }) = load(&job, &|fraction| fractions.borrow_mut().push(fraction)) else {
+            panic!("monster.obj is not loaded");
+        };
+        assert_eq!(mesh.len(), obj.faces.len() * 3);
+        assert_eq!(ranges.last().unwrap().vertices.end, mesh.len());
+        let fractions = fractions.into_inner();
+        assert_eq!((fractions[0], *fractions.last().unwrap()), (0.0, 1.0));
+        assert!(fractions.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn jobs_finish_in_order_and_errors_do_not_stop_the_others() {
+        let mut loader = Loader::new();
+        loader.send(LoadKind::Add, "./obj/missing.obj", None);
+        loader.send(LoadKind::Texture, "./obj/Texture/metal.ppm", Some(0));
+        loader.send(LoadKind::Replace, "./obj/42.obj", Some(0));
+        assert_eq!(loader.status().unwrap(), "Loading missing.obj 0% (+2 queued)");
+        let finished = finish(&mut loader);
+        let kinds: Vec<LoadKind> = finished.iter().map(|(job, _)| job.kind).collect();
+        assert_eq!(kinds, [LoadKind::Add, LoadKind::Texture, LoadKind::Replace]);
+        assert!(finished[0].1.as_ref().is_err_and(|err| err.contains("missing.obj")));
+        assert!(matches!(finished[1].1, Ok(Loaded::Texture { .. })));
+        assert!(matches!(&finished[2].1, Ok(Loaded::Model { obj, .. }) if obj.path == "./obj/42.obj"));
+        assert_eq!(loader.status(), None);
+    }
+
+    #[test]
+    fn targets_follow_the_removed_objects() {
+        let mut loader = Loader::new();
+        loader.send(LoadKind::Texture, "./obj/Texture/metal.ppm", Some(0));
+        loader.send(LoadKind::Replace, "./obj/42.obj", Some(1));
+        loader.send(LoadKind::Replace, "./obj/42.obj", Some(2));
+        loader.remove_target(1);
+        let targets: Vec<Option<usize>> = loader.pending.iter().map(|job| job.target).collect();
+        assert_eq!(targets, [Some(0), None, Some(1)]);
+        finish(&mut loader);
+    }
+}
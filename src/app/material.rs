@@ -0,0 +1,187 @@
+use std::fs::read_to_string;
+
+use glium::{
+    backend::Facade,
+    texture::RawImage2d,
+    Texture2d
+};
+
+use super::parser::ppm_parser;
+
+// Material from a .mtl file, Pr/Pm/map_Pr/map_Pm follow the PBR extension of the format.
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    pub ka: [f32; 3],
+    pub kd: [f32; 3],
+    pub ks: [f32; 3],
+    pub ke: [f32; 3],
+    pub ns: f32,
+    pub d: f32,
+    pub roughness: Option<f32>,
+    pub metallic: Option<f32>,
+    pub map_kd: Option<String>,
+    pub map_pr: Option<String>,
+    pub map_pm: Option<String>,
+    pub map_ao: Option<String>,
+    pub map_bump: Option<String>,
+}
+
+impl Material {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ka: [0.0, 0.0, 0.0],
+            kd: [0.8, 0.8, 0.8],
+            ks: [0.5, 0.5, 0.5],
+            ke: [0.0, 0.0, 0.0],
+            ns: 32.0,
+            d: 1.0,
+            roughness: None,
+            metallic: None,
+            map_kd: None,
+            map_pr: None,
+            map_pm: None,
+            map_ao: None,
+            map_bump: None,
+        }
+    }
+
+    // Without Pr the roughness is derived from the Phong exponent.
+    pub fn get_roughness(&self) -> f32 {
+        match (self.roughness, &self.map_pr) {
+            (Some(roughness), _) => roughness,
+            (None, Some(_)) => 1.0,
+            (None, None) => (2.0 / (self.ns.max(0.0) + 2.0)).sqrt()
+        }
+    }
+
+    pub fn get_metallic(&self) -> f32 {
+        match (self.metallic, &self.map_pm) {
+            (Some(metallic), _) => metallic,
+            (None, Some(_)) => 1.0,
+            (None, None) => 0.0
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new("off")
+    }
+}
+
+fn get_color(splited: &[&str], line: &str) -> Result<[f32; 3], String> {
+    let values: Vec<f32> = splited.iter()
+        .map(|s| s.parse::<f32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Error: Invalid color {}, values must be f32.", line))?;
+    match values.len() {
+        1 => Ok([values[0], values[0], values[0]]),
+        3 => Ok([values[0], values[1], values[2]]),
+        _ => Err(format!("Error: Invalid format : {}.", line))
+    }
+}
+
+fn get_float(splited: &[&str], line: &str) -> Result<f32, String> {
+    if splited.len() != 1 {
+        return Err(format!("Error: Invalid format : {}.", line));
+    }
+    splited[0].parse::<f32>().map_err(|_| format!("Error: Invalid value {}, value must be f32.", line))
+}
+
+// Texture options (-bm, -s...) are skipped, the file name is the last argument.
+fn get_map(splited: &[&str], parent: &str, line: &str) -> Result<String, String> {
+    match splited.last() {
+        Some(file) => Ok(format!("{}/{}", parent, file)),
+        None => Err(format!("Error: Invalid format : {}.", line))
+    }
+}
+
+pub fn mtl_parser(filepath: &str) -> Result<Vec<Material>, String> {
+    let content = read_to_string(filepath).map_err(|e| format!("Error: Cannot open {}. {}", filepath, e))?;
+    let parent = match filepath.rsplit_once('/') {
+        Some((parent, _)) => parent,
+        None => "."
+    };
+    let mut materials: Vec<Material> = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let splited: Vec<&str> = rest.split_whitespace().collect();
+        if key == "newmtl" {
+            if splited.len() != 1 {
+                return Err(format!("Error: Invalid format : {}.", line));
+            }
+            materials.push(Material::new(splited[0]));
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(format!("Error: {} is set before any newmtl.", key))
+        };
+        match key {
+            "Ka" => material.ka = get_color(&splited, line)?,
+            "Kd" => material.kd = get_color(&splited, line)?,
+            "Ks" => material.ks = get_color(&splited, line)?,
+            "Ke" => material.ke = get_color(&splited, line)?,
+            "Ns" => material.ns = get_float(&splited, line)?,
+            "d" => material.d = get_float(&splited, line)?,
+            "Tr" => material.d = 1.0 - get_float(&splited, line)?,
+            "Pr" => material.roughness = Some(get_float(&splited, line)?.clamp(0.0, 1.0)),
+            "Pm" => material.metallic = Some(get_float(&splited, line)?.clamp(0.0, 1.0)),
+            "map_Kd" => material.map_kd = Some(get_map(&splited, parent, line)?),
+            "map_Pr" => material.map_pr = Some(get_map(&splited, parent, line)?),
+            "map_Pm" => material.map_pm = Some(get_map(&splited, parent, line)?),
+            "map_ao" => material.map_ao = Some(get_map(&splited, parent, line)?),
+            "map_Bump" | "map_bump" | "bump" | "norm" => material.map_bump = Some(get_map(&splited, parent, line)?),
+            // Illumination model, transmission and the maps not used by the renderer.
+            "illum" | "Ni" | "Tf" | "Ps" | "Pc" | "Pcr" | "aniso" | "anisor"
+                | "map_Ka" | "map_Ks" | "map_Ns" | "map_d" | "map_Ke" | "map_Ps" | "disp" | "decal" | "refl" => {},
+            _ => return Err(format!("Error: Invalid Token {}.", key)),
+        }
+    }
+    Ok(materials)
+}
+
+// Material textures uploaded to the GPU, missing maps are replaced by a white texel.
+// The normal map comes from map_Bump/norm and is read as a tangent space normal map.
+pub struct GpuMaterial {
+    pub material: Material,
+    pub base_color_map: Option<Texture2d>,
+    pub metallic_map: Texture2d,
+    pub roughness_map: Texture2d,
+    pub occlusion_map: Texture2d,
+    pub normal_map: Option<Texture2d>,
+}
+
+pub fn white_texture(display: &dyn Facade) -> Texture2d {
+    let img = RawImage2d::from_raw_rgb(vec![255u8, 255, 255], (1, 1));
+    Texture2d::new(display, img).expect("Error: Failed to create texture")
+}
+
+fn load_map(display: &dyn Facade, path: &Option<String>) -> Option<Texture2d> {
+    let path = path.as_ref()?;
+    match ppm_parser(path).and_then(|(img, _)| Texture2d::new(display, img).map_err(|e| format!("Failed to create texture: {:?}", e))) {
+        Ok(tex) => Some(tex),
+        Err(err) => {
+            println!("Warning: {} ignored. {}", path, err);
+            None
+        }
+    }
+}
+
+impl GpuMaterial {
+    pub fn new(display: &dyn Facade, material: &Material) -> Self {
+        Self {
+            material: material.clone(),
+            base_color_map: load_map(display, &material.map_kd),
+            metallic_map: load_map(display, &material.map_pm).unwrap_or_else(|| white_texture(display)),
+            roughness_map: load_map(display, &material.map_pr).unwrap_or_else(|| white_texture(display)),
+            occlusion_map: load_map(display, &material.map_ao).unwrap_or_else(|| white_texture(display)),
+            normal_map: load_map(display, &material.map_bump),
+        }
+    }
+}
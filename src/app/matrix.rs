@@ -1,4 +1,17 @@
-use super::ctx::Ctx;
+use super::{ctx::Ctx, scene::{SceneObject, Transform}, vec::Normal};
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalized(v: [f32; 3]) -> [f32; 3] {
+    let len = dot(v, v).sqrt();
+    if len == 0.0 {
+        v
+    } else {
+        [v[0] / len, v[1] / len, v[2] / len]
+    }
+}
 
 pub struct Matrix {
     mx: [f32; 4],
@@ -8,20 +21,21 @@ pub struct Matrix {
 }
 
 impl Matrix {
-    pub fn new_rotation(ctx: &Ctx) -> Self {
-        let cos: f32 = ctx.rot_speed.cos();
-        let sin: f32 = ctx.rot_speed.sin();
+    pub fn new_rotation(transform: &Transform) -> Self {
+        let cos: f32 = transform.angle.cos();
+        let sin: f32 = transform.angle.sin();
+        let [x, y, z] = transform.position;
         Self {
             mx: [   cos          ,       0.0       ,       -sin,         0.0],
             my: [   0.0          ,       1.0       ,        0.0,         0.0],
             mz: [   sin          ,       0.0       ,        cos,         0.0],
-            mw: [ ctx.x_factor   ,  ctx.y_factor   ,   ctx.z_factor,     1.0f32]
+            mw: [      x         ,        y        ,          z,     1.0f32]
         }
     }
     pub  fn new_perspective(ctx: &Ctx) -> Self {
         let aspect_ratio: f32 = ctx.height as f32 / ctx.width as f32;
 
-        let fov: f32 = std::f32::consts::PI / 2.0;
+        let fov: f32 = ctx.fov;
         let zfar: f32 = 2048.0;
         let znear: f32 = 0.1;
         let f: f32 = 1.0 / (fov / 2.0).tan();
@@ -33,8 +47,123 @@ impl Matrix {
             mw: [         0.0         ,    0.0, -(2.0*zfar*znear)/(zfar-znear),   0.0]
         }
     }
+    pub fn new_perspective_fov(fov: f32, aspect_ratio: f32, znear: f32, zfar: f32) -> Self {
+        let f: f32 = 1.0 / (fov / 2.0).tan();
+
+        Self {
+            mx: [f *   aspect_ratio   ,    0.0,              0.0              ,   0.0],
+            my: [         0.0         ,     f,               0.0              ,   0.0],
+            mz: [         0.0         ,    0.0,  (zfar+znear)/(zfar-znear)    ,   1.0],
+            mw: [         0.0         ,    0.0, -(2.0*zfar*znear)/(zfar-znear),   0.0]
+        }
+    }
+    pub fn new_orthographic(half_size: f32, znear: f32, zfar: f32) -> Self {
+        Self {
+            mx: [1.0 / half_size,       0.0      ,             0.0             , 0.0],
+            my: [      0.0      , 1.0 / half_size,             0.0             , 0.0],
+            mz: [      0.0      ,       0.0      ,     2.0 / (zfar - znear)    , 0.0],
+            mw: [      0.0      ,       0.0      , -(zfar+znear)/(zfar-znear)  , 1.0]
+        }
+    }
+    // View matrix looking from eye toward target, +Z forward like the camera.
+    pub fn new_look_at(eye: [f32; 3], target: [f32; 3], up: [f32; 3]) -> Self {
+        let f = normalized([target[0] - eye[0], target[1] - eye[1], target[2] - eye[2]]);
+        let mut r = normalized(up.cross_product(f));
+        if r == [0.0; 3] {
+            r = normalized([0.0, 0.0, 1.0].cross_product(f));
+        }
+        let u = f.cross_product(r);
+        Self {
+            mx: [r[0], u[0], f[0], 0.0],
+            my: [r[1], u[1], f[1], 0.0],
+            mz: [r[2], u[2], f[2], 0.0],
+            mw: [-dot(r, eye), -dot(u, eye), -dot(f, eye), 1.0]
+        }
+    }
+    pub fn new_translation(t: [f32; 3]) -> Self {
+        Self {
+            mx: [1.0, 0.0, 0.0, 0.0],
+            my: [0.0, 1.0, 0.0, 0.0],
+            mz: [0.0, 0.0, 1.0, 0.0],
+            mw: [t[0], t[1], t[2], 1.0]
+        }
+    }
+    pub fn new_scale(s: [f32; 3]) -> Self {
+        Self {
+            mx: [s[0], 0.0, 0.0, 0.0],
+            my: [0.0, s[1], 0.0, 0.0],
+            mz: [0.0, 0.0, s[2], 0.0],
+            mw: [0.0, 0.0, 0.0, 1.0]
+        }
+    }
+    // Euler angles in radians, around X first, then Y, then Z.
+    pub fn new_euler(r: [f32; 3]) -> Self {
+        let (sx, cx) = r[0].sin_cos();
+        let (sy, cy) = r[1].sin_cos();
+        let (sz, cz) = r[2].sin_cos();
+        let x = Self {
+            mx: [1.0, 0.0, 0.0, 0.0],
+            my: [0.0,  cx,  sx, 0.0],
+            mz: [0.0, -sx,  cx, 0.0],
+            mw: [0.0, 0.0, 0.0, 1.0]
+        };
+        let y = Self {
+            mx: [ cy, 0.0, -sy, 0.0],
+            my: [0.0, 1.0, 0.0, 0.0],
+            mz: [ sy, 0.0,  cy, 0.0],
+            mw: [0.0, 0.0, 0.0, 1.0]
+        };
+        let z = Self {
+            mx: [ cz,  sz, 0.0, 0.0],
+            my: [-sz,  cz, 0.0, 0.0],
+            mz: [0.0, 0.0, 1.0, 0.0],
+            mw: [0.0, 0.0, 0.0, 1.0]
+        };
+        z.multiply(&y).multiply(&x)
+    }
+    pub fn from_4x4(m: [[f32; 4]; 4]) -> Self {
+        Self {
+            mx: m[0],
+            my: m[1],
+            mz: m[2],
+            mw: m[3]
+        }
+    }
+    // Same transform as the vertex shader: rotation around the object center then translation.
+    pub fn new_model(object: &SceneObject) -> Self {
+        let c = object.obj.centroid;
+        Matrix::new_translation(c)
+            .multiply(&Matrix::new_rotation(&object.transform))
+            .multiply(&Matrix::new_translation([-c[0], -c[1], -c[2]]))
+    }
+    pub fn multiply(&self, other: &Matrix) -> Self {
+        let a = [self.mx, self.my, self.mz, self.mw];
+        let b = [other.mx, other.my, other.mz, other.mw];
+        let mut ret = [[0.0f32; 4]; 4];
+        for (col, column) in ret.iter_mut().enumerate() {
+            for (row, value) in column.iter_mut().enumerate() {
+                *value = (0..4).map(|k| a[k][row] * b[col][k]).sum();
+            }
+        }
+        Self {
+            mx: ret[0],
+            my: ret[1],
+            mz: ret[2],
+            mw: ret[3]
+        }
+    }
+    // Inverse transpose of the upper 3x3, keeps the normals perpendicular under a non uniform scale.
+    pub fn normal_matrix(&self) -> [[f32; 3]; 3] {
+        let [a, b, c] = [self.mx, self.my, self.mz].map(|column| [column[0], column[1], column[2]]);
+        let (bc, ca, ab) = (b.cross_product(c), c.cross_product(a), a.cross_product(b));
+        let det = dot(a, bc);
+        if det.abs() < 1e-12 {
+            return [a, b, c];
+        }
+        [bc, ca, ab].map(|column| column.map(|value| value / det))
+    }
     pub fn get_4x4_matrix(self) -> [[f32; 4]; 4] {
-        return [
+        [
             self.mx,
             self.my,
             self.mz,
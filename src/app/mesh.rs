@@ -1,14 +1,25 @@
-use super::ctx::Ctx;
+use std::{collections::HashMap, ops::Range};
+
+use super::{
+    light::GizmoVertex,
+    parser::Obj,
+    vec::Normal
+};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Mesh {
 	position: [f32; 3],
 	pub normal: [f32; 3],
 	pub tex_coords: [f32; 2],
+    // Tangent in xyz, w holds the sign of the bitangent: B = w * cross(N, T).
+    pub tangent: [f32; 4],
+    // Corner of the triangle for the wireframe overlay, the component of a hidden edge stays at 1.
+    pub barycentric: [f32; 3],
+    pub hidden_edge: [f32; 3],
     pub id: i32
 }
 
-glium::implement_vertex!(Mesh, position, normal, tex_coords, id);
+glium::implement_vertex!(Mesh, position, normal, tex_coords, tangent, barycentric, hidden_edge, id);
 
 impl Mesh {
     pub fn new(
@@ -21,12 +32,33 @@ impl Mesh {
             position,
             normal,
             tex_coords,
+            tangent: [1.0, 0.0, 0.0, 1.0],
+            barycentric: [1.0, 1.0, 1.0],
+            hidden_edge: [0.0, 0.0, 0.0],
             id
         }
     }
-    pub fn get_mesh_vector(ctx: & mut Ctx) -> Vec<Self> {
+    pub fn position(&self) -> [f32; 3] {
+        self.position
+    }
+    // Vertex ranges of the consecutive faces sharing a material and a group, drawn one after the other.
+    pub fn get_draw_ranges(obj: &Obj) -> Vec<DrawRange> {
+        let mut ranges: Vec<DrawRange> = Vec::new();
+        for (index, face) in obj.faces.iter().enumerate() {
+            match ranges.last_mut() {
+                Some(range) if range.material == face.mtl && range.node == face.group => range.vertices.end = (index + 1) * 3,
+                _ => ranges.push(DrawRange {
+                    material: face.mtl.clone(),
+                    node: face.group,
+                    vertices: index * 3..(index + 1) * 3
+                })
+            }
+        }
+        ranges
+    }
+    pub fn get_mesh_vector(obj: & mut Obj) -> Vec<Self> {
         let mut mesh:  Vec<Mesh> = Vec::new();
-        let obj = & mut ctx.obj;
+        let mut keys: Vec<VertexKey> = Vec::new();
         let vertex_normals = obj.calculate_vertex_normals();
         for face in &obj.faces {
             for i in 0..3 {
@@ -49,10 +81,214 @@ impl Mesh {
                 } else {
                     obj.vt[face.vt[i] as usize]
                 };
-                mesh.push(Mesh::new(vertex, normal, texture, face.id));
+                let mut corner = Mesh::new(vertex, normal, texture, face.id);
+                corner.barycentric = [0.0, 0.0, 0.0];
+                corner.barycentric[i] = 1.0;
+                if let Some(diagonal) = face.diagonal {
+                    corner.hidden_edge[diagonal] = 1.0;
+                }
+                mesh.push(corner);
+                keys.push([face.v[i], face.vn[i], face.vt[i]]);
             }
         }
-        return mesh;
+        compute_tangents(&mut mesh, &keys);
+        mesh
     }
 }
 
+// Vertices drawn with one material and the transform of one scene node.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawRange {
+    pub material: String,
+    pub node: usize,
+    pub vertices: Range<usize>,
+}
+
+// Position, normal and texture coordinate indices of a corner in the .obj file.
+pub type VertexKey = [u32; 3];
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalized(a: [f32; 3]) -> Option<[f32; 3]> {
+    let len = dot(a, a).sqrt();
+    if len > 1e-12 { Some([a[0] / len, a[1] / len, a[2] / len]) } else { None }
+}
+
+// Tangent and bitangent of a triangle along the U and V directions of its texture coordinates,
+// None when the UVs are degenerate. The sign tells whether the UVs are mirrored.
+fn triangle_frame(corners: &[Mesh]) -> Option<([f32; 3], [f32; 3], bool)> {
+    let e1 = sub(corners[1].position, corners[0].position);
+    let e2 = sub(corners[2].position, corners[0].position);
+    let (du1, dv1) = (corners[1].tex_coords[0] - corners[0].tex_coords[0], corners[1].tex_coords[1] - corners[0].tex_coords[1]);
+    let (du2, dv2) = (corners[2].tex_coords[0] - corners[0].tex_coords[0], corners[2].tex_coords[1] - corners[0].tex_coords[1]);
+    let det = du1 * dv2 - du2 * dv1;
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let tangent = normalized([
+        (e1[0] * dv2 - e2[0] * dv1) / det,
+        (e1[1] * dv2 - e2[1] * dv1) / det,
+        (e1[2] * dv2 - e2[2] * dv1) / det
+    ])?;
+    let bitangent = normalized([
+        (e2[0] * du1 - e1[0] * du2) / det,
+        (e2[1] * du1 - e1[1] * du2) / det,
+        (e2[2] * du1 - e1[2] * du2) / det
+    ])?;
+    Some((tangent, bitangent, det > 0.0))
+}
+
+// Any direction perpendicular to the normal, for vertices without usable UVs.
+fn any_tangent(normal: [f32; 3]) -> [f32; 3] {
+    let axis: [f32; 3] = if normal[0].abs() < 0.9 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] };
+    normalized(axis.cross_product(normal))
+        .and_then(|bitangent| normalized(normal.cross_product(bitangent)))
+        .unwrap_or([1.0, 0.0, 0.0])
+}
+
+// Per vertex tangents accumulated like MikkTSpace: at each corner the face tangent is projected on
+// the plane of the vertex normal and weighted by the corner angle measured in that plane, then summed
+// over the corners sharing the same position, normal and UV. Corners with mirrored UVs are kept apart
+// and w holds the sign of the bitangent given by the UV orientation.
+pub fn compute_tangents(mesh: &mut [Mesh], keys: &[VertexKey]) {
+    let mut tangents: HashMap<(VertexKey, bool), [f32; 3]> = HashMap::new();
+    let mut orientations = Vec::with_capacity(mesh.len() / 3);
+    for (face, corners) in mesh.chunks(3).enumerate() {
+        let frame = triangle_frame(corners);
+        orientations.push(frame.map(|(_, _, orientation)| orientation));
+        let (tangent, _, orientation) = match frame {
+            Some(frame) => frame,
+            None => continue
+        };
+        for i in 0..3 {
+            let normal = normalized(corners[i].normal).unwrap_or([0.0, 0.0, 1.0]);
+            let project = |v: [f32; 3]| {
+                let along = dot(normal, v);
+                normalized(sub(v, [normal[0] * along, normal[1] * along, normal[2] * along]))
+            };
+            let to_next = project(sub(corners[(i + 1) % 3].position, corners[i].position));
+            let to_prev = project(sub(corners[(i + 2) % 3].position, corners[i].position));
+            let angle = match (to_next, to_prev) {
+                (Some(a), Some(b)) => dot(a, b).clamp(-1.0, 1.0).acos(),
+                _ => continue
+            };
+            let tangent = project(tangent).unwrap_or([0.0; 3]);
+            let entry = tangents.entry((keys[face * 3 + i], orientation)).or_insert([0.0; 3]);
+            for axis in 0..3 {
+                entry[axis] += tangent[axis] * angle;
+            }
+        }
+    }
+    for (index, vertex) in mesh.iter_mut().enumerate() {
+        let normal = normalized(vertex.normal).unwrap_or([0.0, 0.0, 1.0]);
+        let summed = orientations[index / 3]
+            .and_then(|orientation| Some((normalized(*tangents.get(&(keys[index], orientation))?)?, orientation)));
+        vertex.tangent = match summed {
+            Some((tangent, orientation)) => [tangent[0], tangent[1], tangent[2], if orientation { 1.0 } else { -1.0 }],
+            None => {
+                let tangent = any_tangent(normal);
+                [tangent[0], tangent[1], tangent[2], 1.0]
+            }
+        };
+    }
+}
+
+// Tangent (red), bitangent (green) and normal (blue) of every vertex, in object space.
+pub fn tangent_frame_lines(mesh: &[Mesh], length: f32) -> Vec<GizmoVertex> {
+    let mut lines = Vec::with_capacity(mesh.len() * 6);
+    for vertex in mesh {
+        let p = vertex.position;
+        let t = [vertex.tangent[0], vertex.tangent[1], vertex.tangent[2]];
+        let b = vertex.normal.cross_product(t);
+        let b = [b[0] * vertex.tangent[3], b[1] * vertex.tangent[3], b[2] * vertex.tangent[3]];
+        let axes = [(t, [1.0, 0.0, 0.0]), (b, [0.0, 1.0, 0.0]), (vertex.normal, [0.0, 0.0, 1.0])];
+        for (axis, color) in axes {
+            lines.push(GizmoVertex { position: p, color });
+            lines.push(GizmoVertex {
+                position: [p[0] + axis[0] * length, p[1] + axis[1] * length, p[2] + axis[2] * length],
+                color
+            });
+        }
+    }
+    lines
+}
+
+
+// Segment along the normal of every vertex, in object space.
+pub fn normal_lines(mesh: &[Mesh], length: f32) -> Vec<GizmoVertex> {
+    let mut lines = Vec::with_capacity(mesh.len() * 2);
+    for vertex in mesh {
+        let p = vertex.position;
+        let n = normalized(vertex.normal).unwrap_or([0.0, 0.0, 0.0]);
+        let color = [n[0] * 0.5 + 0.5, n[1] * 0.5 + 0.5, n[2] * 0.5 + 0.5];
+        lines.push(GizmoVertex { position: p, color });
+        lines.push(GizmoVertex { position: [p[0] + n[0] * length, p[1] + n[1] * length, p[2] + n[2] * length], color });
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: [f32; 4], b: [f32; 4]) -> bool {
+        a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5)
+    }
+
+    // Square face from corner along u then v, with a whole texture along the same directions.
+    fn quad(corner: [f32; 3], u: [f32; 3], v: [f32; 3], face: u32, mirrored: bool) -> (Vec<Mesh>, Vec<VertexKey>) {
+        let normal = u.cross_product(v);
+        let at = |a: f32, b: f32| [corner[0] + u[0] * a + v[0] * b, corner[1] + u[1] * a + v[1] * b, corner[2] + u[2] * a + v[2] * b];
+        let uv = |a: f32, b: f32| [if mirrored { 1.0 - a } else { a }, b];
+        let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let mesh = corners.iter().map(|&(a, b)| Mesh::new(at(a, b), normal, uv(a, b), face as i32)).collect();
+        let keys = corners.iter().map(|&(a, b)| [face * 4 + (a + 2.0 * b) as u32, face, face * 4 + (a + 2.0 * b) as u32]).collect();
+        (mesh, keys)
+    }
+
+    #[test]
+    fn cube_faces_get_the_u_direction_and_the_bitangent_sign() {
+        let faces = [
+            ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], false),
+            ([1.0, 0.0, 0.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], false),
+            ([1.0, 0.0, 1.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0], false),
+            ([0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0], true),
+            ([0.0, 1.0, 1.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0], false),
+            ([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0], true),
+        ];
+        for (face, &(corner, u, v, mirrored)) in faces.iter().enumerate() {
+            let (mut mesh, keys) = quad(corner, u, v, face as u32, mirrored);
+            compute_tangents(&mut mesh, &keys);
+            // Mirrored UVs run against u, the bitangent keeps following v thanks to the sign.
+            let expected = if mirrored { [-u[0], -u[1], -u[2], -1.0] } else { [u[0], u[1], u[2], 1.0] };
+            for vertex in &mesh {
+                assert!(close(vertex.tangent, expected), "face {}: {:?}", face, vertex.tangent);
+                let bitangent = vertex.normal.cross_product([vertex.tangent[0], vertex.tangent[1], vertex.tangent[2]]);
+                assert!(dot(bitangent, v) * vertex.tangent[3] > 0.99);
+            }
+        }
+    }
+
+    #[test]
+    fn face_tangents_are_projected_before_being_summed() {
+        // Two faces around a corner with a normal along z, one of them leaning so its tangent rises along z.
+        let normal = [0.0, 0.0, 1.0];
+        let tex = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+        let faces = [[[0.0; 3], [1.0, 0.0, 1.0], [0.0, 1.0, 0.0]], [[0.0; 3], [0.0, 1.0, 0.0], [-1.0, 0.0, 0.0]]];
+        let mut mesh: Vec<Mesh> = faces.iter()
+            .flat_map(|face| face.iter().zip(tex).map(|(&position, uv)| Mesh::new(position, normal, uv, 0)))
+            .collect();
+        let keys = [[0, 0, 0], [1, 0, 1], [2, 0, 2], [0, 0, 0], [2, 0, 1], [3, 0, 2]];
+        compute_tangents(&mut mesh, &keys);
+        // Both faces turn a right angle in the plane of the normal and count the same, the leaning one included.
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!(close(mesh[0].tangent, [half, half, 0.0, 1.0]), "{:?}", mesh[0].tangent);
+        assert_eq!(mesh[0].tangent, mesh[3].tangent);
+    }
+}
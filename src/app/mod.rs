@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod app;
 pub mod rendering;
 pub mod matrix;
@@ -6,4 +7,31 @@ pub mod ctx;
 pub mod parser;
 pub mod utils;
 pub mod vec;
-pub mod mesh;
\ No newline at end of file
+pub mod mesh;
+pub mod textures;
+pub mod clock;
+pub mod light;
+pub mod shadow;
+pub mod material;
+pub mod environment;
+pub mod text;
+pub mod watcher;
+pub mod hot_reload;
+pub mod shading;
+pub mod cli;
+pub mod debug;
+pub mod overlay;
+pub mod hud;
+pub mod image;
+pub mod headless;
+pub mod software;
+pub mod backend;
+pub mod viewer;
+pub mod gif;
+pub mod recording;
+pub mod scene;
+pub mod scene_file;
+pub mod loader;
+pub mod asset_watcher;
+#[cfg(test)]
+mod golden;
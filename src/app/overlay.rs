@@ -0,0 +1,139 @@
+use glium::{
+    backend::Facade,
+    vertex::EmptyVertexAttributes,
+    Program,
+    Rect,
+    Surface,
+    uniform
+};
+
+use super::{
+    ctx::Ctx,
+    matrix::Matrix,
+    scene::SceneObject,
+    shaders::{
+        AXIS_VERTEX_SHADER,
+        BBOX_VERTEX_SHADER,
+        GIZMO_FRAGMENT_SHADER,
+        GRID_FRAGMENT_SHADER,
+        GRID_VERTEX_SHADER
+    },
+    shadow::GROUND_SCALE,
+    text::TextRenderer
+};
+
+// Size of the axis gizmo viewport, in pixels.
+pub const AXIS_GIZMO_SIZE: u32 = 100;
+// Grid size relative to the ground plane, large enough to fade out before its edges.
+const GRID_SCALE: f32 = 12.0;
+
+fn create_program(display: &dyn Facade, vertex_shader: &str, fragment_shader: &str) -> Program {
+    Program::from_source(display, vertex_shader, fragment_shader, None)
+        .expect("Error: \"glium::Program::from_source\" Fail")
+}
+
+// Spacing of the grid lines, a power of ten close to the object size.
+pub fn grid_spacing(radius: f32) -> f32 {
+    10f32.powf((radius.max(0.0001) * 0.5).log10().floor())
+}
+
+// Spatial references drawn over the scene: ground grid, bounding box and world axes.
+pub struct Overlays {
+    grid_program: Program,
+    bbox_program: Program,
+    axis_program: Program,
+}
+
+impl Overlays {
+    pub fn new(display: &dyn Facade) -> Self {
+        Self {
+            grid_program: create_program(display, GRID_VERTEX_SHADER, GRID_FRAGMENT_SHADER),
+            bbox_program: create_program(display, BBOX_VERTEX_SHADER, GIZMO_FRAGMENT_SHADER),
+            axis_program: create_program(display, AXIS_VERTEX_SHADER, GIZMO_FRAGMENT_SHADER),
+        }
+    }
+
+    // Lifted a little above the ground plane to avoid z-fighting with it.
+    pub fn draw_grid<S: Surface>(&self, frame: &mut S, ctx: &Ctx, perspective_matrix: [[f32; 4]; 4]) {
+        let center = ctx.scene.center();
+        let radius = ctx.scene.radius();
+        let uniforms = uniform! {
+            perspective_matrix: perspective_matrix,
+            grid_center: [center[0], ctx.scene.min_y() + radius * 0.001, center[2]],
+            grid_extent: radius * GROUND_SCALE * GRID_SCALE,
+            grid_spacing: grid_spacing(radius)
+        };
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLessOrEqual,
+                write: false,
+                .. Default::default()
+            },
+            blend: glium::Blend::alpha_blending(),
+            .. Default::default()
+        };
+        frame.draw(
+            EmptyVertexAttributes { len: 6 },
+            glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+            &self.grid_program,
+            &uniforms,
+            &params
+        ).unwrap();
+    }
+
+    pub fn draw_bbox<S: Surface>(&self, frame: &mut S, object: &SceneObject, perspective_matrix: [[f32; 4]; 4]) {
+        // Bounds with the node transforms, turned with the object.
+        let (bbox_min, bbox_max) = object.bounds();
+        let uniforms = uniform! {
+            rotation_matrix: Matrix::new_rotation(&object.transform).get_4x4_matrix(),
+            perspective_matrix: perspective_matrix,
+            object_center: object.obj.centroid,
+            bbox_min: bbox_min,
+            bbox_max: bbox_max,
+            bbox_color: [1.0f32, 0.8, 0.2]
+        };
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLess,
+                write: true,
+                .. Default::default()
+            },
+            .. Default::default()
+        };
+        frame.draw(
+            EmptyVertexAttributes { len: 24 },
+            glium::index::NoIndices(glium::index::PrimitiveType::LinesList),
+            &self.bbox_program,
+            &uniforms,
+            &params
+        ).unwrap();
+    }
+
+    // The camera does not turn, the view rotation only changes if it ever does.
+    pub fn draw_axes<S: Surface>(&self, display: &dyn Facade, frame: &mut S, text: &TextRenderer) {
+        let view_rotation = [[1.0f32, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        let (_, height) = frame.get_dimensions();
+        let margin = 8;
+        let params = glium::DrawParameters {
+            viewport: Some(Rect { left: margin, bottom: margin, width: AXIS_GIZMO_SIZE, height: AXIS_GIZMO_SIZE }),
+            line_width: Some(2.0),
+            .. Default::default()
+        };
+        frame.draw(
+            EmptyVertexAttributes { len: 6 },
+            glium::index::NoIndices(glium::index::PrimitiveType::LinesList),
+            &self.axis_program,
+            &uniform! { view_rotation: view_rotation },
+            &params
+        ).unwrap();
+        // Labels at the tips, the text renderer counts pixels from the top left corner.
+        let half = AXIS_GIZMO_SIZE as f32 * 0.5;
+        let labels = [("X", [1.0, 0.3, 0.3, 1.0]), ("Y", [0.3, 1.0, 0.3, 1.0]), ("Z", [0.4, 0.6, 1.0, 1.0])];
+        for (axis, (label, color)) in labels.iter().enumerate() {
+            let tip: Vec<f32> = (0..3).map(|row| view_rotation[axis][row]).collect();
+            let x = margin as f32 + half + tip[0] * 0.8 * half + 3.0;
+            let y = height as f32 - (margin as f32 + half + tip[1] * 0.8 * half) - 12.0;
+            text.draw(display, frame, label, [x, y], 1.5, *color);
+        }
+    }
+}
@@ -1,7 +1,10 @@
-use super::vec::{Normal, Normalize};
+use super::{
+    material::{mtl_parser, Material},
+    vec::{Normal, Normalize}
+};
 
 use glium::{
-    self, glutin::surface::WindowSurface, texture::RawImage2d, Display, Texture2d
+    self, backend::Facade, texture::RawImage2d, Texture2d
 };
 
 // use std::{fs::{read_to_string, File}, io::{BufRead, Cursor}, time::Instant};
@@ -15,7 +18,11 @@ pub struct Face {
     pub vn: [u32; 3],
     pub vt: [u32; 3],
     pub mtl: String,
-    pub id: i32
+    pub id: i32,
+    // Corner facing the edge added when a quad is split, it is not an edge of the polygon.
+    pub diagonal: Option<usize>,
+    // Index in Obj::groups.
+    pub group: usize
 }
 
 impl Face {
@@ -26,7 +33,7 @@ impl Face {
         mtl: String,
         id: i32 ) -> Self 
     {
-        Self { v, vt, vn, mtl, id}
+        Self { v, vt, vn, mtl, id, diagonal: None, group: 0 }
     }
     pub fn from_vvnvt(vvnvt: Vec<[u32; 3]>, mtl: String, id: i32) -> Self {
         Self { 
@@ -34,14 +41,26 @@ impl Face {
             vn: [vvnvt[0][1], vvnvt[1][1], vvnvt[2][1]],
             vt: [vvnvt[0][2], vvnvt[1][2], vvnvt[2][2]],
             mtl,
-            id
+            id,
+            diagonal: None,
+            group: 0
         }
     }
 }
 
+// Part of the model named by the o and g statements. The first group is the whole file,
+// o starts a group under it, g one under the current o and a/b names nest b under a.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    pub name: String,
+    pub parent: Option<usize>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Obj {
     pub s: String,
+    // File the object was read from, empty when built in memory.
+    pub path: String,
     pub name: Option<String>,
     pub mtlpath: Option<String>,
     
@@ -49,6 +68,9 @@ pub struct Obj {
     pub vn: Vec<[f32; 3]>,
     pub vt: Vec<[f32; 2]>,
     pub faces: Vec<Face>,
+    // Parents come before their children.
+    pub groups: Vec<Group>,
+    pub materials: Vec<Material>,
     pub min_x: f32,
     pub max_x: f32,
     pub min_y: f32,
@@ -64,6 +86,7 @@ impl Obj {
     pub fn new() -> Self {
         Self {
             s: "off".to_string(),
+            path: String::new(),
             name: None,
             mtlpath: None,
 
@@ -71,6 +94,8 @@ impl Obj {
             vn: vec!([0.0, 0.0, 0.0]),
             vt: vec!([0.0, 0.0]),
             faces: Vec::new(),
+            groups: vec![Group { name: String::new(), parent: None }],
+            materials: Vec::new(),
             min_x: f32::MAX,
             min_y: f32::MAX,
             min_z: f32::MAX,
@@ -92,7 +117,7 @@ impl Obj {
             );
             faces_normals.push(a.calc_face_normal(b, c));
         }
-        return faces_normals;
+        faces_normals
     }
 
     pub fn calculate_vertex_normals(&mut self) -> Vec<[f32; 3]>{
@@ -109,7 +134,7 @@ impl Obj {
             }
         }
         ret.normalize();
-        return ret;
+        ret
     }
 
     fn init_centroid(& mut self) {
@@ -125,6 +150,34 @@ impl Obj {
         self.centroid = [x / len, y / len, z / len];
     }
 
+    // Group of the path under parent, created when missing.
+    fn find_group(&mut self, parent: usize, path: &str) -> usize {
+        let mut current = parent;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            current = match self.groups.iter().position(|group| group.parent == Some(current) && group.name == name) {
+                Some(index) => index,
+                None => {
+                    self.groups.push(Group { name: name.to_string(), parent: Some(current) });
+                    self.groups.len() - 1
+                }
+            };
+        }
+        current
+    }
+
+    pub fn get_material(&self, name: &str) -> Option<&Material> {
+        self.materials.iter().find(|material| material.name == name)
+    }
+
+    // Radius of the sphere centered on the centroid enclosing the bounding box.
+    pub fn radius(&self) -> f32 {
+        let c = self.centroid;
+        let dx = (self.max_x - c[0]).abs().max((self.min_x - c[0]).abs());
+        let dy = (self.max_y - c[1]).abs().max((self.min_y - c[1]).abs());
+        let dz = (self.max_z - c[2]).abs().max((self.min_z - c[2]).abs());
+        (dx * dx + dy * dy + dz * dz).sqrt()
+    }
+
     fn get_min_max(& mut self){
         let mut min_x = f32::MAX;
         let mut max_x = f32::MIN;
@@ -166,25 +219,25 @@ pub fn check_coherence(parsed_obj: &Obj) -> Result<(), String> {
     let vtlen: u32 = parsed_obj.vt.len() as u32;
 
     if vlen <= 1 {
-        return Err(format!("Error: vertexs must be between 1 and 1e6."));
+        Err("Error: vertexs must be between 1 and 1e6.".to_string())
     } else {
         for face in &parsed_obj.faces {
             for v in &face.v {
-                if *v <= 0 || *v > vlen {
-                    return Err(format!("Error: A face is out of the vertex range."));
+                if *v == 0 || *v > vlen {
+                    return Err("Error: A face is out of the vertex range.".to_string());
                 }
             }
             if vnlen != 1 {
                 for vn in &face.vn {
-                    if *vn <= 0 || *vn > vnlen {
-                        return Err(format!("Error: A vn is out of the vertex normals range."));
+                    if *vn == 0 || *vn > vnlen {
+                        return Err("Error: A vn is out of the vertex normals range.".to_string());
                     }
                 }
             }
             if vtlen != 1 {
                 for vt in &face.vt {
-                    if *vt <= 0 || *vt > vtlen {
-                        return Err(format!("Error: A vn is out of the vertex normals range."));
+                    if *vt == 0 || *vt > vtlen {
+                        return Err("Error: A vn is out of the vertex normals range.".to_string());
                     }
                 }
             }
@@ -203,7 +256,7 @@ fn get_file_lines(filepath: &str) -> Result<Vec<String>, String> {
         .map(String::from)
         .collect();
     
-    lines.retain(|s| !s.starts_with('#'));
+    lines.retain(|s| !s.starts_with('#') && !s.trim().is_empty());
     Ok(lines)
 }
 
@@ -242,22 +295,37 @@ fn triangulize(splited: Vec<&str>) -> Vec<Vec<&str>> {
     if splited.len() == 4 {
         ret.push(vec![splited[0], splited[2], splited[3]]);
     }
-    return ret;
+    ret
 }
 
+// Lines parsed between two progress reports.
+const PROGRESS_LINES: usize = 1 << 14;
+
 pub fn obj_parser(filepath: &str) -> Result<Obj, String> {
+    obj_parser_with_progress(filepath, &|_| {})
+}
+
+// progress is called with the fraction of the lines parsed, from 0 to 1.
+pub fn obj_parser_with_progress(filepath: &str, progress: &dyn Fn(f32)) -> Result<Obj, String> {
     // let start_time = Instant::now();
     let mut current_material = "off".to_string();
     let lines = get_file_lines(filepath)?;
+    let line_count = lines.len();
     let mut obj: Obj = Obj::new();
+    obj.path = filepath.to_string();
     let mut face_id: i32 = 0;
+    // Group of the last o statement and group of the next faces.
+    let (mut object_group, mut current_group) = (0, 0);
     // println!("read: {:.2?}", start_time.elapsed());
-    for line in lines {
+    for (index, line) in lines.into_iter().enumerate() {
+        if index % PROGRESS_LINES == 0 {
+            progress(index as f32 / line_count as f32);
+        }
         if let Some((key, rest)) = line.split_once(' ') {
             let splited: Vec<&str> = rest.split_whitespace().collect();
             match key {
                 "mtllib" => {
-                    if obj.mtlpath == None {
+                    if obj.mtlpath.is_none() {
                         if splited.len() != 1 {
                             return Err(format!("Error: Invalid format : {} {:?}.", key, splited));
                         }
@@ -268,14 +336,20 @@ pub fn obj_parser(filepath: &str) -> Result<Obj, String> {
                     }
                 },
                 "o" => {
-                    if obj.name == None {
-                        if splited.len() != 1 {
-                            return Err(format!("Error: Invalid format : {} {:?}.", key, splited));
-                        }
+                    if splited.len() != 1 {
+                        return Err(format!("Error: Invalid format : {} {:?}.", key, splited));
+                    }
+                    if obj.name.is_none() {
                         obj.name = Some(rest.to_string());
-                    } else {
-                        return Err(format!("Error: object name cannot be set twice {}.", line));
                     }
+                    object_group = obj.find_group(0, rest);
+                    current_group = object_group;
+                },
+                "g" => {
+                    if splited.len() != 1 {
+                        return Err(format!("Error: Invalid format : {} {:?}.", key, splited));
+                    }
+                    current_group = obj.find_group(object_group, rest);
                 },
                 "usemtl" => {
                     if splited.len() != 1 {
@@ -294,8 +368,9 @@ pub fn obj_parser(filepath: &str) -> Result<Obj, String> {
                     if splited.len() < 3 || splited.len() > 4 {
                         return Err(format!("Error: Face can contain only triangles or quadrilaterals : {} {:?}.",key, splited));
                     }
+                    let quad = splited.len() == 4;
                     let face_args = triangulize(splited);
-                    for vec in face_args {
+                    for (triangle, vec) in face_args.into_iter().enumerate() {
                         let mut vvnvt: Vec<[u32; 3]> = Vec::new();
                         for args in vec {
                             let parts: Vec<&str> = args.split('/').collect();
@@ -317,7 +392,13 @@ pub fn obj_parser(filepath: &str) -> Result<Obj, String> {
                                 _ => return Err(format!("Error: Invalid face format in '{}'", args))
                             }
                         }
-                        obj.faces.push(Face::from_vvnvt(vvnvt, current_material.clone(), face_id));
+                        let mut face = Face::from_vvnvt(vvnvt, current_material.clone(), face_id);
+                        face.group = current_group;
+                        // Both triangles share the 0-2 diagonal of the quad.
+                        if quad {
+                            face.diagonal = Some(if triangle == 0 { 1 } else { 2 });
+                        }
+                        obj.faces.push(face);
                         face_id += 1;
                     }
                 },
@@ -329,7 +410,7 @@ pub fn obj_parser(filepath: &str) -> Result<Obj, String> {
                         return Err(format!("Error: Invalid format : {} {:?}.", key, splited));
                     }
                     let v = get_v(&[splited[0], splited[1], splited[2]])?;
-                    if v[0] < -1.0 || v[0] > 1.0 || v[1] < -1.0 || v[1] > 1.0 || v[2] < -1.0 || v[2] > 1.0 {
+                    if v.iter().any(|c| !(-1.0..=1.0).contains(c)) {
                         return Err(format!("Error: Invalid value : {} {:?}.", key, splited));
                     }
                     obj.vn.push(v);
@@ -340,44 +421,54 @@ pub fn obj_parser(filepath: &str) -> Result<Obj, String> {
                     }
                     match (splited[0].parse::<f32>(), splited[1].parse::<f32>()) {
                         (Ok(u), Ok(v)) => {
-                            if u < 0.0 || u > 1.0 || v < 0.0 || v > 1.0 {
+                            if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
                                 return Err(format!("Error: Invalid value : {} {:?}.", key, splited));
                             }
                             obj.vt.push([u, v])
                         },
-                        _ => Err(format!("Error: Invalid texture coordinates must be f32."))?
+                        _ => Err("Error: Invalid texture coordinates must be f32.".to_string())?
                     }
                 }
                 _ => return Err(format!("Error: Invalid Token {}.", key)),
             }
         } else {
-            return Err(format!("Error: A line does not respect the format."));
+            return Err("Error: A line does not respect the format.".to_string());
         }
     }
     // println!("loop: {:.2?}", start_time.elapsed());
-    if let Err(error) = check_coherence(&obj) {
-        return Err(format!("{}", error));
-    }
-    if obj.vt.len() <= 1 {
-        obj.get_min_max();
-    }
+    progress(1.0);
+    check_coherence(&obj)?;
+    obj.get_min_max();
     obj.init_centroid();
+    if let Some(mtlpath) = &obj.mtlpath {
+        match mtl_parser(mtlpath) {
+            Ok(materials) => obj.materials = materials,
+            Err(err) => println!("Warning: materials ignored. {}", err)
+        }
+    }
     // println!("obj_parser: {:.2?}", start_time.elapsed());
     // println!("----------------------");
 
     Ok(obj)
 }
 pub struct Images {
+    pub path: String,
     pub dimension: (u32, u32),
     pub diffuse_texture: Texture2d
 }
 
 impl Images {
-    pub fn new(display: &Display<WindowSurface>, filepath: &str) -> Result<Self, String> {
+    pub fn new(display: &dyn Facade, filepath: &str) -> Result<Self, String> {
         let (img, dim) = ppm_parser(filepath)?;
+        Self::from_raw(display, filepath, img, dim)
+    }
+
+    // Upload of an image already read, by ppm_parser.
+    pub fn from_raw(display: &dyn Facade, filepath: &str, img: RawImage2d<'static, u8>, dim: (u32, u32)) -> Result<Self, String> {
         match Texture2d::new(display, img) {
             Ok(tex) => {
                 Ok(Self {
+                    path: filepath.to_string(),
                     dimension: dim,
                     diffuse_texture: tex
                 })
@@ -389,7 +480,7 @@ impl Images {
     }
 }
 
-pub fn ppm_parser(filepath: &str) -> Result<(RawImage2d<u8>, (u32, u32)), String> {
+pub fn ppm_parser(filepath: &str) -> Result<(RawImage2d<'static, u8>, (u32, u32)), String> {
     // read file
     let file = File::open(filepath)
                 .map_err(|e| format!("Error: Impossible to open {}: {}", filepath, e))?;
@@ -451,3 +542,178 @@ pub fn ppm_parser(filepath: &str) -> Result<(RawImage2d<u8>, (u32, u32)), String
     let img = RawImage2d::from_raw_rgb_reversed(bytes, dim);
     Ok((img, dim))
 }
+
+// Largest side of an environment map, the GPU could not hold a bigger texture anyway.
+const MAX_HDR_SIZE: usize = 16384;
+
+// Radiance RGBE image, flat or run length encoded scanlines.
+// Returns linear RGB floats with the bottom row first, like the textures.
+pub fn hdr_parser(filepath: &str) -> Result<(Vec<f32>, (u32, u32)), String> {
+    let file = File::open(filepath)
+                .map_err(|e| format!("Error: Impossible to open {}: {}", filepath, e))?;
+    let mmap = unsafe {
+        match Mmap::map(&file) {
+            Ok(mmap) => mmap,
+            Err(e) => Err(format!("Failed to map the file: {:?}", e))?
+        }
+    };
+    let mut reader = Cursor::new(&mmap);
+
+    // header
+    let mut line = String::new();
+    reader.read_line(&mut line)
+            .map_err(|e| format!("Error: Header contain an error: {}", e))?;
+    if !line.starts_with("#?") {
+        return Err("Error: unsupported image format".to_string());
+    }
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)
+                .map_err(|e| format!("Error: Header contain an error: {}", e))?;
+        if read == 0 {
+            return Err("Error: Dimensions must be specified.".to_string());
+        }
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            break;
+        }
+        if let Some(format) = trimmed.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(format!("Error: unsupported pixel format {}", format));
+            }
+        }
+    }
+
+    // width / height, only the standard orientation is supported
+    line.clear();
+    reader.read_line(&mut line)
+            .map_err(|e| format!("Error: Header contain an error: {}", e))?;
+    let splited = line.split_whitespace().collect::<Vec<_>>();
+    if splited.len() != 4 || splited[0] != "-Y" || splited[2] != "+X" {
+        return Err("Error: Dimensions must be specified as -Y height +X width.".to_string());
+    }
+    let (w, h) = match (splited[3].parse::<usize>(), splited[1].parse::<usize>()) {
+        (Ok(w), Ok(h)) if w > 0 && h > 0 => (w, h),
+        _ => Err("Error: Dimensions must be u32.".to_string())?,
+    };
+    if w > MAX_HDR_SIZE || h > MAX_HDR_SIZE {
+        return Err(format!("Error: Dimensions {}x{} are larger than {}.", w, h, MAX_HDR_SIZE));
+    }
+
+    // scanlines, flat or packed by at most 127 bytes in 2 per channel, a file too short for them is not allocated
+    let bytes = &mmap[reader.position() as usize..];
+    let truncated = || "Error: Invalid pixmap.".to_string();
+    let smallest_scanline = (w * 4).min(4 + w.div_ceil(127) * 8);
+    let size = w.checked_mul(h)
+        .and_then(|size| size.checked_mul(4))
+        .filter(|_| smallest_scanline * h <= bytes.len())
+        .ok_or_else(truncated)?;
+    let mut pos: usize = 0;
+    let mut rgbe: Vec<u8> = vec![0; size];
+    for y in 0..h {
+        let scanline = &mut rgbe[y * w * 4..(y + 1) * w * 4];
+        let header = bytes.get(pos..pos + 4).ok_or_else(truncated)?;
+        let rle = (8..0x8000).contains(&w) && header[0] == 2 && header[1] == 2 && header[2] & 0x80 == 0;
+        if !rle {
+            let flat = bytes.get(pos..pos + w * 4).ok_or_else(truncated)?;
+            // A pixel of 1, 1, 1 repeats the previous one in the run length encoding of the first Radiance versions.
+            if flat.chunks_exact(4).any(|pixel| pixel[..3] == [1, 1, 1]) {
+                return Err("Error: Old-style run length encoded scanlines are not supported, save the image again.".to_string());
+            }
+            scanline.copy_from_slice(flat);
+            pos += w * 4;
+            continue;
+        }
+        if ((header[2] as usize) << 8 | header[3] as usize) != w {
+            return Err("Error: Invalid scanline width.".to_string());
+        }
+        pos += 4;
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < w {
+                let count = *bytes.get(pos).ok_or_else(truncated)? as usize;
+                pos += 1;
+                if count > 128 {
+                    let count = count - 128;
+                    let value = *bytes.get(pos).ok_or_else(truncated)?;
+                    pos += 1;
+                    if count == 0 || x + count > w {
+                        return Err(truncated());
+                    }
+                    for i in 0..count {
+                        scanline[(x + i) * 4 + channel] = value;
+                    }
+                    x += count;
+                } else {
+                    let values = bytes.get(pos..pos + count).ok_or_else(truncated)?;
+                    pos += count;
+                    if count == 0 || x + count > w {
+                        return Err(truncated());
+                    }
+                    for (i, value) in values.iter().enumerate() {
+                        scanline[(x + i) * 4 + channel] = *value;
+                    }
+                    x += count;
+                }
+            }
+        }
+    }
+
+    let mut rgb: Vec<f32> = Vec::with_capacity(w * h * 3);
+    for y in (0..h).rev() {
+        for pixel in rgbe[y * w * 4..(y + 1) * w * 4].chunks_exact(4) {
+            let scale = if pixel[3] == 0 { 0.0 } else { 2f32.powi(pixel[3] as i32 - 136) };
+            rgb.push(pixel[0] as f32 * scale);
+            rgb.push(pixel[1] as f32 * scale);
+            rgb.push(pixel[2] as f32 * scale);
+        }
+    }
+    Ok((rgb, (w as u32, h as u32)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+
+    // Radiance file with the given size line and scanline bytes.
+    fn hdr(name: &str, size: &str, scanlines: &[u8]) -> String {
+        let filepath = std::env::temp_dir().join(format!("{}.hdr", name));
+        let mut content = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{}\n", size).into_bytes();
+        content.extend_from_slice(scanlines);
+        write(&filepath, content).unwrap();
+        filepath.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn hdr_sizes_are_checked_before_reading() {
+        let (rgb, dim) = hdr_parser(&hdr("scop_flat", "-Y 1 +X 2", &[128, 64, 32, 129, 0, 0, 0, 0])).unwrap();
+        assert_eq!((rgb, dim), (vec![1.0, 0.5, 0.25, 0.0, 0.0, 0.0], (2, 1)));
+        // Eight pixels in 12 bytes, as small as a scanline gets.
+        let (rgb, _) = hdr_parser(&hdr("scop_rle", "-Y 1 +X 8", &[2, 2, 0, 8, 136, 128, 136, 64, 136, 32, 136, 129])).unwrap();
+        assert_eq!(rgb, [1.0, 0.5, 0.25].repeat(8));
+        let huge = hdr_parser(&hdr("scop_huge", "-Y 100000 +X 100000", &[0; 8])).unwrap_err();
+        assert!(huge.contains("larger than"), "{}", huge);
+        // The header claims more pixels than the file could hold, nothing is allocated for them.
+        assert_eq!(hdr_parser(&hdr("scop_short", "-Y 16384 +X 16384", &[2, 2, 64, 0])).unwrap_err(), "Error: Invalid pixmap.");
+    }
+
+    #[test]
+    fn old_run_length_encoding_is_reported() {
+        let err = hdr_parser(&hdr("scop_old_rle", "-Y 1 +X 2", &[128, 64, 32, 129, 1, 1, 1, 1])).unwrap_err();
+        assert!(err.contains("Old-style run length"), "{}", err);
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let parse = |name: &str, content: &str| {
+            let filepath = std::env::temp_dir().join(format!("{}.obj", name));
+            write(&filepath, content).unwrap();
+            obj_parser(filepath.to_str().unwrap()).unwrap()
+        };
+        let spaced = parse("scop_blank_lines", "v 0 0 0\nv 1 0 0\n\n  \nv 0 1 0\n\nf 1 2 3\n");
+        let packed = parse("scop_no_blank_lines", "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n");
+        assert_eq!((spaced.vertexs, spaced.faces.len()), (packed.vertexs, 1));
+        assert!(obj_parser("./obj/teapot.obj").is_ok());
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    f32::consts::TAU,
+    fs::read_to_string,
+    path::{Path, PathBuf}
+};
+
+use super::{
+    ctx::{Ctx, SCREENSHOT_DIR},
+    gif,
+    image::Image,
+    scene::Transform
+};
+
+// Frames of a turntable recorded from the window, one every 5 degrees.
+pub const RECORD_FRAMES: u32 = 72;
+pub const DEFAULT_FPS: u32 = 24;
+
+// Position in front of the camera and rotation of the active object at a given time.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Keyframe {
+    pub time: f32,
+    pub position: [f32; 3],
+    // Radians, not wrapped so a path can turn several times.
+    pub angle: f32,
+}
+
+impl Keyframe {
+    pub fn from_ctx(ctx: &Ctx, time: f32) -> Self {
+        let transform = ctx.scene.active().transform;
+        Self {
+            time,
+            position: transform.position,
+            angle: transform.angle,
+        }
+    }
+
+    // The other objects keep their offset from the active one in start, the transforms before the recording.
+    pub fn apply(&self, ctx: &mut Ctx, start: &[Transform]) {
+        let Some(&origin) = start.get(ctx.scene.active) else {
+            return;
+        };
+        for (object, start) in ctx.scene.objects.iter_mut().zip(start) {
+            object.transform = Transform::new(
+                [0, 1, 2].map(|i| start.position[i] + self.position[i] - origin.position[i]),
+                (start.angle + self.angle - origin.angle).rem_euclid(TAU)
+            );
+        }
+    }
+
+    fn lerp(&self, other: &Keyframe, t: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        Self {
+            time: mix(self.time, other.time),
+            position: [0, 1, 2].map(|i| mix(self.position[i], other.position[i])),
+            angle: mix(self.angle, other.angle),
+        }
+    }
+}
+
+// Keyframes sorted by time, sampled with linear interpolation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+    // The last keyframe is the first one again, it is not recorded twice.
+    pub looping: bool,
+}
+
+impl CameraPath {
+    // One full turn of the object from where it stands.
+    pub fn turntable(ctx: &Ctx, duration: f32) -> Self {
+        let start = Keyframe::from_ctx(ctx, 0.0);
+        let end = Keyframe { time: duration, angle: start.angle + TAU, ..start };
+        Self { keyframes: vec![start, end], looping: true }
+    }
+
+    // One keyframe per line: time in seconds, position x,y,z and angle in degrees.
+    // Lines starting with # are comments.
+    pub fn parse(filepath: &str) -> Result<Self, String> {
+        let content = read_to_string(filepath).map_err(|e| format!("Error: Cannot open {}. {}", filepath, e))?;
+        let mut keyframes: Vec<Keyframe> = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("Error: {} line {}: expected <time> <x>,<y>,<z> <degrees>.", filepath, number + 1);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [time, position, angle] = fields[..] else {
+                return Err(invalid());
+            };
+            let time = time.parse::<f32>().ok().filter(|time| time.is_finite()).ok_or_else(invalid)?;
+            let position = position.split(',')
+                .map(|value| value.parse::<f32>().ok().filter(|value| value.is_finite()))
+                .collect::<Option<Vec<f32>>>()
+                .and_then(|values| <[f32; 3]>::try_from(values).ok())
+                .ok_or_else(invalid)?;
+            let angle = angle.parse::<f32>().ok().filter(|angle| angle.is_finite()).ok_or_else(invalid)?;
+            if keyframes.last().is_some_and(|last| time <= last.time) {
+                return Err(format!("Error: {} line {}: times must increase.", filepath, number + 1));
+            }
+            keyframes.push(Keyframe { time, position, angle: angle.to_radians() });
+        }
+        if keyframes.len() < 2 {
+            return Err(format!("Error: {} needs at least two keyframes.", filepath));
+        }
+        Ok(Self { keyframes, looping: false })
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |last| last.time) - self.keyframes[0].time
+    }
+
+    pub fn sample(&self, time: f32) -> Keyframe {
+        let time = time + self.keyframes[0].time;
+        let next = self.keyframes.iter().position(|keyframe| keyframe.time > time);
+        match next {
+            Some(0) => self.keyframes[0],
+            Some(index) => {
+                let (a, b) = (&self.keyframes[index - 1], &self.keyframes[index]);
+                a.lerp(b, (time - a.time) / (b.time - a.time))
+            },
+            None => *self.keyframes.last().unwrap()
+        }
+    }
+}
+
+// Numbered file of a sequence, shot.png gives shot_0001.png.
+pub fn frame_path(filepath: &Path, index: u32) -> PathBuf {
+    let stem = filepath.file_stem().and_then(|stem| stem.to_str()).unwrap_or("frame");
+    let name = match filepath.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => format!("{}_{:04}.{}", stem, index, extension),
+        None => format!("{}_{:04}", stem, index)
+    };
+    filepath.with_file_name(name)
+}
+
+// Sequence of frames at fixed times, whatever the time taken to draw them.
+pub struct Recording {
+    pub path: CameraPath,
+    pub frames: u32,
+    pub fps: u32,
+    // Numbered after this name, no image files when missing.
+    pub output: Option<PathBuf>,
+    pub gif: Option<PathBuf>,
+    pub index: u32,
+    images: Vec<Image>,
+    // Where the objects were before the recording.
+    start: Vec<Transform>,
+}
+
+impl Recording {
+    pub fn new(path: CameraPath, frames: u32, fps: u32, ctx: &Ctx) -> Self {
+        Self {
+            path,
+            frames: frames.max(1),
+            fps: fps.max(1),
+            output: None,
+            gif: None,
+            index: 0,
+            images: Vec::new(),
+            start: ctx.scene.objects.iter().map(|object| object.transform).collect(),
+        }
+    }
+
+    // Turntable from the window, saved in a new folder of the screenshot directory.
+    pub fn from_window(ctx: &Ctx) -> Self {
+        let dir = (1..).map(|index| Path::new(SCREENSHOT_DIR).join(format!("recording_{}", index)))
+            .find(|dir| !dir.exists())
+            .unwrap();
+        let path = CameraPath::turntable(ctx, RECORD_FRAMES as f32 / DEFAULT_FPS as f32);
+        let mut recording = Self::new(path, RECORD_FRAMES, DEFAULT_FPS, ctx);
+        recording.output = Some(dir.join("frame.png"));
+        recording.gif = Some(dir.join("turntable.gif"));
+        recording
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.index >= self.frames
+    }
+
+    // Time of a frame, a looping path stops one frame before its end.
+    pub fn time(&self, index: u32) -> f32 {
+        let steps = if self.path.looping { self.frames } else { (self.frames - 1).max(1) };
+        self.path.duration() * index as f32 / steps as f32
+    }
+
+    // Moves the objects where the next frame is taken.
+    pub fn apply(&self, ctx: &mut Ctx) {
+        self.path.sample(self.time(self.index)).apply(ctx, &self.start);
+    }
+
+    // Saves the image of the current frame and moves to the next one.
+    pub fn add(&mut self, image: Image) -> Result<(), String> {
+        self.index += 1;
+        if let Some(output) = &self.output {
+            let filepath = if self.frames > 1 { frame_path(output, self.index) } else { output.clone() };
+            image.save(&filepath)?;
+            println!("Screenshot: {} ({}x{})", filepath.display(), image.width, image.height);
+        }
+        if self.gif.is_some() {
+            self.images.push(image);
+        }
+        Ok(())
+    }
+
+    // Writes the GIF once every frame is taken and puts the objects back.
+    pub fn finish(&mut self, ctx: &mut Ctx) -> Result<(), String> {
+        for (object, start) in ctx.scene.objects.iter_mut().zip(&self.start) {
+            object.transform = *start;
+        }
+        if let Some(filepath) = &self.gif {
+            gif::save(filepath, &self.images, self.fps)?;
+            println!("Animation: {} ({} frames)", filepath.display(), self.images.len());
+        }
+        Ok(())
+    }
+}
@@ -1,79 +1,421 @@
+use std::{collections::HashMap, rc::Rc};
+
 use glium::{
     self,
-    glutin::surface::WindowSurface,
+    backend::{Context, Facade},
     Surface,
-    Display,
     VertexBuffer,
     uniform
 };
 
 use super::{
-    ctx::Ctx, 
+    backend::PipelineState,
+    ctx::{Ctx, WIREFRAME_COLORS},
+    debug::DebugView,
+    environment::{environment_rotation, Ibl, PREFILTERED_LEVELS},
+    hot_reload::ShaderReloader,
+    hud::{Hud, HudStats},
+    image::Image,
+    material::{GpuMaterial, Material, white_texture},
+    light::{gizmo_vertices, uniform_index, GizmoVertex, LightUniforms},
     matrix::Matrix,
-    shaders::Shader, 
-    mesh::Mesh,
-    parser::Images
+    shaders::{
+        DEBUG_FRAGMENT_SHADER,
+        GIZMO_FRAGMENT_SHADER,
+        MAIN_VERTEX_SHADER,
+        GIZMO_VERTEX_SHADER,
+        GROUND_FRAGMENT_SHADER,
+        GROUND_VERTEX_SHADER,
+        OUTLINE_FRAGMENT_SHADER,
+        OUTLINE_VERTEX_SHADER,
+        MODEL_LINE_VERTEX_SHADER
+    },
+    overlay::Overlays,
+    shading::{ModeUniforms, ShadingRegistry},
+    shadow::{ground_mesh, ShadowMap},
+    text::TextRenderer,
+    mesh::{normal_lines, tangent_frame_lines, DrawRange, Mesh},
+    parser::{ppm_parser, Images, Obj},
+    scene::SceneObject,
+    software::{self, CpuTexture, SoftwareObject, SoftwareShading},
+    textures::Textures
 };
 
+pub const DEFAULT_MATCAP: &str = "./obj/Matcap/clay.ppm";
+pub const DEFAULT_TEXTURE: &str = "./obj/Texture/rust.ppm";
 
-pub struct Renderer {
+// Mesh, materials and textures of one object of the scene.
+pub struct GpuObject {
     pub mesh: Vec<Mesh>,
-    pub img: Images,
-    shaders: Shader,
+    pub ranges: Vec<DrawRange>,
+    pub materials: Vec<GpuMaterial>,
+    pub textures: Textures,
+}
+
+impl GpuObject {
+    pub fn new(display: &dyn Facade, obj: &mut Obj, textures: Textures) -> Self {
+        let mut object = Self {
+            mesh: Vec::new(),
+            ranges: Vec::new(),
+            materials: Vec::new(),
+            textures,
+        };
+        object.load_obj(display, obj);
+        object
+    }
+
+    pub fn load_obj(&mut self, display: &dyn Facade, obj: &mut Obj) {
+        let mesh = Mesh::get_mesh_vector(obj);
+        let ranges = Mesh::get_draw_ranges(obj);
+        self.load_mesh(display, obj, mesh, ranges);
+    }
+
+    // Mesh already built from obj, only the materials are loaded.
+    pub fn load_mesh(&mut self, display: &dyn Facade, obj: &Obj, mesh: Vec<Mesh>, ranges: Vec<DrawRange>) {
+        self.mesh = mesh;
+        self.ranges = ranges;
+        self.materials = obj.materials.iter()
+            .map(|material| GpuMaterial::new(display, material))
+            .collect();
+    }
+}
+
+pub struct Renderer {
+    // One per object of ctx.scene, in the same order.
+    pub objects: Vec<GpuObject>,
+    default_material: GpuMaterial,
+    // Dropped normal map, used instead of the ones of the materials.
+    pub normal_map: Option<glium::Texture2d>,
+    flat_normal: glium::Texture2d,
+    pub matcap: Option<Images>,
+    pub shading: ShadingRegistry,
+    pub reloader: ShaderReloader,
+    text: TextRenderer,
+    gizmo_program: glium::Program,
+    model_line_program: glium::Program,
+    outline_program: glium::Program,
+    debug_program: glium::Program,
+    overlays: Overlays,
+    ground_program: glium::Program,
+    shadow_map: ShadowMap,
+    pub ibl: Ibl,
+    // CPU copies of the textures for the software renderer, loaded when first needed, None when unreadable.
+    software_textures: HashMap<String, Option<CpuTexture>>,
+}
+
+// Built-in programs of the renderer, they do not depend on the shading mode.
+fn program(display: &dyn Facade, vertex: &str, fragment: &str) -> Result<glium::Program, String> {
+    glium::Program::from_source(display, vertex, fragment, None)
+        .map_err(|err| format!("Error: Built-in shaders do not compile. {}", err))
 }
 
 impl Renderer {
-    pub fn new(display: &Display<WindowSurface>, ctx: & mut Ctx) -> Self {
-        Self {
-            mesh: Mesh::get_mesh_vector(ctx),
-            shaders: Shader::default(),
-            img: {
-                let img = Images::new(display, "./obj/Texture/rust.ppm");
-                match img {
-                    Ok(img) => img,
-                    Err(err) => {
-                        println!("{:?}", err);
-                        std::process::exit(1);
-                    }
+    // Fails on an unknown shading mode, built-in shaders that do not compile or a missing default texture.
+    // The mesh is uploaded by load_obj.
+    pub fn new(display: &dyn Facade, ctx: &Ctx) -> Result<Self, String> {
+        let mut shading = ShadingRegistry::new();
+        shading.select(&ctx.shading)?;
+        Ok(Self {
+            objects: Vec::new(),
+            default_material: GpuMaterial::new(display, &Material::default()),
+            normal_map: None,
+            flat_normal: white_texture(display),
+            matcap: match Images::new(display, DEFAULT_MATCAP) {
+                Ok(img) => Some(img),
+                Err(err) => {
+                    println!("Warning: {} ignored. {}", DEFAULT_MATCAP, err);
+                    None
+                }
+            },
+            reloader: ShaderReloader::new(display, &shading)?,
+            shading,
+            text: TextRenderer::new(display),
+            gizmo_program: program(display, GIZMO_VERTEX_SHADER, GIZMO_FRAGMENT_SHADER)?,
+            model_line_program: program(display, MODEL_LINE_VERTEX_SHADER, GIZMO_FRAGMENT_SHADER)?,
+            outline_program: program(display, OUTLINE_VERTEX_SHADER, OUTLINE_FRAGMENT_SHADER)?,
+            debug_program: program(display, MAIN_VERTEX_SHADER, DEBUG_FRAGMENT_SHADER)?,
+            overlays: Overlays::new(display),
+            ground_program: program(display, GROUND_VERTEX_SHADER, GROUND_FRAGMENT_SHADER)?,
+            shadow_map: ShadowMap::new(display, ctx.shadow_resolution),
+            ibl: Ibl::new(display),
+            software_textures: HashMap::new(),
+        })
+    }
+
+    // Rebuilds every object of the scene. The loaded textures are kept when an object still uses them,
+    // nothing changes when one cannot be read.
+    pub fn load_scene(&mut self, display: &dyn Facade, ctx: &mut Ctx) -> Result<(), String> {
+        let mut available: Vec<&str> = self.objects.iter().map(|gpu| gpu.textures.current().path.as_str()).collect();
+        let mut images: Vec<Option<Images>> = Vec::new();
+        for object in &ctx.scene.objects {
+            match available.iter().position(|path| *path == object.texture) {
+                Some(index) => {
+                    available.swap_remove(index);
+                    images.push(None);
+                },
+                None => images.push(Some(Images::new(display, &object.texture)?))
+            }
+        }
+        let fade_duration = self.fade_duration();
+        // Taken in the same order as above, so every kept texture is still there.
+        let mut previous = std::mem::take(&mut self.objects);
+        for (object, img) in ctx.scene.objects.iter_mut().zip(images) {
+            let mut textures = match img {
+                Some(img) => Textures::new(img),
+                None => {
+                    let index = previous.iter().position(|gpu| gpu.textures.current().path == object.texture).unwrap();
+                    previous.swap_remove(index).textures
                 }
+            };
+            textures.fade_duration = fade_duration;
+            self.objects.push(GpuObject::new(display, &mut object.obj, textures));
+        }
+        Ok(())
+    }
+
+    // Uploads the mesh built for the new model of an object, with its materials.
+    pub fn load_mesh(&mut self, display: &dyn Facade, ctx: &Ctx, index: usize, mesh: Vec<Mesh>, ranges: Vec<DrawRange>) {
+        self.objects[index].load_mesh(display, &ctx.scene.objects[index].obj, mesh, ranges);
+    }
+
+    // Uploads the object last added to the scene and the mesh built for it.
+    pub fn add_object(&mut self, display: &dyn Facade, ctx: &Ctx, mesh: Vec<Mesh>, ranges: Vec<DrawRange>) -> Result<(), String> {
+        let Some(object) = ctx.scene.objects.last() else {
+            return Ok(());
+        };
+        let mut textures = Textures::new(Images::new(display, &object.texture)?);
+        textures.fade_duration = self.fade_duration();
+        let mut gpu = GpuObject { mesh: Vec::new(), ranges: Vec::new(), materials: Vec::new(), textures };
+        gpu.load_mesh(display, &object.obj, mesh, ranges);
+        self.objects.push(gpu);
+        Ok(())
+    }
+
+    pub fn remove_object(&mut self, index: usize) {
+        self.objects.remove(index);
+    }
+
+    pub fn active_textures(&mut self, ctx: &Ctx) -> &mut Textures {
+        &mut self.objects[ctx.scene.active].textures
+    }
+
+    // Texture of the active object.
+    pub fn load_texture(&mut self, display: &dyn Facade, ctx: &mut Ctx, filepath: &str) -> Result<(), String> {
+        self.active_textures(ctx).load(display, filepath)?;
+        ctx.scene.active_mut().texture = filepath.to_string();
+        Ok(())
+    }
+
+    // Texture of an object from an image already read.
+    pub fn set_texture(&mut self, display: &dyn Facade, ctx: &mut Ctx, index: usize, filepath: &str, img: glium::texture::RawImage2d<'static, u8>, dim: (u32, u32)) -> Result<(), String> {
+        let img = Images::from_raw(display, filepath, img, dim)?;
+        self.objects[index].textures.push(img);
+        ctx.scene.objects[index].texture = filepath.to_string();
+        Ok(())
+    }
+
+    // Next texture of the folder of the active one.
+    pub fn cycle_texture(&mut self, display: &dyn Facade, ctx: &mut Ctx, step: i32) -> Result<(), String> {
+        let textures = self.active_textures(ctx);
+        textures.cycle_folder(display, step)?;
+        ctx.scene.active_mut().texture = textures.current().path.clone();
+        Ok(())
+    }
+
+    pub fn fade_duration(&self) -> f32 {
+        self.objects.first().map_or(1.0, |object| object.textures.fade_duration)
+    }
+
+    // Same cross-fade duration for every object.
+    pub fn set_fade_duration(&mut self, duration: f32) {
+        if let Some((first, others)) = self.objects.split_first_mut() {
+            first.textures.set_fade_duration(duration);
+            for object in others {
+                object.textures.fade_duration = first.textures.fade_duration;
             }
         }
     }
 
+    pub fn load_normal_map(&mut self, display: &dyn Facade, filepath: &str) -> Result<(), String> {
+        let (img, (width, height)) = ppm_parser(filepath)?;
+        let texture = glium::Texture2d::new(display, img)
+            .map_err(|e| format!("Error: Failed to create texture: {:?}", e))?;
+        println!("Normal map: {} ({}x{})", filepath, width, height);
+        self.normal_map = Some(texture);
+        Ok(())
+    }
+
+    pub fn load_matcap(&mut self, display: &dyn Facade, filepath: &str) -> Result<(), String> {
+        let img = Images::new(display, filepath)?;
+        println!("Matcap: {} ({}x{})", filepath, img.dimension.0, img.dimension.1);
+        self.matcap = Some(img);
+        Ok(())
+    }
+
     pub fn get_color(r: u8, g: u8, b: u8) -> (f32, f32, f32, f32) {
-        return ((r as f32 / 255.0),  (g as f32 / 255.0), (b as f32 / 255.0) , 1.0);
+        ((r as f32 / 255.0),  (g as f32 / 255.0), (b as f32 / 255.0) , 1.0)
+    }
+    pub fn poll_shaders(&mut self, display: &dyn Facade, ctx: &mut Ctx) -> bool {
+        let changed = self.reloader.poll(display, &mut self.shading);
+        ctx.shading = self.shading.current().name.clone();
+        changed
+    }
+    pub fn export_shaders(&self) -> Result<(), String> {
+        self.reloader.export(self.shading.current())
+    }
+    pub fn hud_stats(&self, ctx: &Ctx) -> HudStats {
+        let light = ctx.selected_light.min(ctx.lights.len() - 1);
+        HudStats {
+            object: format!("{}/{}: {}", ctx.scene.active + 1, ctx.scene.objects.len(), ctx.scene.active().name()),
+            vertices: ctx.scene.objects.iter().map(|object| object.obj.vertexs.len()).sum(),
+            triangles: self.objects.iter().map(|object| object.mesh.len() / 3).sum(),
+            shading: if ctx.software {
+                format!("{} (software)", ctx.shading)
+            } else if ctx.debug_view == DebugView::Off {
+                ctx.shading.clone()
+            } else {
+                format!("{} (debug {:?})", ctx.shading, ctx.debug_view)
+            },
+            polygon_mode: match ctx.polmode {
+                0 => "fill",
+                1 => "line",
+                _ => "point"
+            },
+            culling: ctx.backface,
+            light,
+            light_position: ctx.lights[light].position,
+        }
+    }
+    pub fn animate(&mut self, dt: f32) {
+        for object in &mut self.objects {
+            object.textures.animate(dt);
+        }
     }
-    pub fn shaders_switch(&mut self, ctx: &mut Ctx) {
-        self.shaders.switch_shading(ctx);
+    pub fn is_animating(&self) -> bool {
+        self.objects.iter().any(|object| object.textures.is_fading())
+    }
+    // Draws the scene and the interface to the default framebuffer of the context.
+    pub fn draw_obj(&mut self, display: &Rc<Context>, ctx: &mut Ctx, hud: &mut Hud) -> Result<(), String> {
+        let mut frame = glium::Frame::new(display.clone(), display.get_framebuffer_dimensions());
+        if ctx.software {
+            let image = self.render_software(ctx, 1, &PipelineState::from_ctx(ctx, false));
+            let raw = glium::texture::RawImage2d::from_raw_rgba_reversed(&image.pixels, (image.width, image.height));
+            let texture = glium::Texture2d::with_format(
+                display,
+                raw,
+                glium::texture::UncompressedFloatFormat::U8U8U8U8,
+                glium::texture::MipmapsOption::NoMipmap
+            ).unwrap();
+            texture.as_surface().fill(&frame, glium::uniforms::MagnifySamplerFilter::Nearest);
+        } else {
+            self.render(display, &mut frame, ctx, 1.0, &PipelineState::from_ctx(ctx, false));
+        }
+        if ctx.axis_gizmo {
+            self.overlays.draw_axes(display, &mut frame, &self.text);
+        }
+        hud.frame();
+        let stats = self.hud_stats(ctx);
+        let hud_height = hud.draw(display, &mut frame, &self.text, &stats);
+        if let Some(error) = &self.reloader.error {
+            self.text.draw(display, &mut frame, error, [8.0, hud_height + 8.0], 2.0, [1.0, 0.3, 0.3, 1.0]);
+        }
+        frame.finish().map_err(|e| format!("Error: Failed to show the frame: {:?}", e))
     }
-    pub fn draw_obj(&mut self, display: &Display<WindowSurface>, ctx: &mut Ctx) {
-        if ctx.rotation {
-            ctx.rot_speed += ctx.speed_factor;
+
+    // Renders the scene offscreen, scale times larger than the window.
+    pub fn capture(&mut self, display: &dyn Facade, ctx: &mut Ctx, scale: u32, state: &PipelineState) -> Result<Image, String> {
+        let (width, height) = (ctx.width * scale, ctx.height * scale);
+        let texture = glium::texture::SrgbTexture2d::empty_with_format(
+            display,
+            glium::texture::SrgbFormat::U8U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            width,
+            height
+        ).map_err(|e| format!("Error: Failed to create a {}x{} capture: {:?}", width, height, e))?;
+        let depth = glium::framebuffer::DepthRenderBuffer::new(display, glium::texture::DepthFormat::I24, width, height)
+            .map_err(|e| format!("Error: Failed to create a {}x{} depth buffer: {:?}", width, height, e))?;
+        let mut target = glium::framebuffer::SimpleFrameBuffer::with_depth_buffer(display, &texture, &depth)
+            .map_err(|e| format!("Error: Failed to bind the capture: {:?}", e))?;
+        self.render(display, &mut target, ctx, scale as f32, state);
+        let mut image = Image::from_gl_rows(texture.read());
+        if !state.transparent {
+            for pixel in image.pixels.chunks_exact_mut(4) {
+                pixel[3] = 255;
+            }
         }
-        if ctx.texture && ctx.mix_factor < 1.0 {
-            ctx.mix_factor += 0.05;
-        } else if !ctx.texture && ctx.mix_factor > 0.0 {
-            ctx.mix_factor -= 0.05;
+        Ok(image)
+    }
+
+    // Scene drawn by the CPU rasterizer, modes it does not have fall back to gray.
+    pub fn render_software(&mut self, ctx: &mut Ctx, scale: u32, state: &PipelineState) -> Image {
+        let matcap = self.matcap.as_ref().map(|img| img.path.clone());
+        let paths: Vec<&String> = self.objects.iter().map(|object| &object.textures.current().path).chain(&matcap).collect();
+        self.software_textures.retain(|path, _| paths.contains(&path));
+        for path in paths {
+            if !self.software_textures.contains_key(path) {
+                let texture = CpuTexture::load(path).map_err(|err| println!("{} Drawn without texture.", err)).ok();
+                self.software_textures.insert(path.clone(), texture);
+            }
         }
-        let rotation_matrix = Matrix::new_rotation(ctx).get_4x4_matrix();
+        let objects: Vec<SoftwareObject> = self.objects.iter()
+            .map(|object| SoftwareObject {
+                mesh: &object.mesh,
+                ranges: &object.ranges,
+                texture: self.software_textures[&object.textures.current().path].as_ref(),
+            })
+            .collect();
+        let matcap = matcap.and_then(|path| self.software_textures[&path].as_ref());
+        let shading = SoftwareShading::from_name(&state.shading).unwrap_or(SoftwareShading::Gray);
+        let (width, height) = (ctx.width * scale, ctx.height * scale);
+        software::render(&objects, ctx, shading, state, (width, height), matcap)
+    }
+
+    // Draws the scene without the interface, sizes given in pixels are multiplied by scale.
+    // A transparent render skips the background: skybox, ground and grid.
+    pub fn render<S: Surface>(&mut self, display: &dyn Facade, frame: &mut S, ctx: &mut Ctx, scale: f32, state: &PipelineState) {
+        let transparent = state.transparent;
+        ctx.aim_lights();
+        ctx.scene.update_world();
         let perspective_matrix = Matrix::new_perspective(ctx).get_4x4_matrix();
-        let vertex_buffer = VertexBuffer::<Mesh>::new(display, &self.mesh).unwrap();
+        let vertex_buffers: Vec<VertexBuffer<Mesh>> = self.objects.iter()
+            .map(|object| VertexBuffer::<Mesh>::new(display, &object.mesh).unwrap())
+            .collect();
 
-    
-        let uniforms = uniform! {
-            rotation_matrix: rotation_matrix,
-            perspective_matrix: perspective_matrix,
-            object_center: ctx.obj.centroid,
-            tex: &self.img.diffuse_texture,
-            mix_factor: ctx.mix_factor,
-            light: ctx.light
+        let shadow_light = if ctx.shadows {
+            uniform_index(&ctx.lights, ctx.selected_light)
+        } else {
+            None
         };
+        let light_matrix = match shadow_light {
+            Some(_) => ShadowMap::light_matrix(&ctx.lights[ctx.selected_light], ctx.scene.center(), ctx.scene.radius()),
+            None => Matrix::new_translation([0.0, 0.0, 0.0]).get_4x4_matrix()
+        };
+        if shadow_light.is_some() {
+            self.shadow_map.resize(display, ctx.shadow_resolution);
+            let casters: Vec<_> = vertex_buffers.iter()
+                .zip(&self.objects)
+                .zip(&ctx.scene.objects)
+                .map(|((vertex_buffer, gpu), object)| (vertex_buffer, &gpu.ranges[..], object))
+                .collect();
+            self.shadow_map.render(display, &casters, light_matrix);
+        }
+        let shadow_light = shadow_light.map_or(-1, |index| index as i32);
     
-        let program = glium::Program::from_source(display, self.shaders.vertex_shader, self.shaders.fragment_shader, None)
-            .expect("Error: \"glium::Program::from_source\" Fail");
-    
-        let mut frame = display.draw();
-        frame.clear_color_and_depth(Renderer::get_color(0x00, 0x05, 0x10), 1.0);
+        let background = if transparent {
+            (0.0, 0.0, 0.0, 0.0)
+        } else {
+            let [r, g, b] = ctx.background;
+            (r, g, b, 1.0)
+        };
+        frame.clear_color_and_depth(background, 1.0);
+        let env_rotation = environment_rotation(ctx.env_rotation);
+        let environment = match &self.ibl.environment {
+            Some(environment) if ctx.environment && !transparent => Some(environment),
+            _ => None
+        };
+        if environment.is_some() {
+            self.ibl.draw_skybox(frame, perspective_matrix, env_rotation, ctx.exposure);
+        }
     
         let params = glium::DrawParameters {
             depth: glium::Depth {
@@ -81,14 +423,14 @@ impl Renderer {
                 write: true,
                 .. Default::default()
             },
-            backface_culling: if ctx.backface {
+            backface_culling: if state.backface_culling {
                 glium::draw_parameters::BackfaceCullingMode::CullCounterClockwise
             } else {
                 glium::draw_parameters::BackfaceCullingMode::CullingDisabled
             },
-            polygon_mode: if ctx.polmode == 0 {
+            polygon_mode: if state.polygon_mode == 0 {
                 glium::draw_parameters::PolygonMode::Fill
-            } else if ctx.polmode == 1 {
+            } else if state.polygon_mode == 1 {
                 glium::draw_parameters::PolygonMode::Line
             } else {
                 glium::draw_parameters::PolygonMode::Point
@@ -96,13 +438,223 @@ impl Renderer {
             .. Default::default()
         };
 
+        let program = if ctx.debug_view == DebugView::Off {
+            &self.reloader.program
+        } else {
+            &self.debug_program
+        };
+        let mut failed = None;
+        for ((object, gpu), vertex_buffer) in ctx.scene.objects.iter().zip(&self.objects).zip(&vertex_buffers) {
+            let center = object.center();
+            let depth_range = [center[2] - object.radius(), center[2] + object.radius()];
+            for range in gpu.ranges.iter().filter(|range| object.is_shown(range.node)) {
+                let rotation_matrix = object.node_matrix(range.node);
+                let material = gpu.materials.iter()
+                    .find(|material| material.material.name == range.material)
+                    .unwrap_or(&self.default_material);
+                let normal_map = self.normal_map.as_ref().or(material.normal_map.as_ref());
+                let uniforms = uniform! {
+                    rotation_matrix: rotation_matrix,
+                    normal_matrix: object.normal_matrix(range.node),
+                    perspective_matrix: perspective_matrix,
+                    light_matrix: light_matrix,
+                    shadow_map: self.shadow_map.sampler(),
+                    shadow_light: shadow_light,
+                    shadow_bias: ctx.shadow_bias,
+                    object_center: object.obj.centroid,
+                    diffuse_texture: &gpu.textures.current().diffuse_texture,
+                    previous_texture: &gpu.textures.previous().diffuse_texture,
+                    texture_fade: gpu.textures.fade(),
+                    mix_factor: ctx.mix_factor,
+                    base_color: material.material.kd,
+                    metallic: material.material.get_metallic(),
+                    roughness: material.material.get_roughness(),
+                    has_base_color_map: material.base_color_map.is_some(),
+                    base_color_map: material.base_color_map.as_ref().unwrap_or(&material.metallic_map),
+                    metallic_map: &material.metallic_map,
+                    roughness_map: &material.roughness_map,
+                    occlusion_map: &material.occlusion_map,
+                    has_normal_map: ctx.normal_mapping && normal_map.is_some(),
+                    normal_map: normal_map.unwrap_or(&self.flat_normal),
+                    has_environment: environment.is_some(),
+                    irradiance_map: environment.map_or(self.ibl.black_sampler(), |env| env.irradiance_sampler()),
+                    prefiltered_map: environment.map_or(self.ibl.black_sampler(), |env| env.prefiltered_sampler()),
+                    brdf_lut: self.ibl.brdf_sampler(),
+                    prefiltered_levels: PREFILTERED_LEVELS as f32,
+                    environment_rotation: env_rotation,
+                    exposure: ctx.exposure,
+                    toon_bands: ctx.toon_bands,
+                    matcap_texture: self.matcap.as_ref().map_or(&self.flat_normal, |img| &img.diffuse_texture),
+                    wireframe: ctx.wireframe,
+                    wireframe_polygons: ctx.wireframe_polygons,
+                    wireframe_width: ctx.wireframe_width * scale,
+                    wireframe_color: WIREFRAME_COLORS[ctx.wireframe_color % WIREFRAME_COLORS.len()],
+                    debug_view: ctx.debug_view.as_int(),
+                    depth_range: depth_range
+                };
+                let uniforms = LightUniforms {
+                    uniforms: ModeUniforms {
+                        uniforms,
+                        mode: self.shading.current()
+                    },
+                    lights: &ctx.lights
+                };
+                let drawn = frame.draw(
+                    vertex_buffer.slice(range.vertices.clone()).unwrap(),
+                    glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                    program,
+                    &uniforms,
+                    &params
+                );
+                if let Err(err) = drawn {
+                    failed = Some(err);
+                }
+            }
+            let outline = self.shading.current().outline && ctx.debug_view == DebugView::Off;
+            for range in gpu.ranges.iter().filter(|range| outline && object.is_shown(range.node)) {
+                let uniforms = uniform! {
+                    rotation_matrix: object.node_matrix(range.node),
+                    normal_matrix: object.normal_matrix(range.node),
+                    perspective_matrix: perspective_matrix,
+                    object_center: object.obj.centroid,
+                    outline_width: ctx.outline_width * object.obj.radius(),
+                    outline_color: [0.02f32, 0.02, 0.02]
+                };
+                frame.draw(
+                    vertex_buffer.slice(range.vertices.clone()).unwrap(),
+                    glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                    &self.outline_program,
+                    &uniforms,
+                    &glium::DrawParameters {
+                        depth: glium::Depth {
+                            test: glium::draw_parameters::DepthTest::IfLess,
+                            write: true,
+                            .. Default::default()
+                        },
+                        .. Default::default()
+                    }
+                ).unwrap();
+            }
+        }
+        if let Some(err) = failed {
+            self.reloader.draw_failed(err);
+        }
+        if ctx.ground && !transparent {
+            let ground_buffer = VertexBuffer::<Mesh>::new(display, &ground_mesh(ctx)).unwrap();
+            let uniforms = LightUniforms {
+                uniforms: uniform! {
+                    perspective_matrix: perspective_matrix,
+                    light_matrix: light_matrix,
+                    shadow_map: self.shadow_map.sampler(),
+                    shadow_light: shadow_light,
+                    shadow_bias: ctx.shadow_bias
+                },
+                lights: &ctx.lights
+            };
+            let params = glium::DrawParameters {
+                depth: glium::Depth {
+                    test: glium::draw_parameters::DepthTest::IfLess,
+                    write: true,
+                    .. Default::default()
+                },
+                .. Default::default()
+            };
+            frame.draw(
+                &ground_buffer,
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &self.ground_program,
+                &uniforms,
+                &params
+            ).unwrap();
+        }
+        if ctx.grid && !transparent {
+            self.overlays.draw_grid(frame, ctx, perspective_matrix);
+        }
+        if ctx.bbox {
+            self.overlays.draw_bbox(frame, ctx.scene.active(), perspective_matrix);
+        }
+        for (object, gpu) in ctx.scene.objects.iter().zip(&self.objects) {
+            for range in gpu.ranges.iter().filter(|range| object.is_shown(range.node)) {
+                let mesh = &gpu.mesh[range.vertices.clone()];
+                let rotation_matrix = object.node_matrix(range.node);
+                if ctx.tangent_frames {
+                    let lines = tangent_frame_lines(mesh, object.obj.radius() * 0.03);
+                    self.draw_model_lines(display, frame, object, &lines, rotation_matrix, perspective_matrix);
+                }
+                if ctx.normal_lines {
+                    let lines = normal_lines(mesh, object.obj.radius() * ctx.normal_length);
+                    self.draw_model_lines(display, frame, object, &lines, rotation_matrix, perspective_matrix);
+                }
+            }
+        }
+        if ctx.gizmos {
+            self.draw_gizmos(display, frame, ctx, perspective_matrix, scale);
+        }
+    }
+
+    // Lines given in object space, depth tested against the model so only the visible side shows.
+    fn draw_model_lines<S: Surface>(
+        &self,
+        display: &dyn Facade,
+        frame: &mut S,
+        object: &SceneObject,
+        lines: &[GizmoVertex],
+        rotation_matrix: [[f32; 4]; 4],
+        perspective_matrix: [[f32; 4]; 4]
+    ) {
+        if lines.is_empty() {
+            return;
+        }
+        let vertex_buffer = VertexBuffer::<GizmoVertex>::new(display, lines).unwrap();
+        let uniforms = uniform! {
+            rotation_matrix: rotation_matrix,
+            perspective_matrix: perspective_matrix,
+            object_center: object.obj.centroid
+        };
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLessOrEqual,
+                write: false,
+                .. Default::default()
+            },
+            .. Default::default()
+        };
         frame.draw(
-			&vertex_buffer,
-            &glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
-			&program,
-			&uniforms,
-			&params
-		).unwrap();
-        frame.finish().unwrap();
-    }
-}
\ No newline at end of file
+            &vertex_buffer,
+            glium::index::NoIndices(glium::index::PrimitiveType::LinesList),
+            &self.model_line_program,
+            &uniforms,
+            &params
+        ).unwrap();
+    }
+
+    // Lights are drawn on top of the model so they stay visible behind it.
+    fn draw_gizmos<S: Surface>(&self, display: &dyn Facade, frame: &mut S, ctx: &Ctx, perspective_matrix: [[f32; 4]; 4], scale: f32) {
+        let (points, lines) = gizmo_vertices(&ctx.lights, ctx.selected_light);
+        let uniforms = uniform! {
+            perspective_matrix: perspective_matrix
+        };
+        let params = glium::DrawParameters {
+            point_size: Some(10.0 * scale),
+            line_width: Some(2.0 * scale),
+            .. Default::default()
+        };
+        let buffers = [
+            (points, glium::index::PrimitiveType::Points),
+            (lines, glium::index::PrimitiveType::LinesList)
+        ];
+        for (vertices, primitive) in buffers {
+            if vertices.is_empty() {
+                continue;
+            }
+            let vertex_buffer = VertexBuffer::<GizmoVertex>::new(display, &vertices).unwrap();
+            frame.draw(
+                &vertex_buffer,
+                glium::index::NoIndices(primitive),
+                &self.gizmo_program,
+                &uniforms,
+                &params
+            ).unwrap();
+        }
+    }
+}
@@ -0,0 +1,579 @@
+use std::{f32::consts::TAU, fs, path::{Path, PathBuf}};
+
+use super::{matrix::Matrix, parser::Obj, rendering::DEFAULT_TEXTURE};
+
+// Space left between two objects placed side by side, in radii of the added one.
+const SPACING: f32 = 0.2;
+
+// Rotation around the object center then translation, like the vertex shader.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub position: [f32; 3],
+    // Radians around the Y axis.
+    pub angle: f32,
+}
+
+impl Transform {
+    pub fn new(position: [f32; 3], angle: f32) -> Self {
+        Self { position, angle }
+    }
+
+    pub fn rotate(&mut self, angle: f32) {
+        self.angle = (self.angle + angle).rem_euclid(TAU);
+    }
+}
+
+const IDENTITY: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0]
+];
+
+// Part of an object from its o and g groups, placed relative to its parent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneNode {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub translation: [f32; 3],
+    // Euler angles in radians, around X first, then Y, then Z.
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+    // Center of the rotation and the scale, the middle of the faces of the node and its children.
+    pub pivot: [f32; 3],
+    pub visible: bool,
+    pub faces: usize,
+    // Box of the own faces of the node, without its children, none without faces.
+    bounds: Option<([f32; 3], [f32; 3])>,
+    // Object space matrix and visibility with the parents applied, kept by SceneObject::update_world.
+    world: [[f32; 4]; 4],
+    shown: bool,
+}
+
+impl SceneNode {
+    pub fn new(name: &str, parent: Option<usize>) -> Self {
+        Self {
+            name: name.to_string(),
+            parent,
+            translation: [0.0; 3],
+            rotation: [0.0; 3],
+            scale: [1.0; 3],
+            pivot: [0.0; 3],
+            visible: true,
+            faces: 0,
+            bounds: None,
+            world: IDENTITY,
+            shown: true,
+        }
+    }
+
+    pub fn local_matrix(&self) -> Matrix {
+        let p = self.pivot;
+        Matrix::new_translation(self.translation)
+            .multiply(&Matrix::new_translation(p))
+            .multiply(&Matrix::new_euler(self.rotation))
+            .multiply(&Matrix::new_scale(self.scale))
+            .multiply(&Matrix::new_translation([-p[0], -p[1], -p[2]]))
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.translation == [0.0; 3] && self.rotation == [0.0; 3] && self.scale == [1.0; 3]
+    }
+
+    pub fn reset(&mut self) {
+        (self.translation, self.rotation, self.scale) = ([0.0; 3], [0.0; 3], [1.0; 3]);
+    }
+
+    pub fn describe(&self) -> String {
+        let [tx, ty, tz] = self.translation;
+        let [rx, ry, rz] = self.rotation.map(f32::to_degrees);
+        let [sx, sy, sz] = self.scale;
+        format!("t {:.2},{:.2},{:.2} r {:.1},{:.1},{:.1} s {:.2},{:.2},{:.2}", tx, ty, tz, rx, ry, rz, sx, sy, sz)
+    }
+}
+
+// Names of the nodes joined with the names of their parents, "chassis/axle/wheel".
+fn node_paths(nodes: &[SceneNode]) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let path = match node.parent {
+            Some(parent) => format!("{}/{}", paths[parent], node.name),
+            None => node.name.clone()
+        };
+        paths.push(path);
+    }
+    paths
+}
+
+// Nodes of the groups of the model, in the same order, with the face counts and pivots.
+pub fn build_nodes(obj: &Obj) -> Vec<SceneNode> {
+    let mut nodes: Vec<SceneNode> = obj.groups.iter().map(|group| SceneNode::new(&group.name, group.parent)).collect();
+    let mut bounds = vec![([f32::MAX; 3], [f32::MIN; 3]); nodes.len()];
+    for face in &obj.faces {
+        nodes[face.group].faces += 1;
+        let (min, max) = &mut bounds[face.group];
+        for v in face.v {
+            let position = obj.vertexs[v as usize];
+            for i in 0..3 {
+                min[i] = min[i].min(position[i]);
+                max[i] = max[i].max(position[i]);
+            }
+        }
+    }
+    for (node, (min, max)) in nodes.iter_mut().zip(&bounds) {
+        if min[0] <= max[0] {
+            node.bounds = Some((*min, *max));
+        }
+    }
+    // Children come after their parents, their bounds are merged upward.
+    for index in (0..nodes.len()).rev() {
+        let (min, max) = bounds[index];
+        if let Some(parent) = nodes[index].parent {
+            let (parent_min, parent_max) = &mut bounds[parent];
+            for i in 0..3 {
+                parent_min[i] = parent_min[i].min(min[i]);
+                parent_max[i] = parent_max[i].max(max[i]);
+            }
+        }
+        if min[0] <= max[0] {
+            nodes[index].pivot = [0, 1, 2].map(|i| (min[i] + max[i]) / 2.0);
+        }
+    }
+    nodes
+}
+
+pub struct SceneObject {
+    pub obj: Obj,
+    pub transform: Transform,
+    pub texture: String,
+    // The first node is the whole object.
+    pub nodes: Vec<SceneNode>,
+    // Node moved, shown and hidden by key.
+    pub selected_node: usize,
+    // Object space box of the faces with the node transforms applied, kept by update_world.
+    bounds: ([f32; 3], [f32; 3]),
+}
+
+fn obj_bounds(obj: &Obj) -> ([f32; 3], [f32; 3]) {
+    ([obj.min_x, obj.min_y, obj.min_z], [obj.max_x, obj.max_y, obj.max_z])
+}
+
+impl SceneObject {
+    pub fn new(obj: Obj, transform: Transform) -> Self {
+        let nodes = build_nodes(&obj);
+        let bounds = obj_bounds(&obj);
+        Self { obj, transform, texture: DEFAULT_TEXTURE.to_string(), nodes, selected_node: 0, bounds }
+    }
+
+    // New model in place of the current one, its nodes start untransformed.
+    pub fn set_obj(&mut self, obj: Obj) {
+        self.nodes = build_nodes(&obj);
+        self.bounds = obj_bounds(&obj);
+        self.obj = obj;
+        self.selected_node = 0;
+    }
+
+    // New version of the same model, the nodes found again at the same place of the tree
+    // keep their transform and visibility.
+    pub fn reload_obj(&mut self, obj: Obj) {
+        let previous = std::mem::take(&mut self.nodes);
+        let previous_paths = node_paths(&previous);
+        let selected = previous_paths.get(self.selected_node).cloned();
+        self.set_obj(obj);
+        let paths = node_paths(&self.nodes);
+        for (node, path) in self.nodes.iter_mut().zip(&paths) {
+            if let Some(old) = previous_paths.iter().position(|old| old == path).map(|index| &previous[index]) {
+                (node.translation, node.rotation, node.scale, node.visible) = (old.translation, old.rotation, old.scale, old.visible);
+            }
+        }
+        self.selected_node = paths.iter().position(|path| Some(path) == selected.as_ref()).unwrap_or(0);
+    }
+
+    // World matrices and visibility of the nodes from their parents.
+    pub fn update_world(&mut self) {
+        for index in 0..self.nodes.len() {
+            let local = self.nodes[index].local_matrix();
+            let (world, shown) = match self.nodes[index].parent {
+                Some(parent) => (
+                    Matrix::from_4x4(self.nodes[parent].world).multiply(&local).get_4x4_matrix(),
+                    self.nodes[parent].shown
+                ),
+                None => (local.get_4x4_matrix(), true)
+            };
+            let node = &mut self.nodes[index];
+            node.world = world;
+            node.shown = shown && node.visible;
+        }
+        self.update_bounds();
+    }
+
+    // Corners of the box of each node placed by its world matrix, hidden nodes included
+    // so the framing does not jump when they are shown again.
+    fn update_bounds(&mut self) {
+        let (mut min, mut max) = ([f32::MAX; 3], [f32::MIN; 3]);
+        for node in &self.nodes {
+            let Some((low, high)) = node.bounds else { continue };
+            let world = Matrix::from_4x4(node.world);
+            for corner in 0..8 {
+                let p = [0, 1, 2].map(|i| if corner & (1 << i) == 0 { low[i] } else { high[i] });
+                let p = world.multiply(&Matrix::new_translation(p)).get_4x4_matrix()[3];
+                for i in 0..3 {
+                    min[i] = min[i].min(p[i]);
+                    max[i] = max[i].max(p[i]);
+                }
+            }
+        }
+        self.bounds = if min[0] <= max[0] { (min, max) } else { obj_bounds(&self.obj) };
+    }
+
+    pub fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        self.bounds
+    }
+
+    pub fn is_shown(&self, node: usize) -> bool {
+        self.nodes.get(node).is_none_or(|node| node.shown)
+    }
+
+    // Rotation matrix of the shaders for the faces of a node, they are drawn around the object center.
+    pub fn node_matrix(&self, node: usize) -> [[f32; 4]; 4] {
+        let c = self.obj.centroid;
+        let world = self.nodes.get(node).map_or(IDENTITY, |node| node.world);
+        let local = Matrix::new_translation([-c[0], -c[1], -c[2]])
+            .multiply(&Matrix::from_4x4(world))
+            .multiply(&Matrix::new_translation(c));
+        Matrix::new_rotation(&self.transform).multiply(&local).get_4x4_matrix()
+    }
+
+    // Matrix of the shaders for the normals of a node.
+    pub fn normal_matrix(&self, node: usize) -> [[f32; 3]; 3] {
+        Matrix::from_4x4(self.node_matrix(node)).normal_matrix()
+    }
+
+    // Next node of the object, wrapping around.
+    pub fn select_node(&mut self, step: i32) {
+        let len = self.nodes.len() as i32;
+        self.selected_node = (self.selected_node as i32 + step).rem_euclid(len) as usize;
+    }
+
+    // The root node is named after the object.
+    pub fn node_name(&self, node: usize) -> String {
+        match self.nodes.get(node) {
+            Some(node) if node.parent.is_some() => node.name.clone(),
+            _ => self.name()
+        }
+    }
+
+    // Indented lines of the nodes, children under their parent.
+    pub fn tree(&self) -> String {
+        let mut lines = Vec::new();
+        self.tree_lines(0, 0, &mut lines);
+        lines.join("\n")
+    }
+
+    fn tree_lines(&self, index: usize, depth: usize, lines: &mut Vec<String>) {
+        let node = &self.nodes[index];
+        let mut line = format!("{}{} ({} faces)", "  ".repeat(depth), self.node_name(index), node.faces);
+        if !node.is_identity() {
+            line += &format!(" {}", node.describe());
+        }
+        if !node.visible {
+            line += " hidden";
+        }
+        lines.push(line);
+        for child in (0..self.nodes.len()).filter(|&child| self.nodes[child].parent == Some(index)) {
+            self.tree_lines(child, depth + 1, lines);
+        }
+    }
+
+    // File name without its extension, else the object name of the file.
+    pub fn name(&self) -> String {
+        Path::new(&self.obj.path).file_stem()
+            .and_then(|stem| stem.to_str())
+            .or(self.obj.name.as_deref())
+            .unwrap_or("object")
+            .to_string()
+    }
+
+    // Where the object center ends up once rotated and translated.
+    pub fn center(&self) -> [f32; 3] {
+        let [x, y, z] = self.transform.position;
+        let c = self.obj.centroid;
+        [c[0] + x, c[1] + y, c[2] + z]
+    }
+
+    // Radius of the sphere centered on the centroid enclosing the bounds.
+    pub fn radius(&self) -> f32 {
+        let c = self.obj.centroid;
+        let (min, max) = self.bounds;
+        let d = [0, 1, 2].map(|i| (max[i] - c[i]).abs().max((min[i] - c[i]).abs()));
+        (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+    }
+
+    // The rotation is around Y so the lowest point stays the same.
+    pub fn min_y(&self) -> f32 {
+        self.bounds.0[1] + self.transform.position[1]
+    }
+}
+
+// Objects drawn together, the active one follows the keys and the drops.
+pub struct Scene {
+    pub objects: Vec<SceneObject>,
+    pub active: usize,
+}
+
+impl Scene {
+    pub fn new(object: SceneObject) -> Self {
+        Self { objects: vec![object], active: 0 }
+    }
+
+    pub fn active(&self) -> &SceneObject {
+        &self.objects[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut SceneObject {
+        &mut self.objects[self.active]
+    }
+
+    // Next or previous object, wrapping around.
+    pub fn select(&mut self, step: i32) {
+        let len = self.objects.len() as i32;
+        self.active = (self.active as i32 + step).rem_euclid(len) as usize;
+    }
+
+    // Placed right of the others on the same ground, then the row is centered where the scene was.
+    pub fn add(&mut self, obj: Obj) {
+        let (center, right, min_y) = (self.center(), self.max_x(), self.min_y());
+        let (c, radius) = (obj.centroid, obj.radius());
+        let position = [
+            right + radius * (1.0 + SPACING) - c[0],
+            min_y - obj.min_y,
+            center[2] - c[2]
+        ];
+        let angle = self.active().transform.angle;
+        let mut object = SceneObject::new(obj, Transform::new(position, angle));
+        object.texture = self.active().texture.clone();
+        self.objects.push(object);
+        let shift = center[0] - self.center()[0];
+        for object in &mut self.objects {
+            object.transform.position[0] += shift;
+        }
+        self.active = self.objects.len() - 1;
+    }
+
+    // The scene keeps at least one object.
+    pub fn remove(&mut self, index: usize) -> Result<SceneObject, String> {
+        if self.objects.len() == 1 {
+            return Err("Error: The scene needs at least one object.".to_string());
+        }
+        let object = self.objects.remove(index);
+        if self.active > index || self.active == self.objects.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+        Ok(object)
+    }
+
+    // Indices of the objects read from a file, relative and absolute paths match.
+    pub fn find_path(&self, filepath: &str) -> Vec<usize> {
+        let canonical = |path: &str| fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        let filepath = canonical(filepath);
+        (0..self.objects.len()).filter(|&index| canonical(&self.objects[index].obj.path) == filepath).collect()
+    }
+
+    fn max_x(&self) -> f32 {
+        self.objects.iter().map(|object| object.center()[0] + object.radius()).fold(f32::MIN, f32::max)
+    }
+
+    // Center of the box enclosing the bounding spheres.
+    pub fn center(&self) -> [f32; 3] {
+        [0, 1, 2].map(|i| {
+            let min = self.objects.iter().map(|object| object.center()[i] - object.radius()).fold(f32::MAX, f32::min);
+            let max = self.objects.iter().map(|object| object.center()[i] + object.radius()).fold(f32::MIN, f32::max);
+            (min + max) / 2.0
+        })
+    }
+
+    // Radius of the sphere around center() enclosing every object.
+    pub fn radius(&self) -> f32 {
+        let center = self.center();
+        self.objects.iter()
+            .map(|object| {
+                let c = object.center();
+                let d = [0, 1, 2].map(|i| c[i] - center[i]);
+                (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt() + object.radius()
+            })
+            .fold(0.0, f32::max)
+    }
+
+    pub fn min_y(&self) -> f32 {
+        self.objects.iter().map(SceneObject::min_y).fold(f32::MAX, f32::min)
+    }
+
+    pub fn update_world(&mut self) {
+        for object in &mut self.objects {
+            object.update_world();
+        }
+    }
+
+    // Every object with its nodes, the active one marked.
+    pub fn tree(&self) -> String {
+        self.objects.iter().enumerate()
+            .map(|(index, object)| {
+                let [x, y, z] = object.transform.position;
+                let marker = if index == self.active { " *" } else { "" };
+                format!(
+                    "Object {}{}: position {:.2},{:.2},{:.2} angle {:.1}\n{}",
+                    index + 1, marker, x, y, z, object.transform.angle.to_degrees(), object.tree()
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::parser::obj_parser;
+
+    fn scene() -> Scene {
+        Scene::new(SceneObject::new(obj_parser("./obj/monkey.obj").unwrap(), Transform::new([0.0, 0.0, 8.0], 0.5)))
+    }
+
+    #[test]
+    fn single_object_bounds_are_its_own() {
+        let scene = scene();
+        let object = scene.active();
+        assert_eq!(scene.radius(), object.radius());
+        assert_eq!(scene.min_y(), object.min_y());
+        let (a, b) = (scene.center(), object.center());
+        assert!((0..3).all(|i| (a[i] - b[i]).abs() < 1e-5));
+    }
+
+    #[test]
+    fn added_objects_stand_side_by_side() {
+        let mut scene = scene();
+        let center = scene.center();
+        scene.add(obj_parser("./obj/42.obj").unwrap());
+        assert_eq!(scene.active, 1);
+        let (left, right) = (&scene.objects[0], &scene.objects[1]);
+        let gap = right.center()[0] - left.center()[0];
+        assert!(gap > left.radius() + right.radius());
+        assert!((left.min_y() - right.min_y()).abs() < 1e-5);
+        assert!((scene.center()[0] - center[0]).abs() < 1e-4);
+        assert_eq!(right.transform.angle, left.transform.angle);
+        assert_eq!(right.name(), "42");
+    }
+
+    #[test]
+    fn selection_wraps_around() {
+        let mut scene = scene();
+        scene.add(obj_parser("./obj/42.obj").unwrap());
+        scene.add(obj_parser("./obj/42.obj").unwrap());
+        scene.select(1);
+        assert_eq!(scene.active, 0);
+        scene.select(-1);
+        assert_eq!(scene.active, 2);
+        assert_eq!(scene.find_path("./obj/42.obj"), [1, 2]);
+    }
+
+    // Chassis with an axle and its wheel, and a trailer, written to a file named after the test.
+    fn assembly(name: &str) -> Obj {
+        let filepath = std::env::temp_dir().join(format!("{}.obj", name));
+        let content = [
+            "o chassis", "v 0 0 0", "v 1 0 0", "v 0 1 0", "v 2 0 0", "v 3 0 0", "v 2 1 0", "f 1 2 3",
+            "g axle", "f 4 5 6",
+            "g axle/wheel", "f 4 5 6", "f 1 2 3",
+            "o trailer", "f 1 2 3"
+        ].join("\n");
+        fs::write(&filepath, content).unwrap();
+        obj_parser(filepath.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn groups_nest_under_objects() {
+        let object = SceneObject::new(assembly("scop_groups"), Transform::new([0.0; 3], 0.0));
+        let parents: Vec<Option<usize>> = object.nodes.iter().map(|node| node.parent).collect();
+        assert_eq!(parents, [None, Some(0), Some(1), Some(2), Some(0)]);
+        let faces: Vec<usize> = object.nodes.iter().map(|node| node.faces).collect();
+        assert_eq!(faces, [0, 1, 1, 2, 1]);
+        assert_eq!(object.nodes[3].pivot, [1.5, 0.5, 0.0]);
+        assert_eq!(object.tree(), [
+            "scop_groups (0 faces)",
+            "  chassis (1 faces)",
+            "    axle (1 faces)",
+            "      wheel (2 faces)",
+            "  trailer (1 faces)"
+        ].join("\n"));
+    }
+
+    #[test]
+    fn world_matrices_follow_parents() {
+        let mut object = SceneObject::new(assembly("scop_world"), Transform::new([0.0, 0.0, 5.0], 0.3));
+        object.update_world();
+        assert_eq!(object.node_matrix(3), Matrix::new_rotation(&object.transform).get_4x4_matrix());
+        object.nodes[2].translation = [1.0, 0.0, 0.0];
+        object.nodes[3].rotation = [0.0, 0.0, std::f32::consts::FRAC_PI_2];
+        object.nodes[1].visible = false;
+        object.update_world();
+        // The wheel turns around its pivot and moves with the axle.
+        let world = object.nodes[3].world;
+        let pivot = object.nodes[3].pivot;
+        let moved = [0, 1, 2].map(|i| (0..3).map(|k| world[k][i] * pivot[k]).sum::<f32>() + world[3][i]);
+        assert!((0..3).all(|i| (moved[i] - pivot[i] - [1.0, 0.0, 0.0][i]).abs() < 1e-5));
+        assert!(!object.is_shown(3));
+        assert!(object.is_shown(4));
+        assert!(object.tree().contains("axle (1 faces) t 1.00,0.00,0.00 r 0.0,0.0,0.0 s 1.00,1.00,1.00"));
+        assert!(object.tree().contains("chassis (1 faces) hidden"));
+    }
+
+    #[test]
+    fn bounds_and_normals_follow_node_transforms() {
+        let mut object = SceneObject::new(assembly("scop_bounds"), Transform::new([0.0; 3], 0.0));
+        object.update_world();
+        assert_eq!(object.bounds(), ([0.0; 3], [3.0, 1.0, 0.0]));
+        let (radius, min_y) = (object.radius(), object.min_y());
+        object.nodes[2].translation = [0.0, -2.0, 0.0];
+        object.nodes[1].visible = false;
+        object.update_world();
+        assert_eq!(object.bounds().0[1], -2.0);
+        assert_eq!(object.min_y(), min_y - 2.0);
+        assert!(object.radius() > radius);
+        // Stretched along X, the normal of a slanted face stays perpendicular to it.
+        object.nodes[4].scale = [3.0, 1.0, 1.0];
+        object.update_world();
+        let (m, n) = (object.node_matrix(4), object.normal_matrix(4));
+        let (edge, normal) = ([1.0f32, -1.0, 0.0], [1.0f32, 1.0, 0.0]);
+        let moved = [0, 1, 2].map(|i| (0..3).map(|k| m[k][i] * edge[k]).sum::<f32>());
+        let normal = [0, 1, 2].map(|i| (0..3).map(|k| n[k][i] * normal[k]).sum::<f32>());
+        assert!(moved.iter().zip(normal).map(|(a, b)| a * b).sum::<f32>().abs() < 1e-5);
+    }
+
+    #[test]
+    fn reloads_keep_the_nodes_found_again() {
+        let mut object = SceneObject::new(assembly("scop_reload"), Transform::new([0.0; 3], 0.0));
+        object.nodes[2].translation = [1.0, 0.0, 0.0];
+        object.nodes[3].visible = false;
+        object.selected_node = 3;
+        object.reload_obj(assembly("scop_reload"));
+        assert_eq!(object.nodes[2].translation, [1.0, 0.0, 0.0]);
+        assert!(!object.nodes[3].visible);
+        assert_eq!(object.selected_node, 3);
+        // The axle is gone, its place in the tree is taken by another node.
+        let mut obj = assembly("scop_reload");
+        obj.groups[2].name = "frame".to_string();
+        object.reload_obj(obj);
+        assert_eq!(object.nodes[2].translation, [0.0; 3]);
+        assert!(object.nodes[3].visible);
+        assert_eq!(object.selected_node, 0);
+    }
+
+    #[test]
+    fn the_last_object_cannot_be_removed() {
+        let mut scene = scene();
+        scene.add(obj_parser("./obj/42.obj").unwrap());
+        assert_eq!(scene.remove(1).unwrap().name(), "42");
+        assert_eq!(scene.active, 0);
+        assert!(scene.remove(0).is_err());
+        assert_eq!(scene.objects.len(), 1);
+    }
+}
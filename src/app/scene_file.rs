@@ -0,0 +1,526 @@
+use std::{
+    f32::consts::TAU,
+    fs::{create_dir_all, read_to_string, write},
+    path::{Path, PathBuf}
+};
+
+use super::{
+    ctx::Ctx,
+    light::{Light, LightKind, MAX_LIGHTS},
+    parser::obj_parser,
+    scene::{Scene, SceneObject, Transform}
+};
+
+pub const SCENE_DIR: &str = "./scenes";
+pub const SCENE_EXTENSION: &str = "scene";
+
+const POLYGON_MODES: [&str; 3] = ["fill", "line", "point"];
+
+const HEADER: &str = "\
+# Scop scene, one setting per line. The lines after an object line describe that object.
+# Paths are relative to the working directory, angles are in degrees.
+# light <directional|point|spot> <x,y,z> <r,g,b> <intensity> <range> <inner,outer cone> <on|off> [direction x,y,z]
+# A light without direction points at the center of the scene.
+# node <name> <translation x,y,z> <rotation x,y,z> <scale x,y,z> <shown|hidden>
+";
+
+// Transform and visibility of a node, found by name when the scene is opened.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeEntry {
+    pub name: String,
+    pub translation: [f32; 3],
+    // Degrees.
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+    pub visible: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectEntry {
+    pub model: String,
+    pub texture: Option<String>,
+    pub position: [f32; 3],
+    // Degrees.
+    pub angle: f32,
+    pub nodes: Vec<NodeEntry>,
+}
+
+// Session written as text: the objects, then the settings, which keep their current value when missing.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SceneFile {
+    pub size: Option<(u32, u32)>,
+    // Camera field of view, in degrees.
+    pub fov: Option<f32>,
+    pub exposure: Option<f32>,
+    pub background: Option<[f32; 3]>,
+    pub shading: Option<String>,
+    pub polygon_mode: Option<i32>,
+    pub culling: Option<bool>,
+    pub rotation: Option<bool>,
+    pub speed: Option<f32>,
+    pub textured: Option<bool>,
+    pub environment: Option<bool>,
+    // Degrees.
+    pub environment_rotation: Option<f32>,
+    pub lights: Vec<Light>,
+    // Index in objects.
+    pub active: Option<usize>,
+    pub objects: Vec<ObjectEntry>,
+}
+
+// Shortest text giving back the same f32.
+fn floats(values: &[f32]) -> String {
+    values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(",")
+}
+
+fn parse_float(value: &str) -> Option<f32> {
+    value.trim().parse::<f32>().ok().filter(|value| value.is_finite())
+}
+
+fn parse_floats<const N: usize>(value: &str) -> Option<[f32; N]> {
+    value.split(',')
+        .map(parse_float)
+        .collect::<Option<Vec<f32>>>()
+        .and_then(|values| <[f32; N]>::try_from(values).ok())
+}
+
+fn parse_switch(value: &str, on: &str, off: &str) -> Option<bool> {
+    if value == on {
+        Some(true)
+    } else if value == off {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+fn switch(value: bool, on: &'static str, off: &'static str) -> &'static str {
+    if value { on } else { off }
+}
+
+impl SceneFile {
+    pub fn from_ctx(ctx: &Ctx) -> Self {
+        let objects = ctx.scene.objects.iter()
+            .map(|object| ObjectEntry {
+                model: object.obj.path.clone(),
+                texture: Some(object.texture.clone()),
+                position: object.transform.position,
+                angle: object.transform.angle.to_degrees(),
+                // Only the nodes moved or hidden.
+                nodes: object.nodes.iter().enumerate()
+                    .filter(|(_, node)| !node.is_identity() || !node.visible)
+                    .map(|(index, node)| NodeEntry {
+                        name: object.node_name(index),
+                        translation: node.translation,
+                        rotation: node.rotation.map(f32::to_degrees),
+                        scale: node.scale,
+                        visible: node.visible,
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self {
+            size: Some((ctx.width, ctx.height)),
+            fov: Some(ctx.fov.to_degrees()),
+            exposure: Some(ctx.exposure),
+            background: Some(ctx.background),
+            shading: Some(ctx.shading.clone()),
+            polygon_mode: Some(ctx.polmode),
+            culling: Some(ctx.backface),
+            rotation: Some(ctx.rotation),
+            speed: Some(ctx.speed_factor),
+            textured: Some(ctx.texture),
+            environment: Some(ctx.environment),
+            environment_rotation: Some(ctx.env_rotation.to_degrees()),
+            lights: ctx.lights.clone(),
+            active: Some(ctx.scene.active),
+            objects,
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines: Vec<String> = vec![HEADER.to_string()];
+        if let Some((width, height)) = self.size {
+            lines.push(format!("size {}x{}", width, height));
+        }
+        if let Some(fov) = self.fov {
+            lines.push(format!("fov {}", fov));
+        }
+        if let Some(exposure) = self.exposure {
+            lines.push(format!("exposure {}", exposure));
+        }
+        if let Some(background) = self.background {
+            lines.push(format!("background {}", floats(&background)));
+        }
+        if let Some(shading) = &self.shading {
+            lines.push(format!("shading {}", shading));
+        }
+        if let Some(mode) = self.polygon_mode {
+            lines.push(format!("polygon_mode {}", POLYGON_MODES[mode as usize]));
+        }
+        let switches = [
+            ("culling", self.culling),
+            ("rotation", self.rotation),
+            ("textured", self.textured),
+            ("environment", self.environment)
+        ];
+        for (key, value) in switches {
+            if let Some(value) = value {
+                lines.push(format!("{} {}", key, switch(value, "on", "off")));
+            }
+        }
+        if let Some(speed) = self.speed {
+            lines.push(format!("speed {}", speed));
+        }
+        if let Some(rotation) = self.environment_rotation {
+            lines.push(format!("environment_rotation {}", rotation));
+        }
+        for light in &self.lights {
+            lines.push(format!(
+                "light {} {} {} {} {} {} {}{}",
+                light.kind.name(), floats(&light.position), floats(&light.color), light.intensity, light.range,
+                floats(&[light.inner_cone.to_degrees(), light.outer_cone.to_degrees()]), switch(light.enabled, "on", "off"),
+                if light.aimed { String::new() } else { format!(" {}", floats(&light.direction)) }
+            ));
+        }
+        if let Some(active) = self.active {
+            lines.push(format!("active {}", active + 1));
+        }
+        for object in &self.objects {
+            lines.push(String::new());
+            lines.push(format!("object {}", object.model));
+            if let Some(texture) = &object.texture {
+                lines.push(format!("texture {}", texture));
+            }
+            lines.push(format!("position {}", floats(&object.position)));
+            lines.push(format!("angle {}", object.angle));
+            for node in &object.nodes {
+                lines.push(format!(
+                    "node {} {} {} {} {}",
+                    node.name, floats(&node.translation), floats(&node.rotation), floats(&node.scale), switch(node.visible, "shown", "hidden")
+                ));
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+
+    pub fn save(&self, filepath: &Path) -> Result<(), String> {
+        if let Some(parent) = filepath.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            create_dir_all(parent).map_err(|e| format!("Error: Cannot create {}. {}", parent.display(), e))?;
+        }
+        write(filepath, self.to_text()).map_err(|e| format!("Error: Cannot write {}. {}", filepath.display(), e))
+    }
+
+    // Lines starting with # are comments.
+    pub fn parse(filepath: &Path) -> Result<Self, String> {
+        let content = read_to_string(filepath).map_err(|e| format!("Error: Cannot open {}. {}", filepath.display(), e))?;
+        let mut file = Self::default();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("Error: {} line {}: Invalid format : {}.", filepath.display(), number + 1, line);
+            let (key, value) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let value = value.trim();
+            let fields: Vec<&str> = value.split_whitespace().collect();
+            let float = || parse_float(value).ok_or_else(invalid);
+            let on_off = || parse_switch(value, "on", "off").ok_or_else(invalid);
+            match key {
+                "size" => {
+                    let size = value.split_once('x')
+                        .and_then(|(width, height)| Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?)))
+                        .filter(|&(width, height)| width >= 1 && height >= 1)
+                        .ok_or_else(invalid)?;
+                    file.size = Some(size);
+                },
+                "fov" => file.fov = Some(parse_float(value).filter(|fov| (1.0..179.0).contains(fov)).ok_or_else(invalid)?),
+                "exposure" => file.exposure = Some(float()?),
+                "background" => file.background = Some(parse_floats(value).ok_or_else(invalid)?),
+                "shading" => file.shading = Some(value.to_string()),
+                "polygon_mode" => {
+                    let mode = POLYGON_MODES.iter().position(|mode| *mode == value).ok_or_else(invalid)?;
+                    file.polygon_mode = Some(mode as i32);
+                },
+                "culling" => file.culling = Some(on_off()?),
+                "rotation" => file.rotation = Some(on_off()?),
+                "textured" => file.textured = Some(on_off()?),
+                "environment" => file.environment = Some(on_off()?),
+                "speed" => file.speed = Some(float()?),
+                "environment_rotation" => file.environment_rotation = Some(float()?),
+                "light" => {
+                    let [kind, position, color, intensity, range, cones, enabled, ref direction @ ..] = fields[..] else {
+                        return Err(invalid());
+                    };
+                    if direction.len() > 1 {
+                        return Err(invalid());
+                    }
+                    let kind = LightKind::from_name(kind).ok_or_else(invalid)?;
+                    let mut light = Light::new(kind, parse_floats(position).ok_or_else(invalid)?, parse_floats(color).ok_or_else(invalid)?);
+                    light.intensity = parse_float(intensity).ok_or_else(invalid)?;
+                    light.range = parse_float(range).ok_or_else(invalid)?;
+                    let [inner, outer] = parse_floats(cones).ok_or_else(invalid)?;
+                    (light.inner_cone, light.outer_cone) = (inner.to_radians(), outer.to_radians());
+                    light.enabled = parse_switch(enabled, "on", "off").ok_or_else(invalid)?;
+                    if let Some(direction) = direction.first() {
+                        light.set_direction(parse_floats(direction).filter(|&direction| direction != [0.0; 3]).ok_or_else(invalid)?);
+                    }
+                    if file.lights.len() == MAX_LIGHTS {
+                        return Err(format!("Error: {} line {}: No more than {} lights.", filepath.display(), number + 1, MAX_LIGHTS));
+                    }
+                    file.lights.push(light);
+                },
+                "active" => {
+                    let active = value.parse::<usize>().ok().filter(|&active| active >= 1).ok_or_else(invalid)?;
+                    file.active = Some(active - 1);
+                },
+                "object" => file.objects.push(ObjectEntry {
+                    model: value.to_string(),
+                    texture: None,
+                    position: [0.0, 0.0, 8.0],
+                    angle: 0.0,
+                    nodes: Vec::new(),
+                }),
+                "texture" | "position" | "angle" | "node" => {
+                    let object = file.objects.last_mut()
+                        .ok_or_else(|| format!("Error: {} line {}: {} is set before any object.", filepath.display(), number + 1, key))?;
+                    match key {
+                        "texture" => object.texture = Some(value.to_string()),
+                        "position" => object.position = parse_floats(value).ok_or_else(invalid)?,
+                        "angle" => object.angle = float()?,
+                        _ => {
+                            // The name may hold spaces, the four last fields are the transform and the visibility.
+                            let Some(split) = fields.len().checked_sub(4).filter(|&split| split > 0) else {
+                                return Err(invalid());
+                            };
+                            let [translation, rotation, scale, visible] = fields[split..] else {
+                                return Err(invalid());
+                            };
+                            object.nodes.push(NodeEntry {
+                                name: fields[..split].join(" "),
+                                translation: parse_floats(translation).ok_or_else(invalid)?,
+                                rotation: parse_floats(rotation).ok_or_else(invalid)?,
+                                scale: parse_floats(scale).ok_or_else(invalid)?,
+                                visible: parse_switch(visible, "shown", "hidden").ok_or_else(invalid)?,
+                            });
+                        }
+                    }
+                },
+                _ => return Err(invalid())
+            }
+        }
+        if file.objects.is_empty() {
+            return Err(format!("Error: {} has no object.", filepath.display()));
+        }
+        if file.active.is_some_and(|active| active >= file.objects.len()) {
+            return Err(format!("Error: {} has no object {}.", filepath.display(), file.active.unwrap() + 1));
+        }
+        Ok(file)
+    }
+
+    // Objects with their models loaded, the textures are read by the renderer.
+    pub fn scene(&self) -> Result<Scene, String> {
+        let mut objects = Vec::new();
+        for entry in &self.objects {
+            let transform = Transform::new(entry.position, entry.angle.to_radians().rem_euclid(TAU));
+            let mut object = SceneObject::new(obj_parser(&entry.model)?, transform);
+            if let Some(texture) = &entry.texture {
+                object.texture = texture.clone();
+            }
+            for node in &entry.nodes {
+                let index = (0..object.nodes.len())
+                    .find(|&index| object.node_name(index) == node.name)
+                    .ok_or_else(|| format!("Error: {} has no node {}.", entry.model, node.name))?;
+                let target = &mut object.nodes[index];
+                target.translation = node.translation;
+                target.rotation = node.rotation.map(f32::to_radians);
+                target.scale = node.scale;
+                target.visible = node.visible;
+            }
+            objects.push(object);
+        }
+        let mut objects = objects.into_iter();
+        let Some(first) = objects.next() else {
+            return Err("Error: The scene needs at least one object.".to_string());
+        };
+        let mut scene = Scene::new(first);
+        scene.objects.extend(objects);
+        scene.active = self.active.unwrap_or(0).min(scene.objects.len() - 1);
+        Ok(scene)
+    }
+
+    // Settings given by the file, the scene is set apart with scene().
+    pub fn apply(&self, ctx: &mut Ctx) {
+        if let Some((width, height)) = self.size {
+            (ctx.width, ctx.height) = (width, height);
+        }
+        if let Some(fov) = self.fov {
+            ctx.fov = fov.to_radians();
+        }
+        if let Some(exposure) = self.exposure {
+            ctx.exposure = exposure;
+        }
+        if let Some(background) = self.background {
+            ctx.background = background;
+        }
+        if let Some(shading) = &self.shading {
+            ctx.shading = shading.clone();
+        }
+        if let Some(mode) = self.polygon_mode {
+            ctx.polmode = mode;
+        }
+        if let Some(culling) = self.culling {
+            ctx.backface = culling;
+        }
+        if let Some(rotation) = self.rotation {
+            ctx.rotation = rotation;
+        }
+        if let Some(speed) = self.speed {
+            ctx.speed_factor = speed;
+        }
+        // Without transition, the scene shows up as it was saved.
+        if let Some(textured) = self.textured {
+            ctx.texture = textured;
+            ctx.mix_factor = if textured { 1.0 } else { 0.0 };
+        }
+        if let Some(environment) = self.environment {
+            ctx.environment = environment;
+        }
+        if let Some(rotation) = self.environment_rotation {
+            ctx.env_rotation = rotation.to_radians();
+        }
+        if !self.lights.is_empty() {
+            ctx.lights = self.lights.clone();
+            ctx.selected_light = 0;
+        }
+    }
+
+    // Objects then settings, in a context made for them.
+    pub fn ctx(&self) -> Result<Ctx, String> {
+        let mut ctx = Ctx::with_scene(self.scene()?);
+        self.apply(&mut ctx);
+        Ok(ctx)
+    }
+}
+
+// Unused name in the scene folder, numbered after the saved scenes.
+pub fn scene_path() -> PathBuf {
+    let dir = Path::new(SCENE_DIR);
+    (1..).map(|index| dir.join(format!("scene_{}.{}", index, SCENE_EXTENSION)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{backend::CpuBackend, viewer::Viewer};
+
+    // Two objects, a hidden and moved node, and settings away from their defaults.
+    fn session() -> Ctx {
+        let mut ctx = Ctx::with_obj(obj_parser("./obj/monkey.obj").unwrap());
+        ctx.scene.active_mut().texture = "./obj/Texture/texture.ppm".to_string();
+        ctx.scene.add(obj_parser("./obj/42.obj").unwrap());
+        let object = &mut ctx.scene.objects[1];
+        object.texture = "./obj/Texture/metal.ppm".to_string();
+        object.transform = Transform::new([0.5, -0.25, 3.0], 1.2);
+        object.nodes[1].translation = [0.0, 0.5, 0.0];
+        object.nodes[1].rotation = [0.1, 0.2, 0.3];
+        object.nodes[0].visible = false;
+        (ctx.width, ctx.height) = (64, 48);
+        ctx.fov = 1.2;
+        ctx.background = [0.2, 0.3, 0.4];
+        ctx.shading = "blinn_phong".to_string();
+        ctx.polmode = 1;
+        ctx.backface = false;
+        ctx.rotation = false;
+        ctx.speed_factor = -1.5;
+        ctx.texture = false;
+        ctx.mix_factor = 0.0;
+        ctx.exposure = 2.5;
+        ctx.lights.push(Light::new(LightKind::Spot, [-1.0, 2.0, 3.0], [1.0, 0.5, 0.25]));
+        ctx.lights[1].enabled = false;
+        ctx.lights[1].set_direction([0.0, -1.0, 0.0]);
+        ctx.scene.active = 1;
+        ctx
+    }
+
+    fn saved(name: &str, text: &str) -> PathBuf {
+        let filepath = std::env::temp_dir().join(format!("{}.{}", name, SCENE_EXTENSION));
+        write(&filepath, text).unwrap();
+        filepath
+    }
+
+    #[test]
+    fn saved_scenes_read_back_the_same() {
+        let ctx = session();
+        let file = SceneFile::from_ctx(&ctx);
+        let filepath = std::env::temp_dir().join("scop_round_trip").join("session.scene");
+        file.save(&filepath).unwrap();
+        // The directions of the lights aimed at the scene are not saved, they are found again.
+        assert_eq!(SceneFile::parse(&filepath).unwrap().to_text(), file.to_text());
+
+        let restored = file.ctx().unwrap();
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        assert_eq!((restored.width, restored.height, restored.polmode), (64, 48, 1));
+        assert_eq!((restored.backface, restored.rotation, restored.texture), (false, false, false));
+        assert_eq!((restored.shading.as_str(), restored.background, restored.mix_factor), ("blinn_phong", [0.2, 0.3, 0.4], 0.0));
+        assert!(close(restored.fov, 1.2) && close(restored.speed_factor, -1.5) && close(restored.exposure, 2.5));
+        assert_eq!(restored.lights.len(), 2);
+        assert!(!restored.lights[1].enabled && restored.lights[1].kind == LightKind::Spot);
+        assert!(close(restored.lights[1].outer_cone, ctx.lights[1].outer_cone));
+        assert!(restored.lights[0].aimed && !restored.lights[1].aimed);
+        assert!(restored.lights[1].direction.iter().zip(ctx.lights[1].direction).all(|(&a, b)| close(a, b)));
+        assert_eq!(restored.scene.active, 1);
+        for (object, original) in restored.scene.objects.iter().zip(&ctx.scene.objects) {
+            assert_eq!(object.obj.path, original.obj.path);
+            assert_eq!(object.texture, original.texture);
+            assert_eq!(object.transform.position, original.transform.position);
+            assert!(close(object.transform.angle, original.transform.angle));
+            for (node, original) in object.nodes.iter().zip(&original.nodes) {
+                assert_eq!((node.translation, node.scale, node.visible), (original.translation, original.scale, original.visible));
+                assert!((0..3).all(|i| close(node.rotation[i], original.rotation[i])));
+            }
+        }
+    }
+
+    #[test]
+    fn saved_scenes_render_the_same() {
+        let mut ctx = session();
+        ctx.shading = "gouraud".to_string();
+        let restored = SceneFile::from_ctx(&ctx).ctx().unwrap();
+        let image = Viewer::new(ctx, CpuBackend::new()).unwrap().render(1, false).unwrap();
+        let restored = Viewer::new(restored, CpuBackend::new()).unwrap().render(1, false).unwrap();
+        assert!(image.pixels == restored.pixels);
+    }
+
+    #[test]
+    fn missing_settings_keep_their_value() {
+        let filepath = saved("scop_minimal", "# only a model\nobject ./obj/42.obj\nangle 90\n");
+        let file = SceneFile::parse(&filepath).unwrap();
+        let mut ctx = session();
+        let speed = ctx.speed_factor;
+        ctx.scene = file.scene().unwrap();
+        file.apply(&mut ctx);
+        assert_eq!(ctx.speed_factor, speed);
+        assert_eq!(ctx.lights.len(), 2);
+        assert_eq!(ctx.scene.objects.len(), 1);
+        assert_eq!(ctx.scene.active().transform.position, [0.0, 0.0, 8.0]);
+    }
+
+    #[test]
+    fn errors_give_the_line() {
+        let cases = [
+            ("scop_no_object", "shading gray\n", "has no object"),
+            ("scop_early_node", "node wheel 0,0,0 0,0,0 1,1,1 shown\nobject ./obj/42.obj\n", "line 1: node is set before any object"),
+            ("scop_bad_light", "object ./obj/42.obj\nlight sun 0,0,0 1,1,1 1 20 10,20 on\n", "line 2: Invalid format"),
+            ("scop_bad_mode", "object ./obj/42.obj\n\npolygon_mode solid\n", "line 3: Invalid format")
+        ];
+        for (name, text, message) in cases {
+            let err = SceneFile::parse(&saved(name, text)).unwrap_err();
+            assert!(err.contains(message), "{}", err);
+        }
+        let file = SceneFile::parse(&saved("scop_bad_node", "object ./obj/42.obj\nnode wheel 0,0,0 0,0,0 1,1,1 hidden\n")).unwrap();
+        assert!(file.scene().is_err_and(|err| err.contains("has no node wheel")));
+    }
+}
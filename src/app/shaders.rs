@@ -1,42 +1,635 @@
-use super::ctx::Ctx;
+// Light list shared by the lit fragment shaders, filled by light::LightUniforms.
+macro_rules! lights_glsl {
+    () => {
+        r#"
+                #define MAX_LIGHTS 8
+                #define LIGHT_DIRECTIONAL 0
+                #define LIGHT_POINT 1
+                #define LIGHT_SPOT 2
 
-pub struct Shader {
-    pub vertex_shader: &'static str,
-    pub fragment_shader: &'static str,
+                struct Light {
+                    int kind;
+                    vec3 position;
+                    vec3 direction;
+                    vec3 color;
+                    float intensity;
+                    float range;
+                    float inner_cone;
+                    float outer_cone;
+                };
+
+                uniform Light lights[MAX_LIGHTS];
+                uniform int light_count;
+
+                // Radiance reaching position, l receives the direction toward the light.
+                vec3 light_radiance(Light light, vec3 position, out vec3 l) {
+                    if (light.kind == LIGHT_DIRECTIONAL) {
+                        l = -normalize(light.direction);
+                        return light.color * light.intensity;
+                    }
+                    vec3 to_light = light.position - position;
+                    float dist = length(to_light);
+                    l = to_light / max(dist, 0.0001);
+                    float window = clamp(1.0 - pow(dist / light.range, 4.0), 0.0, 1.0);
+                    float attenuation = window * window;
+                    if (light.kind == LIGHT_SPOT) {
+                        attenuation *= smoothstep(light.outer_cone, light.inner_cone, dot(-l, normalize(light.direction)));
+                    }
+                    return light.color * light.intensity * attenuation;
+                }
+        "#
+    };
+}
+
+// Percentage closer filtering of the shadow map, only the light at shadow_light casts shadows.
+macro_rules! shadow_glsl {
+    () => {
+        r#"
+                in vec4 v_light_space;
+
+                uniform sampler2D shadow_map;
+                uniform int shadow_light;
+                uniform float shadow_bias;
+
+                float shadow_factor(int light_index, vec3 normal, vec3 l) {
+                    if (light_index != shadow_light) {
+                        return 1.0;
+                    }
+                    vec3 projected = v_light_space.xyz / v_light_space.w * 0.5 + 0.5;
+                    if (projected.z > 1.0 || any(lessThan(projected.xy, vec2(0.0))) || any(greaterThan(projected.xy, vec2(1.0)))) {
+                        return 1.0;
+                    }
+                    float bias = max(shadow_bias * (1.0 - dot(normal, l)), shadow_bias * 0.2);
+                    vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0));
+                    float lit = 0.0;
+                    for (int x = -2; x <= 2; x++) {
+                        for (int y = -2; y <= 2; y++) {
+                            float depth = texture(shadow_map, projected.xy + vec2(x, y) * texel).r;
+                            lit += projected.z - bias > depth ? 0.0 : 1.0;
+                        }
+                    }
+                    return lit / 25.0;
+                }
+        "#
+    };
 }
 
-impl Shader {
-    pub fn new() -> Self {
-        Self {
-            vertex_shader: r#"
+// Tangent space normal mapping, the tangent frame is left unnormalized like MikkTSpace expects.
+macro_rules! normal_mapping_glsl {
+    () => {
+        r#"
+                uniform sampler2D normal_map;
+                uniform bool has_normal_map;
+
+                vec3 shading_normal(vec3 normal, vec4 tangent, vec2 tex_coords) {
+                    if (!has_normal_map || dot(tangent.xyz, tangent.xyz) < 1e-8) {
+                        return normalize(normal);
+                    }
+                    vec3 mapped = texture(normal_map, tex_coords).rgb * 2.0 - 1.0;
+                    vec3 bitangent = tangent.w * cross(normal, tangent.xyz);
+                    return normalize(mapped.x * tangent.xyz + mapped.y * bitangent + mapped.z * normal);
+                }
+        "#
+    };
+}
+
+// Anti-aliased edges drawn over the shaded color from the barycentric coordinates,
+// with v_hidden_edge set the diagonals of the split quads disappear.
+macro_rules! wireframe_glsl {
+    () => {
+        r#"
+                in vec3 v_barycentric;
+                in vec3 v_hidden_edge;
+
+                uniform bool wireframe;
+                uniform bool wireframe_polygons;
+                uniform float wireframe_width;
+                uniform vec3 wireframe_color;
+
+                vec4 apply_wireframe(vec4 color) {
+                    if (!wireframe) {
+                        return color;
+                    }
+                    vec3 b = wireframe_polygons ? max(v_barycentric, v_hidden_edge) : v_barycentric;
+                    vec3 pixels = b / max(fwidth(b), vec3(1e-6));
+                    float edge_distance = min(min(pixels.x, pixels.y), pixels.z);
+                    float coverage = 1.0 - smoothstep(wireframe_width * 0.5 - 0.5, wireframe_width * 0.5 + 0.5, edge_distance);
+                    return vec4(mix(color.rgb, wireframe_color, coverage), color.a);
+                }
+        "#
+    };
+}
+
+// Snippets available to the shader files through `#include <name>`.
+pub const SHADER_SNIPPETS: [(&str, &str); 4] = [
+    ("lights", lights_glsl!()),
+    ("shadow", shadow_glsl!()),
+    ("normal_mapping", normal_mapping_glsl!()),
+    ("wireframe", wireframe_glsl!())
+];
+
+// Cook-Torrance with a GGX distribution, inputs are linear and the output is tone mapped to sRGB.
+// A light of intensity 1 brings a white diffuse surface facing it to 1.0, like the other modes.
+pub const PBR_FRAGMENT_SHADER: &str = concat!("#version 330\n", wireframe_glsl!(), lights_glsl!(), shadow_glsl!(), normal_mapping_glsl!(), r#"
+                #define PI 3.14159265359
+
+                in vec3 v_normal;
+                in vec3 v_position;
+                in vec2 v_tex_coords;
+                in vec4 v_tangent;
+
+                out vec4 color;
+
+                uniform sampler2D diffuse_texture;
+                uniform sampler2D previous_texture;
+                uniform float texture_fade;
+                uniform float mix_factor;
+
+                uniform vec3 base_color;
+                uniform float metallic;
+                uniform float roughness;
+                uniform bool has_base_color_map;
+                uniform sampler2D base_color_map;
+                uniform sampler2D metallic_map;
+                uniform sampler2D roughness_map;
+                uniform sampler2D occlusion_map;
+
+                uniform bool has_environment;
+                uniform samplerCube irradiance_map;
+                uniform samplerCube prefiltered_map;
+                uniform sampler2D brdf_lut;
+                uniform float prefiltered_levels;
+                uniform mat3 environment_rotation;
+                uniform float exposure;
+
+                vec3 srgb_to_linear(vec3 c) {
+                    return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+                }
+
+                vec3 linear_to_srgb(vec3 c) {
+                    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+                }
+
+                // Narkowicz fit of the ACES filmic curve.
+                vec3 tone_map(vec3 c) {
+                    return clamp((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14), 0.0, 1.0);
+                }
+
+                float distribution_ggx(float n_dot_h, float alpha) {
+                    float a2 = alpha * alpha;
+                    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+                    return a2 / (PI * d * d);
+                }
+
+                float geometry_smith(float n_dot_v, float n_dot_l, float rough) {
+                    float k = (rough + 1.0) * (rough + 1.0) / 8.0;
+                    float gv = n_dot_v / (n_dot_v * (1.0 - k) + k);
+                    float gl = n_dot_l / (n_dot_l * (1.0 - k) + k);
+                    return gv * gl;
+                }
+
+                vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+                    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+                }
+
+                vec3 fresnel_schlick_roughness(float cos_theta, vec3 f0, float rough) {
+                    return f0 + (max(vec3(1.0 - rough), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+                }
+
+                void main() {
+                    vec3 texture_color = has_base_color_map
+                        ? texture(base_color_map, v_tex_coords).rgb
+                        : mix(texture(previous_texture, v_tex_coords).rgb, texture(diffuse_texture, v_tex_coords).rgb, texture_fade);
+                    vec3 albedo = mix(base_color, srgb_to_linear(texture_color), mix_factor);
+                    float metal = clamp(metallic * texture(metallic_map, v_tex_coords).r, 0.0, 1.0);
+                    float rough = clamp(roughness * texture(roughness_map, v_tex_coords).r, 0.04, 1.0);
+                    float occlusion = texture(occlusion_map, v_tex_coords).r;
+
+                    vec3 n = shading_normal(v_normal, v_tangent, v_tex_coords);
+                    vec3 v = normalize(-v_position);
+                    float n_dot_v = max(dot(n, v), 0.0001);
+                    vec3 f0 = mix(vec3(0.04), albedo, metal);
+
+                    vec3 radiance_out = vec3(0.0);
+                    for (int i = 0; i < light_count; i++) {
+                        vec3 l;
+                        vec3 radiance = light_radiance(lights[i], v_position, l) * PI;
+                        float n_dot_l = max(dot(n, l), 0.0);
+                        if (n_dot_l <= 0.0) {
+                            continue;
+                        }
+                        vec3 h = normalize(v + l);
+                        float n_dot_h = max(dot(n, h), 0.0);
+                        vec3 f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+                        float d = distribution_ggx(n_dot_h, rough * rough);
+                        float g = geometry_smith(n_dot_v, n_dot_l, rough);
+                        vec3 specular = d * g * f / (4.0 * n_dot_v * n_dot_l + 0.0001);
+                        vec3 diffuse = (1.0 - f) * (1.0 - metal) * albedo / PI;
+                        radiance_out += (diffuse + specular) * radiance * n_dot_l * shadow_factor(i, n, l);
+                    }
+                    vec3 ambient = vec3(0.03) * albedo * occlusion;
+                    if (has_environment) {
+                        vec3 f = fresnel_schlick_roughness(n_dot_v, f0, rough);
+                        vec3 kd = (1.0 - f) * (1.0 - metal);
+                        vec3 irradiance = texture(irradiance_map, environment_rotation * n).rgb;
+                        vec3 r = environment_rotation * reflect(-v, n);
+                        vec3 prefiltered = textureLod(prefiltered_map, r, rough * (prefiltered_levels - 1.0)).rgb;
+                        vec2 brdf = texture(brdf_lut, vec2(n_dot_v, rough)).rg;
+                        ambient = (kd * irradiance * albedo + prefiltered * (f * brdf.x + brdf.y)) * occlusion;
+                    }
+                    color = vec4(linear_to_srgb(tone_map((ambient + radiance_out) * exposure)), 1.0);
+                    color = apply_wireframe(color);
+                }
+"#);
+
+// Full screen triangle without vertex attributes, used by the environment passes.
+pub const SCREEN_VERTEX_SHADER: &str = r#"
+            #version 330
+                out vec2 v_uv;
+
+                void main() {
+                    vec2 position = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+                    v_uv = position;
+                    gl_Position = vec4(position * 2.0 - 1.0, 0.0, 1.0);
+                }
+"#;
+
+// Direction of a cubemap texel, following the OpenGL face orientation.
+macro_rules! cube_face_glsl {
+    () => {
+        r#"
+                in vec2 v_uv;
+
+                uniform int face;
+
+                vec3 face_direction() {
+                    vec2 st = v_uv * 2.0 - 1.0;
+                    vec3 dir;
+                    if (face == 0) dir = vec3(1.0, -st.y, -st.x);
+                    else if (face == 1) dir = vec3(-1.0, -st.y, st.x);
+                    else if (face == 2) dir = vec3(st.x, 1.0, st.y);
+                    else if (face == 3) dir = vec3(st.x, -1.0, -st.y);
+                    else if (face == 4) dir = vec3(st.x, -st.y, 1.0);
+                    else dir = vec3(-st.x, -st.y, -1.0);
+                    return normalize(dir);
+                }
+        "#
+    };
+}
+
+// GGX importance sampling shared by the prefilter and the BRDF integration.
+macro_rules! importance_sampling_glsl {
+    () => {
+        r#"
+                #define PI 3.14159265359
+
+                vec2 hammersley(uint i, uint count) {
+                    uint bits = i;
+                    bits = (bits << 16u) | (bits >> 16u);
+                    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+                    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+                    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+                    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+                    return vec2(float(i) / float(count), float(bits) * 2.3283064365386963e-10);
+                }
+
+                vec3 importance_sample_ggx(vec2 xi, vec3 n, float rough) {
+                    float a = rough * rough;
+                    float phi = 2.0 * PI * xi.x;
+                    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+                    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+                    vec3 h = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+                    vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+                    vec3 tangent = normalize(cross(up, n));
+                    vec3 bitangent = cross(n, tangent);
+                    return normalize(tangent * h.x + bitangent * h.y + n * h.z);
+                }
+        "#
+    };
+}
+
+pub const EQUIRECT_FRAGMENT_SHADER: &str = concat!("#version 330\n", cube_face_glsl!(), r#"
+                #define PI 3.14159265359
+
+                out vec4 color;
+
+                uniform sampler2D equirect;
+
+                void main() {
+                    vec3 dir = face_direction();
+                    vec2 uv = vec2(atan(dir.z, dir.x) / (2.0 * PI) + 0.5, asin(clamp(dir.y, -1.0, 1.0)) / PI + 0.5);
+                    color = vec4(texture(equirect, uv).rgb, 1.0);
+                }
+"#);
+
+// Cosine weighted convolution of the environment over the hemisphere.
+pub const IRRADIANCE_FRAGMENT_SHADER: &str = concat!("#version 330\n", cube_face_glsl!(), r#"
+                #define PI 3.14159265359
+
+                out vec4 color;
+
+                uniform samplerCube environment;
+
+                void main() {
+                    vec3 n = face_direction();
+                    vec3 up = abs(n.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+                    vec3 right = normalize(cross(up, n));
+                    up = cross(n, right);
+                    vec3 irradiance = vec3(0.0);
+                    float samples = 0.0;
+                    for (float phi = 0.0; phi < 2.0 * PI; phi += 0.1) {
+                        for (float theta = 0.0; theta < 0.5 * PI; theta += 0.1) {
+                            vec3 tangent_sample = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+                            vec3 dir = tangent_sample.x * right + tangent_sample.y * up + tangent_sample.z * n;
+                            irradiance += textureLod(environment, dir, 4.0).rgb * cos(theta) * sin(theta);
+                            samples += 1.0;
+                        }
+                    }
+                    color = vec4(PI * irradiance / samples, 1.0);
+                }
+"#);
+
+// Split sum approximation, one roughness per mip level.
+pub const PREFILTER_FRAGMENT_SHADER: &str = concat!("#version 330\n", cube_face_glsl!(), importance_sampling_glsl!(), r#"
+                out vec4 color;
+
+                uniform samplerCube environment;
+                uniform float roughness;
+                uniform float resolution;
+
+                void main() {
+                    vec3 n = face_direction();
+                    vec3 v = n;
+                    const uint SAMPLES = 256u;
+                    vec3 prefiltered = vec3(0.0);
+                    float weight = 0.0;
+                    for (uint i = 0u; i < SAMPLES; i++) {
+                        vec3 h = importance_sample_ggx(hammersley(i, SAMPLES), n, roughness);
+                        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+                        float n_dot_l = dot(n, l);
+                        if (n_dot_l > 0.0) {
+                            // Reads a blurrier level for the unlikely samples to avoid fireflies.
+                            float a = roughness * roughness;
+                            float n_dot_h = max(dot(n, h), 0.0);
+                            float d = (n_dot_h * n_dot_h * (a * a - 1.0) + 1.0);
+                            float pdf = a * a / (PI * d * d) / 4.0 + 0.0001;
+                            float sa_texel = 4.0 * PI / (6.0 * resolution * resolution);
+                            float sa_sample = 1.0 / (float(SAMPLES) * pdf + 0.0001);
+                            float level = roughness == 0.0 ? 0.0 : 0.5 * log2(sa_sample / sa_texel);
+                            prefiltered += textureLod(environment, l, level).rgb * n_dot_l;
+                            weight += n_dot_l;
+                        }
+                    }
+                    color = vec4(prefiltered / max(weight, 0.0001), 1.0);
+                }
+"#);
+
+pub const BRDF_FRAGMENT_SHADER: &str = concat!("#version 330\n", importance_sampling_glsl!(), r#"
+                in vec2 v_uv;
+
+                out vec4 color;
+
+                void main() {
+                    float n_dot_v = max(v_uv.x, 0.0001);
+                    float roughness = v_uv.y;
+                    vec3 v = vec3(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+                    vec3 n = vec3(0.0, 0.0, 1.0);
+                    float k = roughness * roughness / 2.0;
+                    const uint SAMPLES = 512u;
+                    float scale = 0.0;
+                    float bias = 0.0;
+                    for (uint i = 0u; i < SAMPLES; i++) {
+                        vec3 h = importance_sample_ggx(hammersley(i, SAMPLES), n, roughness);
+                        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+                        float n_dot_l = max(l.z, 0.0);
+                        float n_dot_h = max(h.z, 0.0);
+                        float v_dot_h = max(dot(v, h), 0.0);
+                        if (n_dot_l > 0.0) {
+                            float g = n_dot_v / (n_dot_v * (1.0 - k) + k) * n_dot_l / (n_dot_l * (1.0 - k) + k);
+                            float g_vis = g * v_dot_h / (n_dot_h * n_dot_v);
+                            float fc = pow(1.0 - v_dot_h, 5.0);
+                            scale += (1.0 - fc) * g_vis;
+                            bias += fc * g_vis;
+                        }
+                    }
+                    color = vec4(scale / float(SAMPLES), bias / float(SAMPLES), 0.0, 1.0);
+                }
+"#);
+
+// Environment behind the model, the camera looks toward +Z without rotation.
+pub const SKYBOX_FRAGMENT_SHADER: &str = r#"
+            #version 330
+                in vec2 v_uv;
+
+                out vec4 color;
+
+                uniform samplerCube environment;
+                uniform mat4 perspective_matrix;
+                uniform mat3 environment_rotation;
+                uniform float exposure;
+
+                vec3 linear_to_srgb(vec3 c) {
+                    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+                }
+
+                vec3 tone_map(vec3 c) {
+                    return clamp((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14), 0.0, 1.0);
+                }
+
+                void main() {
+                    vec2 ndc = v_uv * 2.0 - 1.0;
+                    vec3 dir = normalize(vec3(ndc.x / perspective_matrix[0][0], ndc.y / perspective_matrix[1][1], 1.0));
+                    vec3 env = textureLod(environment, environment_rotation * dir, 0.0).rgb;
+                    color = vec4(linear_to_srgb(tone_map(env * exposure)), 1.0);
+                }
+"#;
+
+// Depth pre-pass from the shadow casting light.
+pub const SHADOW_VERTEX_SHADER: &str = r#"
+            #version 330
+                in vec3 position;
+
+                uniform mat4 rotation_matrix;
+                uniform mat4 light_matrix;
+                uniform vec3 object_center;
+
+                void main() {
+                    vec3 centered_position = position - object_center;
+                    vec4 rotated_position = rotation_matrix * vec4(centered_position, 1.0);
+                    vec3 final_position = vec3(rotated_position) + object_center;
+                    gl_Position = light_matrix * vec4(final_position, 1.0);
+                }
+"#;
+
+pub const SHADOW_FRAGMENT_SHADER: &str = r#"
+            #version 330
+                void main() {
+                }
+"#;
+
+// Ground plane receiving the shadows, it does not follow the object rotation.
+pub const GROUND_VERTEX_SHADER: &str = r#"
+            #version 330
+                in vec3 position;
+                in vec3 normal;
+
+                out vec3 v_position;
+                out vec3 v_normal;
+                out vec4 v_light_space;
+
+                uniform mat4 perspective_matrix;
+                uniform mat4 light_matrix;
+
+                void main() {
+                    v_position = position;
+                    v_normal = normal;
+                    v_light_space = light_matrix * vec4(position, 1.0);
+                    gl_Position = perspective_matrix * vec4(position, 1.0);
+                }
+"#;
+
+pub const GROUND_FRAGMENT_SHADER: &str = concat!("#version 330\n", lights_glsl!(), shadow_glsl!(), r#"
+                in vec3 v_position;
+                in vec3 v_normal;
+
+                out vec4 color;
+
+                void main() {
+                    vec3 ground_color = vec3(0.35, 0.35, 0.4);
+                    vec3 normal = normalize(v_normal);
+                    vec3 result = ground_color * 0.3;
+                    for (int i = 0; i < light_count; i++) {
+                        vec3 l;
+                        vec3 radiance = light_radiance(lights[i], v_position, l);
+                        float diffuse = max(dot(normal, l), 0.0);
+                        result += diffuse * ground_color * radiance * shadow_factor(i, normal, l);
+                    }
+                    color = vec4(result, 1.0);
+                }
+"#);
+
+pub const GIZMO_VERTEX_SHADER: &str = r#"
+            #version 330
+                in vec3 position;
+                in vec3 color;
+
+                out vec3 v_color;
+
+                uniform mat4 perspective_matrix;
+
+                void main() {
+                    v_color = color;
+                    gl_Position = perspective_matrix * vec4(position, 1.0);
+                }
+"#;
+
+// Lines given in object space, placed like the model.
+pub const MODEL_LINE_VERTEX_SHADER: &str = r#"
+            #version 330
+                in vec3 position;
+                in vec3 color;
+
+                out vec3 v_color;
+
+                uniform mat4 rotation_matrix;
+                uniform mat4 perspective_matrix;
+                uniform vec3 object_center;
+
+                void main() {
+                    v_color = color;
+                    vec4 rotated_position = rotation_matrix * vec4(position - object_center, 1.0);
+                    gl_Position = perspective_matrix * vec4(vec3(rotated_position) + object_center, 1.0);
+                }
+"#;
+
+pub const GIZMO_FRAGMENT_SHADER: &str = r#"
+            #version 330
+                in vec3 v_color;
+
+                out vec4 color;
+
+                void main() {
+                    color = vec4(v_color, 1.0);
+                }
+"#;
+
+// Positions in pixels from the top left corner of the target.
+pub const TEXT_VERTEX_SHADER: &str = r#"
+            #version 330
+                in vec2 position;
+                in vec2 tex_coords;
+
+                out vec2 v_tex_coords;
+
+                uniform vec2 screen_size;
+
+                void main() {
+                    v_tex_coords = tex_coords;
+                    vec2 ndc = position / screen_size * 2.0 - 1.0;
+                    gl_Position = vec4(ndc.x, -ndc.y, 0.0, 1.0);
+                }
+"#;
+
+pub const TEXT_FRAGMENT_SHADER: &str = r#"
+            #version 330
+                in vec2 v_tex_coords;
+
+                out vec4 color;
+
+                uniform sampler2D font;
+                uniform vec4 text_color;
+
+                void main() {
+                    float glyph = texture(font, v_tex_coords).r;
+                    color = mix(vec4(0.0, 0.0, 0.0, 0.6), vec4(text_color.rgb, 1.0), glyph);
+                    color.a *= text_color.a;
+                }
+"#;
+
+// Model transform shared by the built-in shading modes.
+pub const MAIN_VERTEX_SHADER: &str = r#"
             #version 330
                 in vec3 position;
                 in vec3 normal;
                 in vec2 tex_coords;
+                in vec4 tangent;
+                in vec3 barycentric;
+                in vec3 hidden_edge;
                 in int id;
                 
                 out vec2 v_tex_coords;
                 out vec3 v_normal;
+                out vec4 v_tangent;
                 out vec3 v_position;
+                out vec4 v_light_space;
+                out vec3 v_barycentric;
+                out vec3 v_hidden_edge;
                 flat out int v_id; 
 
                 uniform mat4 rotation_matrix;
+                uniform mat3 normal_matrix;
                 uniform mat4 perspective_matrix;
+                uniform mat4 light_matrix;
                 uniform vec3 object_center;
 
                 void main() {
                     v_id = id;
                     v_tex_coords = tex_coords;
-                    v_normal = normalize(transpose(inverse(mat3(rotation_matrix))) * normal);
-                    v_position = vec3(rotation_matrix * vec4(position, 1.0));
+                    v_barycentric = barycentric;
+                    v_hidden_edge = hidden_edge;
+                    v_normal = normalize(normal_matrix * normal);
+                    v_tangent = vec4(mat3(rotation_matrix) * tangent.xyz, tangent.w);
                     vec3 centered_position = position - object_center;
                     vec4 rotated_position = rotation_matrix * vec4(centered_position, 1.0);
                     vec3 final_position = vec3(rotated_position) + object_center;
+                    v_position = final_position;
+                    v_light_space = light_matrix * vec4(final_position, 1.0);
                     gl_Position = perspective_matrix * vec4(final_position, 1.0);
                 }
-            "#,
-            fragment_shader: r#"
-            #version 330
+"#;
+
+// Facets in alternating gray levels.
+pub const GRAY_FRAGMENT_SHADER: &str = concat!("#version 330\n", wireframe_glsl!(), r#"
                 flat in int v_id;
                 in vec2 v_tex_coords;
 
@@ -44,10 +637,12 @@ impl Shader {
                 out vec4 color;
 
                 uniform sampler2D diffuse_texture;
+                uniform sampler2D previous_texture;
+                uniform float texture_fade;
                 uniform float mix_factor;
 
                 void main() {
-                    vec3 texture_color = texture(diffuse_texture, v_tex_coords).rgb;
+                    vec3 texture_color = mix(texture(previous_texture, v_tex_coords).rgb, texture(diffuse_texture, v_tex_coords).rgb, texture_fade);
 
                     float gray_levels[4] = float[4](0.2, 0.4, 0.6, 0.8);
 
@@ -57,77 +652,369 @@ impl Shader {
                     vec3 regular_color = mix(gray_vec, texture_color, mix_factor);
 
                     color = vec4(regular_color, 1.0);
+                    color = apply_wireframe(color);
                 }
-            "#,
-        }
-    }
+"#);
 
-    pub fn switch_shading(&mut self, ctx: &mut Ctx) {
-        // println!("{:?}", ctx.shading);
-        if ctx.shading == 0 {
-            self.fragment_shader = r#"
-            #version 330
+// Per light diffuse term only.
+pub const GOURAUD_FRAGMENT_SHADER: &str = concat!("#version 330\n", wireframe_glsl!(), lights_glsl!(), shadow_glsl!(), normal_mapping_glsl!(), r#"
                 in vec3 v_normal;
+                in vec3 v_position;
                 in vec2 v_tex_coords;
+                in vec4 v_tangent;
 
                 out vec4 color;
 
-                uniform vec3 light;
                 uniform sampler2D diffuse_texture;
+                uniform sampler2D previous_texture;
+                uniform float texture_fade;
                 uniform float mix_factor;
 
                 void main() {
-                    float brightness = dot(normalize(v_normal), normalize(light));
+                    vec3 normal = shading_normal(v_normal, v_tangent, v_tex_coords);
+                    vec3 brightness = vec3(0.0);
+                    for (int i = 0; i < light_count; i++) {
+                        vec3 l;
+                        vec3 radiance = light_radiance(lights[i], v_position, l);
+                        brightness += max(dot(normal, l), 0.0) * radiance * shadow_factor(i, normal, l);
+                    }
                     vec3 dark_color = vec3(0.0, 0.05, 0.05);
 
-                    vec3 texture_color = texture(diffuse_texture, v_tex_coords).rgb;
+                    vec3 texture_color = mix(texture(previous_texture, v_tex_coords).rgb, texture(diffuse_texture, v_tex_coords).rgb, texture_fade);
                     vec3 default_color = vec3(0.0, 1.0, 1.0);
                     vec3 regular_color = mix(default_color, texture_color, mix_factor);
 
-                    color = vec4(mix(dark_color, regular_color, brightness), 1.0);
+                    color = vec4(dark_color + (regular_color - dark_color) * brightness, 1.0);
+                    color = apply_wireframe(color);
                 }
-            "#;
-            ctx.shading = 1;
-        } else if ctx.shading == 1 {
-            self.fragment_shader = r#"
-                #version 330
+"#);
+
+pub const BLINN_PHONG_FRAGMENT_SHADER: &str = concat!("#version 330\n", wireframe_glsl!(), lights_glsl!(), shadow_glsl!(), normal_mapping_glsl!(), r#"
                 in vec3 v_normal;
                 in vec3 v_position;
                 in vec2 v_tex_coords;
+                in vec4 v_tangent;
 
                 out vec4 color;
 
-                uniform vec3 light;
                 uniform sampler2D diffuse_texture;
+                uniform sampler2D previous_texture;
+                uniform float texture_fade;
                 uniform float mix_factor;
                 
                 void main() {
 
                     vec3 diffuse_color = vec3(0.0, 0.6, 0.6);
                     vec3 specular_color = vec3(1.0, 1.0, 1.0);
-                    vec3 texture_color = texture(diffuse_texture, v_tex_coords).rgb;
+                    vec3 texture_color = mix(texture(previous_texture, v_tex_coords).rgb, texture(diffuse_texture, v_tex_coords).rgb, texture_fade);
                     
                     vec3 regular_color = mix(diffuse_color, texture_color, mix_factor);
                     vec3 ambient_color = regular_color * 0.3;
-                    
-                    float diffuse = max(dot(v_normal, light), 0.0);
-
-                    vec3 camera_dir = -v_position;
-                    vec3 half_direction = normalize(light + camera_dir);
-                    float specular = pow(max(dot(v_normal, half_direction), 0.0), 16.0);
-                    color = vec4(ambient_color + diffuse * regular_color + specular * specular_color, 1.0);
-                }
-            "#;
-            ctx.shading = 2;
-        } else if ctx.shading == 2 {
-            *self = Shader::new();
-            ctx.shading = 0;
-        }
-    }
-}
 
-impl Default for Shader {
-    fn default() -> Self {
-        Self::new()
-    }
-}
+                    vec3 normal = shading_normal(v_normal, v_tangent, v_tex_coords);
+                    vec3 camera_dir = normalize(-v_position);
+                    vec3 result = ambient_color;
+                    for (int i = 0; i < light_count; i++) {
+                        vec3 l;
+                        vec3 radiance = light_radiance(lights[i], v_position, l);
+                        float diffuse = max(dot(normal, l), 0.0);
+                        vec3 half_direction = normalize(l + camera_dir);
+                        float specular = pow(max(dot(normal, half_direction), 0.0), 16.0);
+                        result += (diffuse * regular_color + specular * specular_color) * radiance * shadow_factor(i, normal, l);
+                    }
+                    color = vec4(result, 1.0);
+                    color = apply_wireframe(color);
+                }
+"#);
+
+// Normal of the triangle from the screen space derivatives of the position, turned toward the camera.
+pub const FLAT_FRAGMENT_SHADER: &str = concat!("#version 330\n", wireframe_glsl!(), lights_glsl!(), shadow_glsl!(), r#"
+                in vec3 v_position;
+                in vec2 v_tex_coords;
+
+                out vec4 color;
+
+                uniform sampler2D diffuse_texture;
+                uniform sampler2D previous_texture;
+                uniform float texture_fade;
+                uniform float mix_factor;
+
+                void main() {
+                    vec3 normal = normalize(cross(dFdx(v_position), dFdy(v_position)));
+                    if (dot(normal, -v_position) < 0.0) {
+                        normal = -normal;
+                    }
+                    vec3 texture_color = mix(texture(previous_texture, v_tex_coords).rgb, texture(diffuse_texture, v_tex_coords).rgb, texture_fade);
+                    vec3 regular_color = mix(vec3(0.0, 0.6, 0.6), texture_color, mix_factor);
+                    vec3 result = regular_color * 0.2;
+                    for (int i = 0; i < light_count; i++) {
+                        vec3 l;
+                        vec3 radiance = light_radiance(lights[i], v_position, l);
+                        result += max(dot(normal, l), 0.0) * regular_color * radiance * shadow_factor(i, normal, l);
+                    }
+                    color = vec4(result, 1.0);
+                    color = apply_wireframe(color);
+                }
+"#);
+
+// Diffuse lighting quantized in toon_bands steps with a hard specular spot.
+pub const TOON_FRAGMENT_SHADER: &str = concat!("#version 330\n", wireframe_glsl!(), lights_glsl!(), shadow_glsl!(), normal_mapping_glsl!(), r#"
+                in vec3 v_normal;
+                in vec3 v_position;
+                in vec2 v_tex_coords;
+                in vec4 v_tangent;
+
+                out vec4 color;
+
+                uniform sampler2D diffuse_texture;
+                uniform sampler2D previous_texture;
+                uniform float texture_fade;
+                uniform float mix_factor;
+                uniform float toon_bands;
+
+                void main() {
+                    vec3 normal = shading_normal(v_normal, v_tangent, v_tex_coords);
+                    vec3 camera_dir = normalize(-v_position);
+                    vec3 texture_color = mix(texture(previous_texture, v_tex_coords).rgb, texture(diffuse_texture, v_tex_coords).rgb, texture_fade);
+                    vec3 regular_color = mix(vec3(0.0, 0.6, 0.6), texture_color, mix_factor);
+                    float bands = max(toon_bands, 1.0);
+                    vec3 result = regular_color * 0.25;
+                    for (int i = 0; i < light_count; i++) {
+                        vec3 l;
+                        vec3 radiance = light_radiance(lights[i], v_position, l);
+                        float lit = max(dot(normal, l), 0.0) * shadow_factor(i, normal, l);
+                        float band = ceil(lit * bands) / bands;
+                        float specular = step(0.95, max(dot(normal, normalize(l + camera_dir)), 0.0)) * step(0.5, lit);
+                        result += (band * regular_color + specular * 0.5) * radiance;
+                    }
+                    color = vec4(result, 1.0);
+                    color = apply_wireframe(color);
+                }
+"#);
+
+// Material capture indexed by the normal seen from the camera, which looks toward +Z with Y up.
+pub const MATCAP_FRAGMENT_SHADER: &str = concat!("#version 330\n", wireframe_glsl!(), normal_mapping_glsl!(), r#"
+                in vec3 v_normal;
+                in vec2 v_tex_coords;
+                in vec4 v_tangent;
+
+                out vec4 color;
+
+                uniform sampler2D matcap_texture;
+
+                void main() {
+                    vec3 normal = shading_normal(v_normal, v_tangent, v_tex_coords);
+                    color = vec4(texture(matcap_texture, normal.xy * 0.495 + 0.5).rgb, 1.0);
+                    color = apply_wireframe(color);
+                }
+"#);
+
+// Hull pushed out along the normals, only the sides facing away from the camera are kept
+// so the model covers the rest and a silhouette line remains.
+pub const OUTLINE_VERTEX_SHADER: &str = r#"
+            #version 330
+                in vec3 position;
+                in vec3 normal;
+
+                out float v_facing;
+
+                uniform mat4 rotation_matrix;
+                uniform mat3 normal_matrix;
+                uniform mat4 perspective_matrix;
+                uniform vec3 object_center;
+                uniform float outline_width;
+
+                void main() {
+                    vec3 extruded = position + normalize(normal) * outline_width;
+                    vec4 rotated_position = rotation_matrix * vec4(extruded - object_center, 1.0);
+                    vec3 final_position = vec3(rotated_position) + object_center;
+                    vec3 rotated_normal = normal_matrix * normal;
+                    v_facing = dot(normalize(rotated_normal), normalize(-final_position));
+                    gl_Position = perspective_matrix * vec4(final_position, 1.0);
+                }
+"#;
+
+pub const OUTLINE_FRAGMENT_SHADER: &str = r#"
+            #version 330
+                in float v_facing;
+
+                out vec4 color;
+
+                uniform vec3 outline_color;
+
+                void main() {
+                    if (v_facing > 0.0) {
+                        discard;
+                    }
+                    color = vec4(outline_color, 1.0);
+                }
+"#;
+
+// Inspection views, used with MAIN_VERTEX_SHADER instead of the shading mode.
+pub const DEBUG_FRAGMENT_SHADER: &str = r#"
+            #version 330
+                #define VIEW_NORMALS 1
+                #define VIEW_UV_CHECKER 2
+                #define VIEW_DEPTH 3
+                #define VIEW_FACE_ID 4
+                #define VIEW_DENSITY 5
+
+                in vec3 v_normal;
+                in vec3 v_position;
+                in vec2 v_tex_coords;
+                in vec3 v_barycentric;
+                flat in int v_id;
+
+                out vec4 color;
+
+                uniform int debug_view;
+                uniform vec2 depth_range;
+
+                vec3 hash_color(int id) {
+                    uint h = uint(id) * 2654435761u;
+                    h ^= h >> 15;
+                    h *= 2246822519u;
+                    h ^= h >> 13;
+                    return vec3(float(h & 255u), float((h >> 8) & 255u), float((h >> 16) & 255u)) / 255.0;
+                }
+
+                // Blue for large triangles to red for the ones under a pixel.
+                vec3 heat(float t) {
+                    t = clamp(t, 0.0, 1.0);
+                    return clamp(vec3(1.5 - abs(4.0 * t - 3.0), 1.5 - abs(4.0 * t - 2.0), 1.5 - abs(4.0 * t - 1.0)), 0.0, 1.0);
+                }
+
+                void main() {
+                    if (debug_view == VIEW_NORMALS) {
+                        color = vec4(normalize(v_normal) * 0.5 + 0.5, 1.0);
+                    } else if (debug_view == VIEW_UV_CHECKER) {
+                        vec2 cell = floor(v_tex_coords * 8.0);
+                        float checker = mod(cell.x + cell.y, 2.0);
+                        vec3 tint = vec3(fract(v_tex_coords), 0.5);
+                        color = vec4(mix(tint * 0.35, tint, checker), 1.0);
+                    } else if (debug_view == VIEW_DEPTH) {
+                        float depth = (v_position.z - depth_range.x) / max(depth_range.y - depth_range.x, 0.0001);
+                        color = vec4(vec3(1.0 - clamp(depth, 0.0, 1.0)), 1.0);
+                    } else if (debug_view == VIEW_FACE_ID) {
+                        color = vec4(hash_color(v_id), 1.0);
+                    } else if (debug_view == VIEW_DENSITY) {
+                        // Screen area of the triangle from how fast the barycentric coordinates change.
+                        vec2 dx = dFdx(v_barycentric.xy);
+                        vec2 dy = dFdy(v_barycentric.xy);
+                        float area = 0.5 / max(abs(dx.x * dy.y - dx.y * dy.x), 1e-8);
+                        color = vec4(heat(1.0 - log2(max(area, 0.25)) / 12.0), 1.0);
+                    } else {
+                        color = vec4(1.0, 0.0, 1.0, 1.0);
+                    }
+                }
+"#;
+
+// Quad of the ground grid built from gl_VertexID, centered under the object.
+pub const GRID_VERTEX_SHADER: &str = r#"
+            #version 330
+                out vec3 v_world;
+
+                uniform mat4 perspective_matrix;
+                uniform vec3 grid_center;
+                uniform float grid_extent;
+
+                const vec2 corners[6] = vec2[6](
+                    vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+                    vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+                );
+
+                void main() {
+                    vec2 corner = corners[gl_VertexID] * grid_extent;
+                    v_world = grid_center + vec3(corner.x, 0.0, corner.y);
+                    gl_Position = perspective_matrix * vec4(v_world, 1.0);
+                }
+"#;
+
+// Minor lines every grid_spacing, major ones every ten, fading out with the distance.
+pub const GRID_FRAGMENT_SHADER: &str = r#"
+            #version 330
+                in vec3 v_world;
+
+                out vec4 color;
+
+                uniform vec3 grid_center;
+                uniform float grid_extent;
+                uniform float grid_spacing;
+
+                // Lines closer than a few pixels fade out instead of turning into moire.
+                float grid_line(vec2 coord) {
+                    vec2 width = fwidth(coord);
+                    vec2 distance_to_line = abs(fract(coord - 0.5) - 0.5) / width;
+                    float line = 1.0 - min(min(distance_to_line.x, distance_to_line.y), 1.0);
+                    return line * (1.0 - smoothstep(0.1, 0.3, max(width.x, width.y)));
+                }
+
+                void main() {
+                    vec2 coord = v_world.xz / grid_spacing;
+                    float minor = grid_line(coord) * 0.35;
+                    float major = grid_line(coord / 10.0) * 0.7;
+                    vec2 axis = abs(v_world.xz) / fwidth(v_world.xz);
+                    float fade = 1.0 - smoothstep(0.2, 1.0, length(v_world.xz - grid_center.xz) / grid_extent);
+                    vec3 rgb = vec3(0.6);
+                    float alpha = max(minor, major);
+                    if (axis.y < 1.0) {
+                        rgb = vec3(0.9, 0.2, 0.2);
+                        alpha = 1.0;
+                    } else if (axis.x < 1.0) {
+                        rgb = vec3(0.2, 0.4, 0.9);
+                        alpha = 1.0;
+                    }
+                    alpha *= fade;
+                    if (alpha < 0.01) {
+                        discard;
+                    }
+                    color = vec4(rgb, alpha);
+                }
+"#;
+
+// The 12 edges of the box between bbox_min and bbox_max, placed like the model.
+pub const BBOX_VERTEX_SHADER: &str = r#"
+            #version 330
+                out vec3 v_color;
+
+                uniform mat4 rotation_matrix;
+                uniform mat4 perspective_matrix;
+                uniform vec3 object_center;
+                uniform vec3 bbox_min;
+                uniform vec3 bbox_max;
+                uniform vec3 bbox_color;
+
+                const int edges[24] = int[24](
+                    0, 1, 1, 3, 3, 2, 2, 0,
+                    4, 5, 5, 7, 7, 6, 6, 4,
+                    0, 4, 1, 5, 2, 6, 3, 7
+                );
+
+                void main() {
+                    int corner = edges[gl_VertexID];
+                    vec3 position = vec3(
+                        (corner & 1) == 0 ? bbox_min.x : bbox_max.x,
+                        (corner & 2) == 0 ? bbox_min.y : bbox_max.y,
+                        (corner & 4) == 0 ? bbox_min.z : bbox_max.z
+                    );
+                    v_color = bbox_color;
+                    vec4 rotated_position = rotation_matrix * vec4(position - object_center, 1.0);
+                    gl_Position = perspective_matrix * vec4(vec3(rotated_position) + object_center, 1.0);
+                }
+"#;
+
+// World axes seen with the camera orientation, drawn in a corner viewport.
+pub const AXIS_VERTEX_SHADER: &str = r#"
+            #version 330
+                out vec3 v_color;
+
+                uniform mat3 view_rotation;
+
+                void main() {
+                    int axis = gl_VertexID / 2;
+                    vec3 direction = vec3(axis == 0 ? 1.0 : 0.0, axis == 1 ? 1.0 : 0.0, axis == 2 ? 1.0 : 0.0);
+                    vec3 position = (gl_VertexID % 2 == 0) ? vec3(0.0) : view_rotation * direction;
+                    v_color = direction;
+                    gl_Position = vec4(position.xy * 0.8, position.z * 0.5, 1.0);
+                }
+"#;
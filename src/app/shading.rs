@@ -0,0 +1,345 @@
+use std::{
+    fs::read_to_string,
+    path::{Path, PathBuf}
+};
+
+use glium::uniforms::{UniformType, UniformValue, Uniforms};
+
+use super::{
+    hot_reload::SHADER_DIR,
+    shaders::{
+        BLINN_PHONG_FRAGMENT_SHADER,
+        FLAT_FRAGMENT_SHADER,
+        GOURAUD_FRAGMENT_SHADER,
+        GRAY_FRAGMENT_SHADER,
+        MAIN_VERTEX_SHADER,
+        MATCAP_FRAGMENT_SHADER,
+        PBR_FRAGMENT_SHADER,
+        TOON_FRAGMENT_SHADER
+    }
+};
+
+pub const MODES_FILE: &str = "modes.conf";
+
+// Uniforms the renderer gives to every shading mode with their type, `lights` is the light array of LIGHT_FIELDS.
+pub const RENDERER_UNIFORMS: [(&str, UniformType); 38] = [
+    ("rotation_matrix", UniformType::FloatMat4), ("perspective_matrix", UniformType::FloatMat4),
+    ("light_matrix", UniformType::FloatMat4), ("object_center", UniformType::FloatVec3),
+    ("shadow_map", UniformType::Sampler2d), ("shadow_light", UniformType::Int), ("shadow_bias", UniformType::Float),
+    ("diffuse_texture", UniformType::Sampler2d), ("previous_texture", UniformType::Sampler2d),
+    ("texture_fade", UniformType::Float), ("mix_factor", UniformType::Float),
+    ("base_color", UniformType::FloatVec3), ("metallic", UniformType::Float), ("roughness", UniformType::Float),
+    ("has_base_color_map", UniformType::Bool), ("base_color_map", UniformType::Sampler2d),
+    ("metallic_map", UniformType::Sampler2d), ("roughness_map", UniformType::Sampler2d),
+    ("occlusion_map", UniformType::Sampler2d), ("has_normal_map", UniformType::Bool), ("normal_map", UniformType::Sampler2d),
+    ("has_environment", UniformType::Bool), ("irradiance_map", UniformType::SamplerCube),
+    ("prefiltered_map", UniformType::SamplerCube), ("brdf_lut", UniformType::Sampler2d),
+    ("prefiltered_levels", UniformType::Float), ("environment_rotation", UniformType::FloatMat3),
+    ("exposure", UniformType::Float), ("light_count", UniformType::Int),
+    ("toon_bands", UniformType::Float), ("matcap_texture", UniformType::Sampler2d),
+    ("wireframe", UniformType::Bool), ("wireframe_polygons", UniformType::Bool),
+    ("wireframe_width", UniformType::Float), ("wireframe_color", UniformType::FloatVec3),
+    ("normal_matrix", UniformType::FloatMat3), ("debug_view", UniformType::Int),
+    ("depth_range", UniformType::FloatVec2)
+];
+
+// Fields of each element of the `lights` array.
+const LIGHT_FIELDS: [(&str, UniformType); 8] = [
+    ("kind", UniformType::Int), ("position", UniformType::FloatVec3), ("direction", UniformType::FloatVec3),
+    ("color", UniformType::FloatVec3), ("intensity", UniformType::Float), ("range", UniformType::Float),
+    ("inner_cone", UniformType::Float), ("outer_cone", UniformType::Float)
+];
+
+// Type the renderer sends for a uniform of the program, `lights[i].field` included.
+pub fn renderer_uniform_type(name: &str) -> Option<UniformType> {
+    let (table, key): (&[(&str, UniformType)], &str) = match name.strip_prefix("lights[") {
+        Some(element) => (&LIGHT_FIELDS, element.split_once("].").map_or("", |(_, field)| field)),
+        None => (&RENDERER_UNIFORMS, name)
+    };
+    table.iter().find(|(other, _)| *other == key).map(|(_, ty)| *ty)
+}
+
+fn is_renderer_uniform(name: &str) -> bool {
+    name == "lights" || RENDERER_UNIFORMS.iter().any(|(other, _)| *other == name)
+}
+
+// Used by the built-in vertex shader, given to the modes keeping it.
+const MAIN_VERTEX_UNIFORMS: [&str; 5] = ["rotation_matrix", "normal_matrix", "perspective_matrix", "light_matrix", "object_center"];
+
+#[derive(Clone, Debug)]
+pub enum ShaderSource {
+    Builtin(&'static str),
+    File(PathBuf),
+}
+
+#[derive(Clone, Debug)]
+pub struct ShadingMode {
+    pub name: String,
+    pub vertex: ShaderSource,
+    pub fragment: ShaderSource,
+    // Renderer uniforms declared by the modes of the config file, None for the built-in ones.
+    pub uniforms: Option<Vec<String>>,
+    // Values set in the config file, sent as float, vec2, vec3 or vec4.
+    pub constants: Vec<(String, Vec<f32>)>,
+    // Draws the silhouette outline after the model.
+    pub outline: bool,
+}
+
+impl ShadingMode {
+    pub fn builtin(name: &str, vertex: &'static str, fragment: &'static str) -> Self {
+        Self {
+            name: name.to_string(),
+            vertex: ShaderSource::Builtin(vertex),
+            fragment: ShaderSource::Builtin(fragment),
+            uniforms: None,
+            constants: Vec::new(),
+            outline: false,
+        }
+    }
+
+    // Whether the program may use a uniform, array elements and struct fields count as their base name.
+    pub fn provides(&self, uniform: &str) -> bool {
+        let base = uniform.split(['[', '.']).next().unwrap_or(uniform);
+        match &self.uniforms {
+            Some(uniforms) => uniforms.iter().any(|name| name == base)
+                || self.constants.iter().any(|(name, _)| name == base)
+                || (matches!(self.vertex, ShaderSource::Builtin(_)) && MAIN_VERTEX_UNIFORMS.contains(&base)),
+            None => true
+        }
+    }
+
+    // Type the program receives for a uniform, constants are typed by their number of values.
+    pub fn uniform_type(&self, uniform: &str) -> Option<UniformType> {
+        match self.constants.iter().find(|(name, _)| name == uniform) {
+            Some((_, values)) => match values.len() {
+                1 => Some(UniformType::Float),
+                2 => Some(UniformType::FloatVec2),
+                3 => Some(UniformType::FloatVec3),
+                _ => Some(UniformType::FloatVec4)
+            },
+            None => renderer_uniform_type(uniform)
+        }
+    }
+}
+
+pub fn builtin_modes() -> Vec<ShadingMode> {
+    vec![
+        ShadingMode::builtin("gray", MAIN_VERTEX_SHADER, GRAY_FRAGMENT_SHADER),
+        ShadingMode::builtin("flat", MAIN_VERTEX_SHADER, FLAT_FRAGMENT_SHADER),
+        ShadingMode::builtin("gouraud", MAIN_VERTEX_SHADER, GOURAUD_FRAGMENT_SHADER),
+        ShadingMode::builtin("blinn_phong", MAIN_VERTEX_SHADER, BLINN_PHONG_FRAGMENT_SHADER),
+        ShadingMode {
+            outline: true,
+            ..ShadingMode::builtin("toon", MAIN_VERTEX_SHADER, TOON_FRAGMENT_SHADER)
+        },
+        ShadingMode::builtin("matcap", MAIN_VERTEX_SHADER, MATCAP_FRAGMENT_SHADER),
+        ShadingMode::builtin("pbr", MAIN_VERTEX_SHADER, PBR_FRAGMENT_SHADER),
+    ]
+}
+
+// Modes of a config file like:
+//   mode heat
+//   vertex heat.vert          (optional, the built-in vertex shader otherwise)
+//   fragment heat.frag
+//   uniform rotation_matrix   (a renderer uniform used by the shaders)
+//   uniform tint 1.0 0.5 0.2  (a constant of 1 to 4 floats)
+//   outline                   (draws the silhouette outline)
+// Paths are relative to the config file.
+pub fn modes_parser(filepath: &Path) -> Result<Vec<ShadingMode>, String> {
+    let content = read_to_string(filepath).map_err(|e| format!("Error: Cannot open {}. {}", filepath.display(), e))?;
+    let parent = filepath.parent().unwrap_or(Path::new("."));
+    let mut modes: Vec<ShadingMode> = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let splited: Vec<&str> = line.split_whitespace().collect();
+        if splited[0] == "mode" {
+            if splited.len() != 2 {
+                return Err(format!("Error: Invalid format : {}.", line));
+            }
+            if modes.iter().any(|mode| mode.name == splited[1]) {
+                return Err(format!("Error: Mode {} is declared twice.", splited[1]));
+            }
+            modes.push(ShadingMode {
+                name: splited[1].to_string(),
+                vertex: ShaderSource::Builtin(MAIN_VERTEX_SHADER),
+                fragment: ShaderSource::Builtin(GRAY_FRAGMENT_SHADER),
+                uniforms: Some(Vec::new()),
+                constants: Vec::new(),
+                outline: false,
+            });
+            continue;
+        }
+        let mode = match modes.last_mut() {
+            Some(mode) => mode,
+            None => return Err(format!("Error: {} is set before any mode.", splited[0]))
+        };
+        match (splited[0], splited.len()) {
+            ("outline", 1) => mode.outline = true,
+            ("vertex", 2) => mode.vertex = ShaderSource::File(parent.join(splited[1])),
+            ("fragment", 2) => mode.fragment = ShaderSource::File(parent.join(splited[1])),
+            ("uniform", 2) => {
+                if !is_renderer_uniform(splited[1]) {
+                    return Err(format!("Error: {} is not a renderer uniform, give it a value.", splited[1]));
+                }
+                mode.uniforms.get_or_insert_with(Vec::new).push(splited[1].to_string());
+            },
+            ("uniform", 3..=6) => {
+                if is_renderer_uniform(splited[1]) {
+                    return Err(format!("Error: {} is a renderer uniform, it cannot be set.", splited[1]));
+                }
+                let values = splited[2..].iter()
+                    .map(|s| s.parse::<f32>())
+                    .collect::<Result<Vec<f32>, _>>()
+                    .map_err(|_| format!("Error: Invalid value {}, values must be f32.", line))?;
+                mode.constants.push((splited[1].to_string(), values));
+            },
+            _ => return Err(format!("Error: Invalid format : {}.", line)),
+        }
+    }
+    if let Some(mode) = modes.iter().find(|mode| matches!(mode.fragment, ShaderSource::Builtin(_))) {
+        return Err(format!("Error: Mode {} has no fragment shader.", mode.name));
+    }
+    Ok(modes)
+}
+
+// Built-in modes followed by the ones of the config file, cycled in this order.
+pub struct ShadingRegistry {
+    pub modes: Vec<ShadingMode>,
+    pub current: usize,
+    pub config: PathBuf,
+}
+
+impl ShadingRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self {
+            modes: builtin_modes(),
+            current: 0,
+            config: Path::new(SHADER_DIR).join(MODES_FILE),
+        };
+        registry.reload_config();
+        registry
+    }
+
+    // Reads the config file again, the current mode is kept when it still exists.
+    pub fn reload_config(&mut self) {
+        let name = self.current().name.clone();
+        self.modes = builtin_modes();
+        if self.config.exists() {
+            match modes_parser(&self.config) {
+                Ok(modes) => {
+                    for mode in modes {
+                        match self.modes.iter_mut().find(|other| other.name == mode.name) {
+                            Some(other) => *other = mode,
+                            None => self.modes.push(mode)
+                        }
+                    }
+                },
+                Err(err) => println!("Warning: {} ignored. {}", self.config.display(), err)
+            }
+        }
+        self.current = self.modes.iter().position(|mode| mode.name == name).unwrap_or(0);
+    }
+
+    pub fn current(&self) -> &ShadingMode {
+        &self.modes[self.current.min(self.modes.len() - 1)]
+    }
+
+    pub fn next(&mut self) -> &ShadingMode {
+        self.current = (self.current + 1) % self.modes.len();
+        self.current()
+    }
+
+    pub fn select(&mut self, name: &str) -> Result<&ShadingMode, String> {
+        match self.modes.iter().position(|mode| mode.name == name) {
+            Some(index) => {
+                self.current = index;
+                Ok(self.current())
+            },
+            None => Err(format!("Error: Unknown shading mode {}, available: {}.", name, self.names().join(", ")))
+        }
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.modes.iter().map(|mode| mode.name.as_str()).collect()
+    }
+}
+
+impl Default for ShadingRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Adds the constants of the shading mode to a set of uniforms.
+pub struct ModeUniforms<'m, U: Uniforms> {
+    pub uniforms: U,
+    pub mode: &'m ShadingMode,
+}
+
+impl<U: Uniforms> Uniforms for ModeUniforms<'_, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        self.uniforms.visit_values(&mut output);
+        for (name, values) in &self.mode.constants {
+            let value = match values[..] {
+                [x] => UniformValue::Float(x),
+                [x, y] => UniformValue::Vec2([x, y]),
+                [x, y, z] => UniformValue::Vec3([x, y, z]),
+                [x, y, z, w] => UniformValue::Vec4([x, y, z, w]),
+                _ => continue
+            };
+            output(name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renderer_uniforms_have_their_type() {
+        assert_eq!(renderer_uniform_type("exposure"), Some(UniformType::Float));
+        assert_eq!(renderer_uniform_type("lights[3].color"), Some(UniformType::FloatVec3));
+        assert_eq!(renderer_uniform_type("lights[3].tint"), None);
+        assert_eq!(renderer_uniform_type("tint"), None);
+    }
+
+    fn parse(name: &str, content: &str) -> Result<Vec<ShadingMode>, String> {
+        let filepath = std::env::temp_dir().join(format!("{}.conf", name));
+        std::fs::write(&filepath, content).unwrap();
+        modes_parser(&filepath)
+    }
+
+    #[test]
+    fn constants_are_typed_by_their_values() {
+        let modes = parse("scop_constants", "mode heat\nfragment heat.frag\nuniform mix_factor\nuniform tint 1.0 0.5 0.2\n").unwrap();
+        assert_eq!(modes[0].uniform_type("tint"), Some(UniformType::FloatVec3));
+        assert_eq!(modes[0].uniform_type("mix_factor"), Some(UniformType::Float));
+        assert_eq!(modes[0].uniform_type("glow"), None);
+    }
+
+    #[test]
+    fn invalid_modes_are_rejected() {
+        let unknown = parse("scop_unknown", "mode heat\nfragment heat.frag\nuniform tint\n").unwrap_err();
+        assert_eq!(unknown, "Error: tint is not a renderer uniform, give it a value.");
+        let renderer = parse("scop_renderer", "mode heat\nfragment heat.frag\nuniform exposure 2.0\n").unwrap_err();
+        assert_eq!(renderer, "Error: exposure is a renderer uniform, it cannot be set.");
+        let arity = parse("scop_arity", "mode heat\nfragment heat.frag\nuniform tint 1 2 3 4 5\n").unwrap_err();
+        assert_eq!(arity, "Error: Invalid format : uniform tint 1 2 3 4 5.");
+        let twice = parse("scop_twice", "mode heat\nfragment a.frag\nmode heat\nfragment b.frag\n").unwrap_err();
+        assert_eq!(twice, "Error: Mode heat is declared twice.");
+    }
+
+    #[test]
+    fn config_modes_replace_the_builtin_ones() {
+        let mut registry = ShadingRegistry::new();
+        registry.config = std::env::temp_dir().join("scop_registry.conf");
+        std::fs::write(&registry.config, "mode pbr\nfragment pbr.frag\nmode heat\nfragment heat.frag\n").unwrap();
+        registry.reload_config();
+        assert_eq!(registry.names(), ["gray", "flat", "gouraud", "blinn_phong", "toon", "matcap", "pbr", "heat"]);
+        assert!(matches!(registry.select("pbr").unwrap().fragment, ShaderSource::File(_)));
+        assert!(registry.select("glow").is_err());
+    }
+}
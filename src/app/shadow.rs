@@ -0,0 +1,146 @@
+use glium::{
+    backend::Facade,
+    framebuffer::SimpleFrameBuffer,
+    texture::{DepthFormat, DepthTexture2d, MipmapsOption},
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction},
+    Program,
+    Surface,
+    VertexBuffer,
+    uniform
+};
+
+use super::{
+    ctx::Ctx,
+    light::{Light, LightKind},
+    matrix::Matrix,
+    mesh::{DrawRange, Mesh},
+    scene::SceneObject,
+    shaders::{SHADOW_FRAGMENT_SHADER, SHADOW_VERTEX_SHADER}
+};
+
+pub const RESOLUTIONS: [u32; 4] = [512, 1024, 2048, 4096];
+
+// Depth of the scene seen from the shadow casting light.
+pub struct ShadowMap {
+    pub depth: DepthTexture2d,
+    pub resolution: u32,
+    program: Program,
+}
+
+impl ShadowMap {
+    pub fn new(display: &dyn Facade, resolution: u32) -> Self {
+        Self {
+            depth: Self::create_texture(display, resolution),
+            resolution,
+            program: Program::from_source(display, SHADOW_VERTEX_SHADER, SHADOW_FRAGMENT_SHADER, None)
+                .expect("Error: \"glium::Program::from_source\" Fail"),
+        }
+    }
+
+    fn create_texture(display: &dyn Facade, resolution: u32) -> DepthTexture2d {
+        DepthTexture2d::empty_with_format(display, DepthFormat::I24, MipmapsOption::NoMipmap, resolution, resolution)
+            .expect("Error: Failed to create the shadow map")
+    }
+
+    pub fn resize(&mut self, display: &dyn Facade, resolution: u32) {
+        if self.resolution != resolution {
+            self.depth = Self::create_texture(display, resolution);
+            self.resolution = resolution;
+        }
+    }
+
+    pub fn sampler(&self) -> Sampler<'_, DepthTexture2d> {
+        self.depth.sampled()
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .minify_filter(MinifySamplerFilter::Nearest)
+            .wrap_function(SamplerWrapFunction::Clamp)
+    }
+
+    // Directional lights use an orthographic projection covering the ground,
+    // point and spot lights a perspective one aimed at the object.
+    pub fn light_matrix(light: &Light, center: [f32; 3], radius: f32) -> [[f32; 4]; 4] {
+        let radius = radius.max(0.01);
+        let up = [0.0, 1.0, 0.0];
+        match light.kind {
+            LightKind::Directional => {
+                let d = light.direction;
+                let distance = radius * 4.0;
+                let eye = [
+                    center[0] - d[0] * distance,
+                    center[1] - d[1] * distance,
+                    center[2] - d[2] * distance
+                ];
+                let view = Matrix::new_look_at(eye, center, up);
+                Matrix::new_orthographic(radius * GROUND_SCALE, 0.0, distance * 2.0)
+                    .multiply(&view)
+                    .get_4x4_matrix()
+            },
+            LightKind::Point | LightKind::Spot => {
+                let p = light.position;
+                let distance = ((center[0] - p[0]).powi(2) + (center[1] - p[1]).powi(2) + (center[2] - p[2]).powi(2)).sqrt();
+                let fov = if light.kind == LightKind::Spot {
+                    light.outer_cone * 2.0
+                } else {
+                    2.0 * (radius / distance.max(radius * 1.01)).asin()
+                };
+                let view = Matrix::new_look_at(p, center, up);
+                Matrix::new_perspective_fov(fov.clamp(0.1, 3.0), 1.0, 0.05, distance + radius * GROUND_SCALE * 2.0)
+                    .multiply(&view)
+                    .get_4x4_matrix()
+            }
+        }
+    }
+
+    // Every shown node of the objects of the scene casts a shadow.
+    pub fn render(&self, display: &dyn Facade, casters: &[(&VertexBuffer<Mesh>, &[DrawRange], &SceneObject)], light_matrix: [[f32; 4]; 4]) {
+        let mut target = SimpleFrameBuffer::depth_only(display, &self.depth)
+            .expect("Error: Failed to bind the shadow map");
+        target.clear_depth(1.0);
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLess,
+                write: true,
+                .. Default::default()
+            },
+            .. Default::default()
+        };
+        let ranges = casters.iter().flat_map(|(vertex_buffer, ranges, object)| {
+            ranges.iter().filter(|range| object.is_shown(range.node)).map(move |range| (vertex_buffer, range, object))
+        });
+        for (vertex_buffer, range, object) in ranges {
+            let uniforms = uniform! {
+                rotation_matrix: object.node_matrix(range.node),
+                object_center: object.obj.centroid,
+                light_matrix: light_matrix
+            };
+            target.draw(
+                vertex_buffer.slice(range.vertices.clone()).unwrap(),
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &self.program,
+                &uniforms,
+                &params
+            ).unwrap();
+        }
+    }
+}
+
+// Ground size relative to the scene radius.
+pub const GROUND_SCALE: f32 = 3.0;
+
+// Horizontal quad under the scene, at its lowest point.
+pub fn ground_mesh(ctx: &Ctx) -> Vec<Mesh> {
+    let center = ctx.scene.center();
+    let half = ctx.scene.radius() * GROUND_SCALE;
+    let y = ctx.scene.min_y();
+    let normal = [0.0, 1.0, 0.0];
+    let corners = [
+        [center[0] - half, y, center[2] - half],
+        [center[0] + half, y, center[2] - half],
+        [center[0] + half, y, center[2] + half],
+        [center[0] - half, y, center[2] + half]
+    ];
+    let uv = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+    [0, 1, 2, 0, 2, 3].iter()
+        .map(|&i| Mesh::new(corners[i], normal, uv[i], 0))
+        .collect()
+}
@@ -0,0 +1,696 @@
+use glium::texture::RawImage2d;
+
+use super::{
+    backend::PipelineState,
+    ctx::Ctx,
+    image::Image,
+    light::{Light, LightKind, MAX_LIGHTS},
+    matrix::Matrix,
+    mesh::{DrawRange, Mesh},
+    parser::ppm_parser,
+    scene::SceneObject
+};
+
+// Shading modes the software renderer can draw, ported from their fragment shaders.
+pub const SOFTWARE_SHADINGS: [&str; 6] = ["gray", "flat", "gouraud", "blinn_phong", "toon", "matcap"];
+
+// Clipping keeps w above this, the camera never sees a vertex behind it.
+const MIN_W: f32 = 1e-5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SoftwareShading {
+    Gray,
+    Flat,
+    Gouraud,
+    BlinnPhong,
+    Toon,
+    Matcap,
+}
+
+impl SoftwareShading {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "gray" => Ok(SoftwareShading::Gray),
+            "flat" => Ok(SoftwareShading::Flat),
+            "gouraud" => Ok(SoftwareShading::Gouraud),
+            "blinn_phong" => Ok(SoftwareShading::BlinnPhong),
+            "toon" => Ok(SoftwareShading::Toon),
+            "matcap" => Ok(SoftwareShading::Matcap),
+            _ => Err(format!("Error: The software renderer has no {} shading, available: {}.", name, SOFTWARE_SHADINGS.join(", ")))
+        }
+    }
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn mul(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] * b[0], a[1] * b[1], a[2] * b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let len = dot(a, a).sqrt();
+    if len > 0.0 { scale(a, 1.0 / len) } else { a }
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    add(scale(a, 1.0 - t), scale(b, t))
+}
+
+fn step(edge: f32, x: f32) -> f32 {
+    if x < edge { 0.0 } else { 1.0 }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// Column major like the uniforms given to the shaders.
+fn transform(m: &[[f32; 4]; 4], v: [f32; 4]) -> [f32; 4] {
+    let mut ret = [0.0; 4];
+    for (row, value) in ret.iter_mut().enumerate() {
+        *value = (0..4).map(|col| m[col][row] * v[col]).sum();
+    }
+    ret
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+// RGB texture sampled like the default glium sampler: mirrored repeat and bilinear filtering.
+pub struct CpuTexture {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    // Bottom row first, like the OpenGL textures.
+    texels: Vec<[f32; 3]>,
+}
+
+impl CpuTexture {
+    pub fn load(filepath: &str) -> Result<Self, String> {
+        let (img, dim) = ppm_parser(filepath)?;
+        Ok(Self::from_raw(filepath, img, dim))
+    }
+
+    // Image already read by ppm_parser.
+    pub fn from_raw(filepath: &str, img: RawImage2d<'static, u8>, (width, height): (u32, u32)) -> Self {
+        let texels = img.data.chunks_exact(3)
+            .map(|rgb| [rgb[0] as f32 / 255.0, rgb[1] as f32 / 255.0, rgb[2] as f32 / 255.0])
+            .collect();
+        Self { path: filepath.to_string(), width, height, texels }
+    }
+
+    fn texel(&self, x: i64, y: i64) -> [f32; 3] {
+        let mirror = |i: i64, size: i64| {
+            let period = i.rem_euclid(2 * size);
+            if period < size { period } else { 2 * size - 1 - period }
+        };
+        let x = mirror(x, self.width as i64);
+        let y = mirror(y, self.height as i64);
+        self.texels[(y * self.width as i64 + x) as usize]
+    }
+
+    pub fn sample(&self, uv: [f32; 2]) -> [f32; 3] {
+        let x = uv[0] * self.width as f32 - 0.5;
+        let y = uv[1] * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let bottom = mix(self.texel(x0, y0), self.texel(x0 + 1, y0), fx);
+        let top = mix(self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1), fx);
+        mix(bottom, top, fy)
+    }
+}
+
+// Values interpolated over a triangle, the outputs of the vertex shader.
+#[derive(Copy, Clone, Debug)]
+struct Varyings {
+    position: [f32; 3],
+    normal: [f32; 3],
+    tex_coords: [f32; 2],
+    // Outline pass only, below zero where the hull faces away from the camera.
+    facing: f32,
+}
+
+impl Varyings {
+    fn weighted(values: [&Varyings; 3], weights: [f32; 3]) -> Self {
+        let mut ret = Varyings { position: [0.0; 3], normal: [0.0; 3], tex_coords: [0.0; 2], facing: 0.0 };
+        for (value, weight) in values.iter().zip(weights) {
+            ret.position = add(ret.position, scale(value.position, weight));
+            ret.normal = add(ret.normal, scale(value.normal, weight));
+            ret.tex_coords[0] += value.tex_coords[0] * weight;
+            ret.tex_coords[1] += value.tex_coords[1] * weight;
+            ret.facing += value.facing * weight;
+        }
+        ret
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct ClipVertex {
+    clip: [f32; 4],
+    varyings: Varyings,
+}
+
+impl ClipVertex {
+    fn lerp(&self, other: &ClipVertex, t: f32) -> Self {
+        let mut clip = [0.0; 4];
+        for (i, value) in clip.iter_mut().enumerate() {
+            *value = self.clip[i] + (other.clip[i] - self.clip[i]) * t;
+        }
+        ClipVertex {
+            clip,
+            varyings: Varyings::weighted([&self.varyings, &other.varyings, &other.varyings], [1.0 - t, t, 0.0]),
+        }
+    }
+}
+
+// Vertex after the perspective divide, in pixels from the top left corner.
+#[derive(Copy, Clone, Debug)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    depth: f32,
+    inv_w: f32,
+    varyings: Varyings,
+}
+
+// Sutherland-Hodgman against the near and far planes, the screen edges are handled by the raster loops.
+fn clip_polygon(polygon: Vec<ClipVertex>) -> Vec<ClipVertex> {
+    let planes: [fn(&[f32; 4]) -> f32; 3] = [
+        |c| c[2] + c[3],
+        |c| c[3] - c[2],
+        |c| c[3] - MIN_W,
+    ];
+    let mut output = polygon;
+    for plane in planes {
+        let input = std::mem::take(&mut output);
+        for (i, current) in input.iter().enumerate() {
+            let next = &input[(i + 1) % input.len()];
+            let (d0, d1) = (plane(&current.clip), plane(&next.clip));
+            if d0 >= 0.0 {
+                output.push(*current);
+            }
+            if (d0 >= 0.0) != (d1 >= 0.0) {
+                output.push(current.lerp(next, d0 / (d0 - d1)));
+            }
+        }
+        if output.len() < 3 {
+            return Vec::new();
+        }
+    }
+    output
+}
+
+// Lights as the shaders receive them: enabled ones only, cones as cosines.
+struct ShadingLight {
+    kind: LightKind,
+    position: [f32; 3],
+    direction: [f32; 3],
+    color: [f32; 3],
+    intensity: f32,
+    range: f32,
+    inner_cone: f32,
+    outer_cone: f32,
+}
+
+impl ShadingLight {
+    fn new(light: &Light) -> Self {
+        Self {
+            kind: light.kind,
+            position: light.position,
+            direction: light.direction,
+            color: light.color,
+            intensity: light.intensity,
+            range: light.range,
+            inner_cone: light.inner_cone.cos(),
+            outer_cone: light.outer_cone.cos(),
+        }
+    }
+
+    // Same as light_radiance in the shaders, returns the radiance and the direction toward the light.
+    fn radiance(&self, position: [f32; 3]) -> ([f32; 3], [f32; 3]) {
+        if self.kind == LightKind::Directional {
+            return (scale(self.color, self.intensity), scale(normalize(self.direction), -1.0));
+        }
+        let to_light = sub(self.position, position);
+        let dist = dot(to_light, to_light).sqrt();
+        let l = scale(to_light, 1.0 / dist.max(0.0001));
+        let window = (1.0 - (dist / self.range).powi(4)).clamp(0.0, 1.0);
+        let mut attenuation = window * window;
+        if self.kind == LightKind::Spot {
+            attenuation *= smoothstep(self.outer_cone, self.inner_cone, dot(scale(l, -1.0), normalize(self.direction)));
+        }
+        (scale(self.color, self.intensity * attenuation), l)
+    }
+}
+
+// Everything the fragment stage reads, the uniforms of the shaders.
+struct Pipeline<'a> {
+    rotation_matrix: [[f32; 4]; 4],
+    normal_matrix: [[f32; 3]; 3],
+    perspective_matrix: [[f32; 4]; 4],
+    object_center: [f32; 3],
+    lights: Vec<ShadingLight>,
+    mix_factor: f32,
+    toon_bands: f32,
+    shading: SoftwareShading,
+    texture: Option<&'a CpuTexture>,
+    matcap: Option<&'a CpuTexture>,
+    backface_culling: bool,
+    polygon_mode: i32,
+    // Distance the hull of the outline pass is pushed out, None for the model pass.
+    outline_width: Option<f32>,
+}
+
+impl Pipeline<'_> {
+    fn vertex(&self, vertex: &Mesh) -> ClipVertex {
+        let c = self.object_center;
+        let n = vertex.normal;
+        let p = match self.outline_width {
+            Some(width) => sub(add(vertex.position(), scale(normalize(n), width)), c),
+            None => sub(vertex.position(), c)
+        };
+        let rotated = transform(&self.rotation_matrix, [p[0], p[1], p[2], 1.0]);
+        let position = add([rotated[0], rotated[1], rotated[2]], c);
+        let m = self.normal_matrix;
+        let normal = normalize([0, 1, 2].map(|i| m[0][i] * n[0] + m[1][i] * n[1] + m[2][i] * n[2]));
+        ClipVertex {
+            clip: transform(&self.perspective_matrix, [position[0], position[1], position[2], 1.0]),
+            varyings: Varyings {
+                position,
+                normal,
+                tex_coords: vertex.tex_coords,
+                facing: dot(normal, normalize(scale(position, -1.0))),
+            },
+        }
+    }
+
+    // None when the fragment is discarded.
+    fn fragment(&self, varyings: &Varyings, id: i32) -> Option<[f32; 3]> {
+        if self.outline_width.is_some() {
+            return (varyings.facing <= 0.0).then_some([0.02; 3]);
+        }
+        let texture_color = self.texture.map(|texture| texture.sample(varyings.tex_coords));
+        let mix_factor = if texture_color.is_some() { self.mix_factor } else { 0.0 };
+        let texture_color = texture_color.unwrap_or([0.0; 3]);
+        let normal = normalize(varyings.normal);
+        let color = match self.shading {
+            SoftwareShading::Gray => {
+                let gray_levels = [0.2, 0.4, 0.6, 0.8];
+                let gray = gray_levels[id.rem_euclid(4) as usize];
+                mix([gray; 3], texture_color, mix_factor)
+            },
+            // Every corner was given the normal of the face, see Rasterizer::triangle.
+            SoftwareShading::Flat => {
+                let regular_color = mix([0.0, 0.6, 0.6], texture_color, mix_factor);
+                let mut result = scale(regular_color, 0.2);
+                for light in &self.lights {
+                    let (radiance, l) = light.radiance(varyings.position);
+                    result = add(result, scale(mul(regular_color, radiance), dot(normal, l).max(0.0)));
+                }
+                result
+            },
+            SoftwareShading::Gouraud => {
+                let mut brightness = [0.0; 3];
+                for light in &self.lights {
+                    let (radiance, l) = light.radiance(varyings.position);
+                    brightness = add(brightness, scale(radiance, dot(normal, l).max(0.0)));
+                }
+                let dark_color = [0.0, 0.05, 0.05];
+                let regular_color = mix([0.0, 1.0, 1.0], texture_color, mix_factor);
+                add(dark_color, mul(sub(regular_color, dark_color), brightness))
+            },
+            SoftwareShading::BlinnPhong => {
+                let regular_color = mix([0.0, 0.6, 0.6], texture_color, mix_factor);
+                let camera_dir = normalize(scale(varyings.position, -1.0));
+                let mut result = scale(regular_color, 0.3);
+                for light in &self.lights {
+                    let (radiance, l) = light.radiance(varyings.position);
+                    let diffuse = dot(normal, l).max(0.0);
+                    let half_direction = normalize(add(l, camera_dir));
+                    let specular = dot(normal, half_direction).max(0.0).powf(16.0);
+                    result = add(result, mul(add(scale(regular_color, diffuse), [specular; 3]), radiance));
+                }
+                result
+            },
+            SoftwareShading::Toon => {
+                let regular_color = mix([0.0, 0.6, 0.6], texture_color, mix_factor);
+                let camera_dir = normalize(scale(varyings.position, -1.0));
+                let bands = self.toon_bands.max(1.0);
+                let mut result = scale(regular_color, 0.25);
+                for light in &self.lights {
+                    let (radiance, l) = light.radiance(varyings.position);
+                    let lit = dot(normal, l).max(0.0);
+                    let band = (lit * bands).ceil() / bands;
+                    let specular = step(0.95, dot(normal, normalize(add(l, camera_dir))).max(0.0)) * step(0.5, lit);
+                    result = add(result, mul(add(scale(regular_color, band), [specular * 0.5; 3]), radiance));
+                }
+                result
+            },
+            // Without matcap the texture of the shaders is white.
+            SoftwareShading::Matcap => {
+                let uv = [normal[0] * 0.495 + 0.5, normal[1] * 0.495 + 0.5];
+                self.matcap.map_or([1.0; 3], |matcap| matcap.sample(uv))
+            }
+        };
+        Some(color)
+    }
+}
+
+// Color and depth buffers of the CPU renderer, rows from top to bottom.
+pub struct Rasterizer {
+    pub width: u32,
+    pub height: u32,
+    color: Vec<[f32; 4]>,
+    depth: Vec<f32>,
+}
+
+impl Rasterizer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        Self {
+            width,
+            height,
+            color: vec![[0.0; 4]; size],
+            depth: vec![1.0; size],
+        }
+    }
+
+    // The clear color is linear and encoded to sRGB like glium does, while the shader
+    // outputs are stored as they are since glium programs declare sRGB outputs.
+    pub fn clear(&mut self, color: [f32; 4]) {
+        let color = [linear_to_srgb(color[0]), linear_to_srgb(color[1]), linear_to_srgb(color[2]), color[3]];
+        self.color.fill(color);
+        self.depth.fill(1.0);
+    }
+
+    pub fn image(&self) -> Image {
+        let mut image = Image::new(self.width, self.height);
+        for (pixel, color) in image.pixels.chunks_exact_mut(4).zip(&self.color) {
+            for i in 0..4 {
+                pixel[i] = (color[i].clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        }
+        image
+    }
+
+    // Depth test IfLess with depth write, like the model pass of the renderer.
+    fn fragment(&mut self, pipeline: &Pipeline, x: i64, y: i64, depth: f32, varyings: &Varyings, id: i32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 || !(0.0..=1.0).contains(&depth) {
+            return;
+        }
+        let index = (y * self.width as i64 + x) as usize;
+        if depth >= self.depth[index] {
+            return;
+        }
+        let Some(rgb) = pipeline.fragment(varyings, id) else {
+            return;
+        };
+        self.depth[index] = depth;
+        self.color[index] = [rgb[0], rgb[1], rgb[2], 1.0];
+    }
+
+    fn to_screen(&self, vertex: &ClipVertex) -> ScreenVertex {
+        let inv_w = 1.0 / vertex.clip[3];
+        let ndc = [vertex.clip[0] * inv_w, vertex.clip[1] * inv_w, vertex.clip[2] * inv_w];
+        ScreenVertex {
+            x: (ndc[0] * 0.5 + 0.5) * self.width as f32,
+            y: (0.5 - ndc[1] * 0.5) * self.height as f32,
+            depth: ndc[2] * 0.5 + 0.5,
+            inv_w,
+            varyings: vertex.varyings,
+        }
+    }
+
+    // Screen space weights turned into perspective correct ones.
+    fn perspective_weights(vertices: [&ScreenVertex; 3], weights: [f32; 3]) -> [f32; 3] {
+        let w = [weights[0] * vertices[0].inv_w, weights[1] * vertices[1].inv_w, weights[2] * vertices[2].inv_w];
+        let sum = w[0] + w[1] + w[2];
+        [w[0] / sum, w[1] / sum, w[2] / sum]
+    }
+
+    fn fill(&mut self, pipeline: &Pipeline, v: [&ScreenVertex; 3], id: i32) {
+        let area = (v[1].x - v[0].x) * (v[2].y - v[0].y) - (v[2].x - v[0].x) * (v[1].y - v[0].y);
+        if area == 0.0 {
+            return;
+        }
+        let min_x = v.iter().map(|v| v.x).fold(f32::MAX, f32::min).floor().max(0.0) as i64;
+        let max_x = v.iter().map(|v| v.x).fold(f32::MIN, f32::max).ceil().min(self.width as f32) as i64;
+        let min_y = v.iter().map(|v| v.y).fold(f32::MAX, f32::min).floor().max(0.0) as i64;
+        let max_y = v.iter().map(|v| v.y).fold(f32::MIN, f32::max).ceil().min(self.height as f32) as i64;
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let edge = |a: &ScreenVertex, b: &ScreenVertex| ((b.x - a.x) * (py - a.y) - (px - a.x) * (b.y - a.y)) / area;
+                let weights = [edge(v[1], v[2]), edge(v[2], v[0]), edge(v[0], v[1])];
+                if weights.iter().any(|&weight| weight < 0.0) {
+                    continue;
+                }
+                let depth = weights[0] * v[0].depth + weights[1] * v[1].depth + weights[2] * v[2].depth;
+                let varyings = Varyings::weighted(
+                    [&v[0].varyings, &v[1].varyings, &v[2].varyings],
+                    Self::perspective_weights(v, weights)
+                );
+                self.fragment(pipeline, x, y, depth, &varyings, id);
+            }
+        }
+    }
+
+    // One fragment per pixel along the longest axis, attributes interpolated with perspective.
+    fn line(&mut self, pipeline: &Pipeline, a: &ScreenVertex, b: &ScreenVertex, id: i32) {
+        let steps = (b.x - a.x).abs().max((b.y - a.y).abs()).ceil().max(1.0) as i64;
+        for step in 0..=steps {
+            let t = step as f32 / steps as f32;
+            let weights = Self::perspective_weights([a, b, b], [1.0 - t, t, 0.0]);
+            let varyings = Varyings::weighted([&a.varyings, &b.varyings, &b.varyings], weights);
+            let x = a.x + (b.x - a.x) * t;
+            let y = a.y + (b.y - a.y) * t;
+            let depth = a.depth + (b.depth - a.depth) * t;
+            self.fragment(pipeline, x.floor() as i64, y.floor() as i64, depth, &varyings, id);
+        }
+    }
+
+    fn triangle(&mut self, pipeline: &Pipeline, corners: &[Mesh]) {
+        let id = corners[0].id;
+        let mut vertices: Vec<ClipVertex> = corners.iter().map(|corner| pipeline.vertex(corner)).collect();
+        // The flat shader takes the normal from the derivatives of the position, turned toward the camera.
+        if pipeline.shading == SoftwareShading::Flat && pipeline.outline_width.is_none() {
+            let [p0, p1, p2] = [0, 1, 2].map(|i| vertices[i].varyings.position);
+            let mut normal = normalize(cross(sub(p1, p0), sub(p2, p0)));
+            if dot(normal, scale(p0, -1.0)) < 0.0 {
+                normal = scale(normal, -1.0);
+            }
+            for vertex in &mut vertices {
+                vertex.varyings.normal = normal;
+            }
+        }
+        let polygon = clip_polygon(vertices);
+        let screen: Vec<ScreenVertex> = polygon.iter().map(|vertex| self.to_screen(vertex)).collect();
+        for i in 1..screen.len().saturating_sub(1) {
+            let v = [&screen[0], &screen[i], &screen[i + 1]];
+            // Counter clockwise in OpenGL window coordinates, where y goes up.
+            let area = (v[1].x - v[0].x) * (v[2].y - v[0].y) - (v[2].x - v[0].x) * (v[1].y - v[0].y);
+            if pipeline.backface_culling && area < 0.0 {
+                continue;
+            }
+            match pipeline.polygon_mode {
+                0 => self.fill(pipeline, v, id),
+                1 => {
+                    // Outline of the clipped polygon, without the diagonals of the fan.
+                    if i == 1 { self.line(pipeline, v[0], v[1], id); }
+                    self.line(pipeline, v[1], v[2], id);
+                    if i + 2 == screen.len() { self.line(pipeline, v[2], v[0], id); }
+                },
+                _ => {
+                    for vertex in v {
+                        self.fragment(pipeline, vertex.x.floor() as i64, vertex.y.floor() as i64, vertex.depth, &vertex.varyings, id);
+                    }
+                }
+            }
+        }
+    }
+
+    // Draws the model like the model pass of Renderer::render, without shadows, normal maps nor wireframe overlay,
+    // then the outline of the toon shading. Each range is drawn with the transform of its node, hidden nodes are skipped.
+    pub fn draw(&mut self, drawn: &SoftwareObject, object: &SceneObject, ctx: &Ctx, shading: SoftwareShading, state: &PipelineState, matcap: Option<&CpuTexture>) {
+        let mut pipeline = Pipeline {
+            rotation_matrix: object.node_matrix(0),
+            normal_matrix: object.normal_matrix(0),
+            perspective_matrix: Matrix::new_perspective(ctx).get_4x4_matrix(),
+            object_center: object.obj.centroid,
+            lights: ctx.lights.iter()
+                .filter(|light| light.enabled)
+                .take(MAX_LIGHTS)
+                .map(ShadingLight::new)
+                .collect(),
+            mix_factor: ctx.mix_factor,
+            toon_bands: ctx.toon_bands,
+            shading,
+            texture: drawn.texture,
+            matcap,
+            backface_culling: state.backface_culling,
+            polygon_mode: state.polygon_mode,
+            outline_width: None,
+        };
+        self.draw_ranges(&mut pipeline, drawn, object);
+        if shading == SoftwareShading::Toon {
+            // Filled and not culled whatever the state, like the outline pass of the renderer.
+            pipeline.outline_width = Some(ctx.outline_width * object.obj.radius());
+            pipeline.backface_culling = false;
+            pipeline.polygon_mode = 0;
+            self.draw_ranges(&mut pipeline, drawn, object);
+        }
+    }
+
+    fn draw_ranges(&mut self, pipeline: &mut Pipeline, drawn: &SoftwareObject, object: &SceneObject) {
+        for range in drawn.ranges.iter().filter(|range| object.is_shown(range.node)) {
+            pipeline.rotation_matrix = object.node_matrix(range.node);
+            pipeline.normal_matrix = object.normal_matrix(range.node);
+            for corners in drawn.mesh[range.vertices.clone()].chunks_exact(3) {
+                self.triangle(pipeline, corners);
+            }
+        }
+    }
+}
+
+// What the rasterizer draws of one object of the scene.
+pub struct SoftwareObject<'a> {
+    pub mesh: &'a [Mesh],
+    pub ranges: &'a [DrawRange],
+    pub texture: Option<&'a CpuTexture>,
+}
+
+// Image of the scene at the given size, the background stays transparent when the state asks for it.
+// Each drawn object belongs to the object of ctx.scene at the same index.
+pub fn render(objects: &[SoftwareObject], ctx: &mut Ctx, shading: SoftwareShading, state: &PipelineState, (width, height): (u32, u32), matcap: Option<&CpuTexture>) -> Image {
+    ctx.aim_lights();
+    ctx.scene.update_world();
+    let mut rasterizer = Rasterizer::new(width, height);
+    let [r, g, b] = ctx.background;
+    rasterizer.clear(if state.transparent { [0.0; 4] } else { [r, g, b, 1.0] });
+    for (drawn, object) in objects.iter().zip(&ctx.scene.objects) {
+        rasterizer.draw(drawn, object, ctx, shading, state, matcap);
+    }
+    rasterizer.image()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: [[f32; 4]; 4] = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+    // Positions are given straight in clip space with w = 1, gray shading shows the face id.
+    fn pipeline(backface_culling: bool) -> Pipeline<'static> {
+        Pipeline {
+            rotation_matrix: IDENTITY,
+            normal_matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            perspective_matrix: IDENTITY,
+            object_center: [0.0; 3],
+            lights: Vec::new(),
+            mix_factor: 0.0,
+            toon_bands: 1.0,
+            shading: SoftwareShading::Gray,
+            texture: None,
+            matcap: None,
+            backface_culling,
+            polygon_mode: 0,
+            outline_width: None,
+        }
+    }
+
+    fn triangle(positions: [[f32; 3]; 3], id: i32) -> Vec<Mesh> {
+        positions.iter().map(|&position| Mesh::new(position, [0.0, 0.0, 1.0], [0.0, 0.0], id)).collect()
+    }
+
+    fn clip_vertex(clip: [f32; 4]) -> ClipVertex {
+        ClipVertex { clip, varyings: Varyings { position: [clip[0], clip[1], clip[2]], normal: [0.0; 3], tex_coords: [0.0; 2], facing: 0.0 } }
+    }
+
+    fn center(rasterizer: &Rasterizer) -> [f32; 4] {
+        rasterizer.color[(rasterizer.height / 2 * rasterizer.width + rasterizer.width / 2) as usize]
+    }
+
+    #[test]
+    fn the_near_plane_cuts_vertices_behind_the_camera() {
+        let polygon = clip_polygon(vec![
+            clip_vertex([0.0, 0.0, 0.5, 1.0]),
+            clip_vertex([1.0, 0.0, 0.5, 1.0]),
+            clip_vertex([0.0, 1.0, -2.0, -1.0]),
+        ]);
+        assert_eq!(polygon.len(), 4);
+        for vertex in &polygon {
+            assert!(vertex.clip[2] + vertex.clip[3] >= -1e-5 && vertex.clip[3] >= MIN_W, "{:?}", vertex.clip);
+        }
+        // The new corners are where the edges cross z = -w, a third of the way to the hidden vertex.
+        assert!((polygon[2].varyings.position[1] - 1.0 / 3.0).abs() < 1e-5);
+        assert!(clip_polygon(vec![clip_vertex([0.0, 0.0, -3.0, -1.0]); 3]).is_empty());
+    }
+
+    #[test]
+    fn interpolation_follows_the_perspective() {
+        let vertex = |x: f32, w: f32| ScreenVertex { x, y: 0.0, depth: 0.5, inv_w: 1.0 / w, varyings: clip_vertex([x; 4]).varyings };
+        let (near, far) = (vertex(0.0, 1.0), vertex(10.0, 3.0));
+        let weights = Rasterizer::perspective_weights([&near, &far, &far], [0.5, 0.5, 0.0]);
+        // Halfway on screen is only a quarter of the way toward the vertex three times farther.
+        assert!((weights[0] - 0.75).abs() < 1e-6 && (weights[1] - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn culling_drops_counter_clockwise_faces_like_opengl() {
+        let counter_clockwise = triangle([[-0.5, -0.5, 0.0], [0.5, -0.5, 0.0], [0.0, 0.5, 0.0]], 0);
+        let clockwise = triangle([[-0.5, -0.5, 0.0], [0.0, 0.5, 0.0], [0.5, -0.5, 0.0]], 0);
+        for (corners, culling, drawn) in [(&counter_clockwise, true, false), (&counter_clockwise, false, true), (&clockwise, true, true)] {
+            let mut rasterizer = Rasterizer::new(16, 16);
+            rasterizer.triangle(&pipeline(culling), corners);
+            assert_eq!(center(&rasterizer)[3] == 1.0, drawn);
+        }
+    }
+
+    #[test]
+    fn the_nearest_face_wins_whatever_the_order() {
+        let far = triangle([[-1.0, -1.0, 0.5], [3.0, -1.0, 0.5], [-1.0, 3.0, 0.5]], 0);
+        let near = triangle([[-1.0, -1.0, -0.5], [3.0, -1.0, -0.5], [-1.0, 3.0, -0.5]], 1);
+        for order in [[&far, &near], [&near, &far]] {
+            let mut rasterizer = Rasterizer::new(8, 8);
+            for corners in order {
+                rasterizer.triangle(&pipeline(false), corners);
+            }
+            assert_eq!(center(&rasterizer), [0.4, 0.4, 0.4, 1.0]);
+            assert_eq!(rasterizer.depth[0], 0.25);
+        }
+    }
+
+    #[test]
+    fn textures_are_bilinear_and_mirrored() {
+        let texture = CpuTexture { path: String::new(), width: 2, height: 1, texels: vec![[0.0; 3], [1.0; 3]] };
+        assert_eq!(texture.sample([0.25, 0.5]), [0.0; 3]);
+        assert_eq!(texture.sample([0.5, 0.5]), [0.5; 3]);
+        assert_eq!(texture.sample([0.75, 0.5]), [1.0; 3]);
+        // Past the edges the image is mirrored, not clamped nor repeated.
+        assert_eq!(texture.sample([1.25, 0.5]), texture.sample([0.75, 0.5]));
+        assert_eq!(texture.sample([-0.25, 0.5]), texture.sample([0.25, 0.5]));
+        assert_eq!(texture.sample([2.25, 0.5]), texture.sample([0.25, 0.5]));
+    }
+}
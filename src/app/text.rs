@@ -0,0 +1,225 @@
+use glium::{
+    backend::Facade,
+    texture::{ClientFormat, MipmapsOption, RawImage2d, UncompressedFloatFormat},
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter},
+    Program,
+    Surface,
+    Texture2d,
+    VertexBuffer,
+    uniform
+};
+
+use super::shaders::{TEXT_FRAGMENT_SHADER, TEXT_VERTEX_SHADER};
+
+// Glyph cell in pixels, the font is 5x7 with one pixel of spacing.
+pub const GLYPH_WIDTH: u32 = 6;
+pub const GLYPH_HEIGHT: u32 = 8;
+
+const FIRST_GLYPH: u8 = b' ';
+const LAST_GLYPH: u8 = b'~';
+
+// Rows from top to bottom, the leftmost pixel is the 5th bit. Lowercase letters use the uppercase ones.
+const FONT: [[u8; 7]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // !
+    [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00], // "
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // #
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // $
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // %
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // &
+    [0x04, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00], // '
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // (
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // )
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // *
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ,
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // .
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // /
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // 0
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // 1
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // 2
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // 3
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // 4
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // 5
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // 6
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // 8
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // :
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ;
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // <
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // =
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // >
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // ?
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E], // @
+    [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11], // A
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // B
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // C
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // D
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // E
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // F
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // G
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // H
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // I
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // L
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // O
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // P
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // Q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // R
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // S
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // W
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // X
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // Y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // Z
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // [
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // \
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ]
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // _
+    [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00], // `
+    [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11], // a
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // b
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // c
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // d
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // e
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // f
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // g
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // h
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // i
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // j
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // k
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // l
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // m
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // n
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // o
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // p
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // r
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // s
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // t
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // u
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // v
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // w
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // x
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // z
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02], // {
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // |
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08], // }
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00], // ~
+];
+
+#[derive(Copy, Clone, Debug)]
+pub struct TextVertex {
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+}
+
+glium::implement_vertex!(TextVertex, position, tex_coords);
+
+// Index of the glyph of a character in the atlas, unknown characters are drawn as '?'.
+fn glyph_index(c: char) -> u32 {
+    match c {
+        ' '..='~' => c as u32 - FIRST_GLYPH as u32,
+        _ => (b'?' - FIRST_GLYPH) as u32
+    }
+}
+
+// Text overlay drawn in pixels from the top left corner, on a dark background for readability.
+pub struct TextRenderer {
+    atlas: Texture2d,
+    program: Program,
+}
+
+impl TextRenderer {
+    pub fn new(display: &dyn Facade) -> Self {
+        let count = (LAST_GLYPH - FIRST_GLYPH + 1) as u32;
+        let width = count * GLYPH_WIDTH;
+        let mut data = vec![0u8; (width * GLYPH_HEIGHT) as usize];
+        for (index, glyph) in FONT.iter().enumerate() {
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..5 {
+                    if bits & (0x10 >> column) != 0 {
+                        // The first row of the texture is the bottom one.
+                        let y = GLYPH_HEIGHT as usize - 1 - row;
+                        let x = index * GLYPH_WIDTH as usize + column;
+                        data[y * width as usize + x] = 255;
+                    }
+                }
+            }
+        }
+        let image = RawImage2d {
+            data: std::borrow::Cow::Owned(data),
+            width,
+            height: GLYPH_HEIGHT,
+            format: ClientFormat::U8
+        };
+        Self {
+            atlas: Texture2d::with_format(display, image, UncompressedFloatFormat::U8, MipmapsOption::NoMipmap)
+                .expect("Error: Failed to create the font texture"),
+            program: Program::from_source(display, TEXT_VERTEX_SHADER, TEXT_FRAGMENT_SHADER, None)
+                .expect("Error: \"glium::Program::from_source\" Fail"),
+        }
+    }
+
+    // Lines longer than the target or below its bottom edge are cut, the alpha of the color fades the whole text.
+    pub fn draw<S: Surface>(&self, display: &dyn Facade, frame: &mut S, text: &str, position: [f32; 2], scale: f32, color: [f32; 4]) {
+        let (width, height) = frame.get_dimensions();
+        let cell_width = GLYPH_WIDTH as f32 * scale;
+        let cell_height = GLYPH_HEIGHT as f32 * scale;
+        let columns = ((width as f32 - position[0]) / cell_width).max(0.0) as usize;
+        let count = (LAST_GLYPH - FIRST_GLYPH + 1) as f32;
+        let mut vertices = Vec::new();
+        for (row, line) in text.lines().enumerate() {
+            let y = position[1] + row as f32 * cell_height;
+            if y + cell_height > height as f32 {
+                break;
+            }
+            for (column, c) in line.chars().take(columns).enumerate() {
+                let x = position[0] + column as f32 * cell_width;
+                let u = glyph_index(c) as f32 / count;
+                let du = 1.0 / count;
+                let corners = [
+                    ([x, y], [u, 1.0]),
+                    ([x + cell_width, y], [u + du, 1.0]),
+                    ([x + cell_width, y + cell_height], [u + du, 0.0]),
+                    ([x, y + cell_height], [u, 0.0])
+                ];
+                for i in [0, 1, 2, 0, 2, 3] {
+                    let (position, tex_coords) = corners[i];
+                    vertices.push(TextVertex { position, tex_coords });
+                }
+            }
+        }
+        if vertices.is_empty() {
+            return;
+        }
+        let vertex_buffer = VertexBuffer::new(display, &vertices).unwrap();
+        let uniforms = uniform! {
+            font: self.atlas.sampled()
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+                .minify_filter(MinifySamplerFilter::Nearest),
+            screen_size: [width as f32, height as f32],
+            text_color: color
+        };
+        let params = glium::DrawParameters {
+            blend: glium::Blend::alpha_blending(),
+            .. Default::default()
+        };
+        frame.draw(
+            &vertex_buffer,
+            glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+            &self.program,
+            &uniforms,
+            &params
+        ).unwrap();
+    }
+}
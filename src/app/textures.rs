@@ -0,0 +1,119 @@
+use std::{collections::VecDeque, fs, path::Path};
+
+use glium::{
+    backend::Facade
+};
+
+use super::parser::Images;
+
+pub const HISTORY_SIZE: usize = 4;
+
+// Last loaded textures, the newest one is at the back.
+// Loading a texture starts a cross-fade from the previous one.
+pub struct Textures {
+    pub history: VecDeque<Images>,
+    pub fade_duration: f32,
+    fade: f32,
+}
+
+impl Textures {
+    pub fn new(img: Images) -> Self {
+        let mut history = VecDeque::with_capacity(HISTORY_SIZE);
+        history.push_back(img);
+        Self {
+            history,
+            fade_duration: 1.0,
+            fade: 1.0,
+        }
+    }
+
+    pub fn current(&self) -> &Images {
+        &self.history[self.history.len() - 1]
+    }
+
+    pub fn previous(&self) -> &Images {
+        if self.history.len() < 2 {
+            self.current()
+        } else {
+            &self.history[self.history.len() - 2]
+        }
+    }
+
+    // 0.0 shows the previous texture, 1.0 the current one.
+    pub fn fade(&self) -> f32 {
+        self.fade
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.fade < 1.0
+    }
+
+    pub fn animate(&mut self, dt: f32) {
+        if self.fade_duration > 0.0 {
+            self.fade = (self.fade + dt / self.fade_duration).min(1.0);
+        } else {
+            self.fade = 1.0;
+        }
+    }
+
+    // Shows the current texture right away.
+    pub fn skip_fade(&mut self) {
+        self.fade = 1.0;
+    }
+
+    // A texture read again replaces its older copy.
+    pub fn push(&mut self, img: Images) {
+        self.history.retain(|old| old.path != img.path);
+        self.history.push_back(img);
+        while self.history.len() > HISTORY_SIZE {
+            self.history.pop_front();
+        }
+        self.fade = 0.0;
+    }
+
+    // Textures still in the history are reused instead of being parsed again.
+    pub fn load(&mut self, display: &dyn Facade, filepath: &str) -> Result<(), String> {
+        if self.current().path == filepath {
+            return Ok(());
+        }
+        let img = match self.history.iter().position(|img| img.path == filepath) {
+            Some(index) => self.history.remove(index).unwrap(),
+            None => Images::new(display, filepath)?
+        };
+        self.push(img);
+        Ok(())
+    }
+
+    pub fn set_fade_duration(&mut self, duration: f32) {
+        self.fade_duration = duration.clamp(0.0, 10.0);
+        println!("Texture fade duration: {:.2}s", self.fade_duration);
+    }
+
+    // Loads the next (or previous) .ppm file found in the folder of the current texture.
+    pub fn cycle_folder(&mut self, display: &dyn Facade, step: i32) -> Result<(), String> {
+        let current = self.current().path.clone();
+        let folder = match Path::new(&current).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => Path::new(".").to_path_buf()
+        };
+        let mut files: Vec<String> = fs::read_dir(&folder)
+            .map_err(|e| format!("Error: Cannot read {}. {}", folder.display(), e))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ppm")))
+            .filter_map(|path| path.to_str().map(String::from))
+            .collect();
+        if files.is_empty() {
+            return Err(format!("Error: No texture found in {}.", folder.display()));
+        }
+        files.sort();
+        let len = files.len() as i32;
+        let next = match files.iter().position(|file| Path::new(file) == Path::new(&current)) {
+            Some(index) => (index as i32 + step).rem_euclid(len),
+            None => 0
+        };
+        let filepath = &files[next as usize];
+        println!("Texture: {}", filepath);
+        self.load(display, filepath)
+    }
+}
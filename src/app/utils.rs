@@ -4,12 +4,12 @@ use std::collections::HashSet;
 pub fn has_duplicate(f: &Vec<u32>) -> bool {
     let mut tmp:  HashSet<u32> = HashSet::new();
     for x in f {
-        if tmp.contains(&x) {
+        if tmp.contains(x) {
             return true;
         }
-        tmp.insert(x.clone());
+        tmp.insert(*x);
     }
-    return false;
+    false
 }
 
 pub fn print_help() {
@@ -17,14 +17,58 @@ pub fn print_help() {
     println!("\x1b[32mH\x1b[0m : Display this help");
     println!("\x1b[32mV\x1b[0m : Change polygon draw mode (fill, line, point)");
     println!("\x1b[32mB\x1b[0m : Toggle backface culling");
-    println!("\x1b[32mM\x1b[0m : Switch between Gouraud or Blinn-Phong shading");
+    println!("\x1b[32mP\x1b[0m : Cycle the shading modes (gray, flat, Gouraud, Blinn-Phong, toon, matcap, PBR and ./shaders/modes.conf)");
+    println!("\x1b[32mShift + P\x1b[0m : Write the current shaders to ./shaders, edits there are reloaded live");
     println!("\x1b[32mL\x1b[0m : Switch between moving camera or light.");
+    println!("\x1b[32mShift\x1b[0m + A / D / Up / Down : Turn the selected light, \x1b[32mShift\x1b[0m + L : Aim it at the scene again.");
+    println!("\x1b[32mTab\x1b[0m : Select the next light, moved with L.");
+    println!("\x1b[32mJ\x1b[0m : Change the selected light type (directional, point, spot).");
+    println!("\x1b[32mO\x1b[0m : Switch on/off the selected light.");
+    println!("\x1b[32m, / .\x1b[0m : Decrease / increase the selected light intensity.");
+    println!("\x1b[32mU\x1b[0m : Add a light, \x1b[32mBackspace\x1b[0m : Remove the selected light.");
+    println!("\x1b[32mG\x1b[0m : Show / hide the light gizmos.");
+    println!("\x1b[32mC\x1b[0m : Switch on/off the shadows of the selected light.");
+    println!("\x1b[32mR\x1b[0m : Change the shadow map resolution.");
+    println!("\x1b[32m9 / 0\x1b[0m : Decrease / increase the shadow bias.");
+    println!("\x1b[32mY\x1b[0m : Show / hide the ground plane.");
+    println!("\x1b[32mI\x1b[0m : Show / hide the environment (drop a .hdr, or a .ppm with Shift held).");
+    println!("\x1b[32mQ / E\x1b[0m : Rotate the environment.");
+    println!("\x1b[32m7 / 8\x1b[0m : Decrease / increase the exposure.");
+    println!("\x1b[32m1 / 2\x1b[0m : Decrease / increase the toon shading bands.");
+    println!("\x1b[32m3 / 4\x1b[0m : Decrease / increase the toon outline width.");
+    println!("\x1b[32m5\x1b[0m : Show / hide the wireframe over the shaded model.");
+    println!("\x1b[32m6\x1b[0m : Switch the wireframe between polygon and triangle edges.");
+    println!("\x1b[32m; / '\x1b[0m : Decrease / increase the wireframe width, \x1b[32m\\\x1b[0m : Change its color.");
+    println!("\x1b[32m`\x1b[0m : Cycle the debug views (normals, UV checker, depth, face ids, triangle density).");
+    println!("\x1b[32m/\x1b[0m : Show / hide the normals, \x1b[32mF1 / F2\x1b[0m : Shorten / lengthen them.");
+    println!("\x1b[32mF3 / F4 / F5\x1b[0m : Show / hide the axis gizmo, the ground grid, the bounding box.");
+    println!("\x1b[32mF9\x1b[0m : Switch between the OpenGL and the software renderer (every shading but pbr).");
+    println!("\x1b[32mF10 / F11\x1b[0m : Toggle the transparent screenshot background, cycle screenshot supersampling (x1, x2, x4).");
+    println!("\x1b[32mF12 / Shift + F12\x1b[0m : Save a screenshot to ./screenshots as PNG / PPM.");
+    println!("\x1b[32mF6\x1b[0m : Show / hide the HUD (FPS, object, mesh size, shading, polygon mode, culling, light).");
+    println!("\x1b[32mF7\x1b[0m : Record a 72 frame turntable and its animated GIF to ./screenshots/recording_N (F7 again stops it).");
+    println!("\x1b[32mAlt\x1b[0m + drop a .ppm : Use it as matcap.");
+    println!("\x1b[32mM\x1b[0m : Switch on/off normal mapping (drop a .ppm with Ctrl held to use it as normal map).");
+    println!("\x1b[32mZ\x1b[0m : Show / hide the tangent frames (tangent red, bitangent green, normal blue).");
     println!("\x1b[32mT\x1b[0m : Switch on/off textures.");
-    println!("\x1b[32mZ\x1b[0m : Move forward in Z");
+    println!("\x1b[32mN\x1b[0m : Cross-fade to the next texture of the folder.");
+    println!("\x1b[32m[ / ]\x1b[0m : Decrease / increase the texture cross-fade duration.");
+    println!("\x1b[32mPageUp / PageDown\x1b[0m : Select the previous / next object, moved by the keys below.");
+    println!("\x1b[32mDelete\x1b[0m : Remove the selected object.");
+    println!("\x1b[32mHome\x1b[0m : Select the next node (OBJ o/g group) of the selected object.");
+    println!("\x1b[32mEnd\x1b[0m : Show / hide the selected node and its children.");
+    println!("\x1b[32mAlt\x1b[0m + W/A/S/D/Up/Down : Move the selected node, with \x1b[32mShift\x1b[0m also held turn it.");
+    println!("\x1b[32mAlt\x1b[0m + - / = : Shrink / grow the selected node, \x1b[32mAlt\x1b[0m + Insert : Reset its transform.");
+    println!("\x1b[32mF8\x1b[0m : Print the scene tree to the terminal (Shift to save the scene file, Ctrl to reopen it).");
+    println!("\x1b[32mShift\x1b[0m + drop a .obj : Add it to the scene, \x1b[32mCtrl\x1b[0m + drop a .obj : Remove the objects loaded from it.");
+    println!("\x1b[32mW\x1b[0m : Move forward in Z");
     println!("\x1b[32mS\x1b[0m : Move backward in Z");
-    println!("\x1b[32mQ\x1b[0m : Move left");
+    println!("\x1b[32mA\x1b[0m : Move left");
     println!("\x1b[32mD\x1b[0m : Move right");
     println!("\x1b[32mX\x1b[0m : Reverse rotation direction");
+    println!("\x1b[32mK\x1b[0m : Pause / resume the animations");
+    println!("\x1b[32m- / =\x1b[0m : Slow down / speed up the animations");
+    println!("\x1b[32mF\x1b[0m : Toggle the fixed time step");
     println!("\x1b[32mUp Arrow\x1b[0m    : Move up");
     println!("\x1b[32mDown Arrow\x1b[0m  : Move down");
     println!("\x1b[32mLeft Arrow\x1b[0m  : Increase rotation speed to the left or decrease to the right");
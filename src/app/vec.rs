@@ -39,16 +39,29 @@ impl Normalize for Vec<[f32; 3]> {
                             normal[1] * normal[1] + 
                             normal[2] * normal[2])
                             .sqrt();
+            // A degenerate face has no direction, it gets one instead of a division by zero.
             if length == 0.00 {
                 normal[0] = 1.0;
                 normal[1] = length;
                 normal[2] = length;
             }
             else {
-            }
                 normal[0] /= length;
                 normal[1] /= length;
                 normal[2] /= length;
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_length_normals_stay_finite() {
+        let mut normals = vec![[0.0, 3.0, 4.0], [0.0; 3]];
+        normals.normalize();
+        assert_eq!(normals, [[0.0, 0.6, 0.8], [1.0, 0.0, 0.0]]);
+    }
+}
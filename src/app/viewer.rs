@@ -0,0 +1,1126 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Instant
+};
+
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use super::{
+    asset_watcher::{model_files, AssetWatcher},
+    backend::{PipelineState, RenderBackend},
+    ctx::{Ctx, MAX_SPEED, SCREENSHOT_SCALES, WIREFRAME_COLORS},
+    hud::Hud,
+    image::Image,
+    light::{LightKind, MAX_LIGHTS},
+    loader::{copy_image, LoadJob, LoadKind, Loaded, Loader},
+    mesh::{DrawRange, Mesh},
+    parser::{obj_parser, Obj},
+    recording::Recording,
+    scene::SceneObject,
+    scene_file::{scene_path, SceneFile, SCENE_EXTENSION},
+    shadow::RESOLUTIONS,
+    software::SoftwareShading,
+    utils
+};
+
+// Distance a light moves per key press.
+const LIGHT_STEP: f32 = 0.5;
+// Angle a light turns per key press, in radians.
+const LIGHT_TURN: f32 = 0.1;
+// Distance a node moves per key press, in radii of its object.
+const NODE_STEP: f32 = 0.05;
+// Angle a node turns per key press, in radians.
+const NODE_TURN: f32 = 0.1;
+// Factor a node grows or shrinks by per key press.
+const NODE_SCALE: f32 = 1.1;
+
+// Scene state and the actions of the viewer, drawn by any backend.
+// The window only forwards its input and redraws here.
+pub struct Viewer<B: RenderBackend> {
+    pub ctx: Ctx,
+    pub backend: B,
+    pub hud: Hud,
+    // Held during the key presses and file drops.
+    pub modifiers: ModifiersState,
+    // Turntable being recorded, one frame per redraw.
+    pub recording: Option<Recording>,
+    // Scene file opened or saved last, saved over by the next save.
+    pub scene_file: Option<PathBuf>,
+    // Dropped models and textures being read on a worker thread.
+    pub loader: Loader,
+    // Files of the scene read again when they change on disk.
+    pub assets: AssetWatcher,
+}
+
+impl<B: RenderBackend> Viewer<B> {
+    pub fn new(mut ctx: Ctx, mut backend: B) -> Result<Self, String> {
+        if backend.registry().current().name != ctx.shading {
+            ctx.shading = backend.registry().select(&ctx.shading)?.name.clone();
+            backend.load_shading();
+        }
+        backend.upload_mesh(&mut ctx)?;
+        Ok(Self {
+            ctx,
+            backend,
+            hud: Hud::new(),
+            modifiers: ModifiersState::empty(),
+            recording: None,
+            scene_file: None,
+            loader: Loader::new(),
+            assets: AssetWatcher::new(),
+        })
+    }
+
+    // Replaces the model of the active object, it is kept when the file cannot be parsed.
+    pub fn load_obj(&mut self, filepath: &str) -> Result<(), String> {
+        let mut obj = obj_parser(filepath)?;
+        let mesh = Mesh::get_mesh_vector(&mut obj);
+        let ranges = Mesh::get_draw_ranges(&obj);
+        self.ctx.scene.active_mut().set_obj(obj);
+        self.backend.set_mesh(&self.ctx, self.ctx.scene.active, mesh, ranges);
+        Ok(())
+    }
+
+    // New object next to the others, it becomes the active one.
+    pub fn add_obj(&mut self, filepath: &str) -> Result<(), String> {
+        let mut obj = obj_parser(filepath)?;
+        let mesh = Mesh::get_mesh_vector(&mut obj);
+        let ranges = Mesh::get_draw_ranges(&obj);
+        self.add_object(obj, mesh, ranges)
+    }
+
+    // Same as add_obj with a mesh already built, nothing changes when the texture cannot be loaded.
+    fn add_object(&mut self, obj: Obj, mesh: Vec<Mesh>, ranges: Vec<DrawRange>) -> Result<(), String> {
+        let active = self.ctx.scene.active;
+        self.ctx.scene.add(obj);
+        if let Err(err) = self.backend.add_object(&self.ctx, mesh, ranges) {
+            self.ctx.scene.objects.pop();
+            self.ctx.scene.active = active;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    pub fn remove_object(&mut self, index: usize) -> Result<SceneObject, String> {
+        let object = self.ctx.scene.remove(index)?;
+        self.backend.remove_object(index);
+        self.loader.remove_target(index);
+        Ok(object)
+    }
+
+    // Every object read from the file, the scene keeps at least one.
+    fn remove_objects(&mut self, filepath: &str) -> Result<(), String> {
+        let indices = self.ctx.scene.find_path(filepath);
+        if indices.is_empty() {
+            return Err(format!("Error: No object was loaded from {}.", filepath));
+        }
+        for index in indices.into_iter().rev() {
+            self.remove_object(index)?;
+        }
+        Ok(())
+    }
+
+    pub fn select_object(&mut self, step: i32) {
+        self.ctx.scene.select(step);
+        let scene = &self.ctx.scene;
+        self.hud.info(&format!("Object {}/{}: {}", scene.active + 1, scene.objects.len(), scene.active().name()));
+    }
+
+    pub fn load_texture(&mut self, filepath: &str) -> Result<(), String> {
+        self.backend.upload_texture(&mut self.ctx, filepath)?;
+        self.backend.skip_fade(self.ctx.scene.active);
+        Ok(())
+    }
+
+    pub fn next_shading(&mut self) -> &str {
+        self.ctx.shading = self.backend.registry().next().name.clone();
+        self.backend.load_shading();
+        self.hud.info(&format!("Shading: {}", self.ctx.shading));
+        self.check_software_shading();
+        &self.ctx.shading
+    }
+
+    pub fn select_shading(&mut self, name: &str) -> Result<(), String> {
+        self.ctx.shading = self.backend.registry().select(name)?.name.clone();
+        self.backend.load_shading();
+        self.check_software_shading();
+        Ok(())
+    }
+
+    fn check_software_shading(&mut self) {
+        if self.ctx.software {
+            if let Err(err) = SoftwareShading::from_name(&self.ctx.shading) {
+                self.hud.error(&format!("{} Drawn in gray.", err));
+            }
+        }
+    }
+
+    pub fn toggle_culling(&mut self) {
+        self.ctx.toggle_culling();
+    }
+
+    pub fn next_polygon_mode(&mut self) {
+        self.ctx.next_polygon_mode();
+    }
+
+    // One image of the scene, scale times larger than the viewport.
+    pub fn render(&mut self, scale: u32, transparent: bool) -> Result<Image, String> {
+        self.backend.set_pipeline(&PipelineState::from_ctx(&self.ctx, transparent))?;
+        self.backend.draw(&mut self.ctx, scale)?;
+        self.backend.read_back()
+    }
+
+    // Image at the screenshot settings.
+    pub fn screenshot(&mut self, filepath: &Path) -> Result<(), String> {
+        let image = self.render(self.ctx.screenshot_scale, self.ctx.screenshot_transparent)?;
+        image.save(filepath)?;
+        self.hud.info(&format!("Screenshot: {} ({}x{})", filepath.display(), image.width, image.height));
+        Ok(())
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.recording.is_some() || (!self.ctx.clock.paused && (self.ctx.is_animating() || self.backend.is_animating() || self.hud.is_animating()))
+    }
+
+    // Nothing moves until the next event.
+    pub fn idle(&mut self) {
+        self.ctx.clock.idle();
+        self.hud.idle();
+    }
+
+    // Moves the scene to the time of the frame and draws it with the interface.
+    pub fn redraw(&mut self) -> Result<(), String> {
+        self.ctx.clock.tick();
+        while let Some(dt) = self.ctx.clock.step() {
+            if self.recording.is_none() {
+                self.ctx.animate(dt);
+            }
+            self.backend.animate(dt);
+        }
+        if let Some(recording) = &self.recording {
+            recording.apply(&mut self.ctx);
+        }
+        self.backend.present(&mut self.ctx, &mut self.hud)?;
+        self.record_frame();
+        Ok(())
+    }
+
+    // Saves the frame just drawn, the object moves to the next one at the next redraw.
+    fn record_frame(&mut self) {
+        if self.recording.is_none() {
+            return;
+        }
+        let image = self.render(self.ctx.screenshot_scale, self.ctx.screenshot_transparent);
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        match image.and_then(|image| recording.add(image)) {
+            Err(err) => {
+                self.hud.error(&err);
+                self.stop_recording();
+            },
+            Ok(()) if recording.is_done() => self.stop_recording(),
+            Ok(()) => {}
+        }
+    }
+
+    // Writes the animation of the frames taken so far and puts the object back.
+    fn stop_recording(&mut self) {
+        if let Some(mut recording) = self.recording.take() {
+            match recording.finish(&mut self.ctx) {
+                Ok(()) => self.hud.info(&format!("Recorded {} frames", recording.index)),
+                Err(err) => self.hud.error(&err)
+            }
+        }
+    }
+
+    fn save_scene(&mut self) -> Result<PathBuf, String> {
+        let filepath = self.scene_file.clone().unwrap_or_else(scene_path);
+        SceneFile::from_ctx(&self.ctx).save(&filepath)?;
+        self.scene_file = Some(filepath.clone());
+        Ok(filepath)
+    }
+
+    // Objects and settings of the file in place of the current ones,
+    // nothing changes when a model, a texture or the shading mode cannot be loaded.
+    // The window takes the size of the file afterwards.
+    pub fn open_scene(&mut self, filepath: &Path) -> Result<(), String> {
+        let file = SceneFile::parse(filepath)?;
+        let registry = self.backend.registry();
+        if let Some(shading) = file.shading.as_deref().filter(|shading| !registry.names().contains(shading)) {
+            return Err(format!("Error: Unknown shading mode {}, available: {}.", shading, registry.names().join(", ")));
+        }
+        self.stop_recording();
+        let previous = std::mem::replace(&mut self.ctx.scene, file.scene()?);
+        if let Err(err) = self.backend.upload_mesh(&mut self.ctx) {
+            self.ctx.scene = previous;
+            return Err(err);
+        }
+        file.apply(&mut self.ctx);
+        self.ctx.aim_lights();
+        self.scene_file = Some(filepath.to_path_buf());
+        let shading = self.ctx.shading.clone();
+        self.select_shading(&shading)
+    }
+
+    // Shader files, scene files and loads finished since the last call, true when the scene changed.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = self.backend.poll_shaders(&mut self.ctx);
+        self.reload_changed_files();
+        for (job, loaded) in self.loader.poll() {
+            self.finish_load(job, loaded);
+            changed = true;
+        }
+        self.hud.progress = self.loader.status();
+        changed
+    }
+
+    // Puts a file read by the loader in the scene between two frames, nothing changes when it failed.
+    // Replaced models and textures go to the object that was active when the file was dropped.
+    fn finish_load(&mut self, job: LoadJob, loaded: Result<Loaded, String>) {
+        let target = job.target.filter(|&index| index < self.ctx.scene.objects.len());
+        let result = loaded.and_then(|loaded| match (loaded, target) {
+            (Loaded::Model { obj, mesh, ranges }, _) if job.kind == LoadKind::Add => self.add_object(*obj, mesh, ranges),
+            // Every object read from the file keeps its transform and the state of its nodes.
+            (Loaded::Model { obj, mesh, ranges }, _) if job.kind == LoadKind::ReloadModel => {
+                for index in self.ctx.scene.find_path(&job.filepath) {
+                    self.ctx.scene.objects[index].reload_obj((*obj).clone());
+                    self.backend.set_mesh(&self.ctx, index, mesh.clone(), ranges.clone());
+                }
+                Ok(())
+            },
+            (Loaded::Texture { img, dim }, _) if job.kind == LoadKind::ReloadTexture => {
+                let indices: Vec<usize> = (0..self.ctx.scene.objects.len())
+                    .filter(|&index| self.ctx.scene.objects[index].texture == job.filepath)
+                    .collect();
+                for index in indices {
+                    self.backend.set_texture(&mut self.ctx, index, &job.filepath, copy_image(&img), dim)?;
+                    self.backend.skip_fade(index);
+                }
+                Ok(())
+            },
+            (_, None) => Err("Error: The object it was dropped on was removed.".to_string()),
+            (Loaded::Model { obj, mesh, ranges }, Some(index)) => {
+                self.ctx.scene.objects[index].set_obj(*obj);
+                self.backend.set_mesh(&self.ctx, index, mesh, ranges);
+                Ok(())
+            },
+            (Loaded::Texture { img, dim }, Some(index)) => self.backend.set_texture(&mut self.ctx, index, &job.filepath, img, dim)
+        });
+        let reload = matches!(job.kind, LoadKind::ReloadModel | LoadKind::ReloadTexture);
+        match result {
+            Ok(()) if reload => self.hud.info(&format!("Reloaded {}", job.filepath)),
+            Ok(()) => self.hud.info(&format!("Loaded {}", job.filepath)),
+            Err(err) => self.hud.error(&format!("Error while parsing: {}", err))
+        }
+    }
+
+    // Sends the files of the scene changed on disk to the loader: the models read with a changed
+    // .obj, .mtl or material map, and the changed textures.
+    fn reload_changed_files(&mut self) {
+        self.assets.update(&self.ctx.scene);
+        for path in self.assets.poll(Instant::now()) {
+            let Some(path) = path.to_str() else {
+                continue;
+            };
+            let mut jobs: Vec<LoadJob> = Vec::new();
+            for object in &self.ctx.scene.objects {
+                if model_files(&object.obj).contains(&path) {
+                    jobs.push(LoadJob { kind: LoadKind::ReloadModel, filepath: object.obj.path.clone(), target: None });
+                }
+                if object.texture == path {
+                    jobs.push(LoadJob { kind: LoadKind::ReloadTexture, filepath: path.to_string(), target: None });
+                }
+            }
+            // A job already waiting reads the new version too, the one being loaded may have read the old one.
+            for (index, job) in jobs.iter().enumerate() {
+                if !jobs[..index].contains(job) && !self.loader.pending.iter().skip(1).any(|pending| pending == job) {
+                    self.loader.send(job.kind, &job.filepath, None);
+                }
+            }
+        }
+    }
+
+    // Moves the selected light, with Shift held it turns instead and keeps its new direction.
+    fn move_light(&mut self, delta: [f32; 3], yaw: f32, pitch: f32) {
+        let shift = self.modifiers.shift_key();
+        let light = self.ctx.selected_light();
+        if shift {
+            light.turn(yaw, pitch);
+            self.hud.info(&light.describe());
+        } else {
+            light.translate(delta);
+        }
+    }
+
+    // With Alt held the move keys move the selected node instead of the object, turn it with Shift also held,
+    // - and = scale it and Insert resets it. Moves are in object space, the arrows and A/D mirror the object ones.
+    fn move_node(&mut self, key_code: KeyCode) -> bool {
+        let shift = self.modifiers.shift_key();
+        let object = self.ctx.scene.active_mut();
+        let step = NODE_STEP * object.obj.radius();
+        let node = &mut object.nodes[object.selected_node];
+        match key_code {
+            KeyCode::Minus => node.scale = node.scale.map(|s| s / NODE_SCALE),
+            KeyCode::Equal => node.scale = node.scale.map(|s| s * NODE_SCALE),
+            KeyCode::Insert => node.reset(),
+            _ => {
+                // Shift turns around Y with A/D, X with the arrows and Z with W/S.
+                let (axis, sign) = match (key_code, shift) {
+                    (KeyCode::KeyA, false) => (0, 1.0),
+                    (KeyCode::KeyD, false) => (0, -1.0),
+                    (KeyCode::KeyA, true) => (1, 1.0),
+                    (KeyCode::KeyD, true) => (1, -1.0),
+                    (KeyCode::ArrowDown, false) => (1, 1.0),
+                    (KeyCode::ArrowUp, false) => (1, -1.0),
+                    (KeyCode::ArrowDown, true) => (0, 1.0),
+                    (KeyCode::ArrowUp, true) => (0, -1.0),
+                    (KeyCode::KeyS, _) => (2, 1.0),
+                    (KeyCode::KeyW, _) => (2, -1.0),
+                    _ => return false
+                };
+                if shift {
+                    node.rotation[axis] += sign * NODE_TURN;
+                } else {
+                    node.translation[axis] += sign * step;
+                }
+            }
+        }
+        let description = node.describe();
+        self.hud.info(&format!("Node {}: {}", object.node_name(object.selected_node), description));
+        true
+    }
+
+    // Action of a key press, with the modifiers held.
+    pub fn key(&mut self, key_code: KeyCode) {
+        if self.modifiers.alt_key() && self.move_node(key_code) {
+            return;
+        }
+        match key_code {
+            KeyCode::Space => {
+                self.ctx.rotation = !self.ctx.rotation;
+            },
+            KeyCode::KeyA => {
+                if self.ctx.light_move {
+                    self.move_light([-LIGHT_STEP, 0.0, 0.0], -LIGHT_TURN, 0.0);
+                } else {
+                    self.ctx.scene.active_mut().transform.position[0] += 0.1;
+                }
+            },
+            KeyCode::KeyD => {
+                if self.ctx.light_move {
+                    self.move_light([LIGHT_STEP, 0.0, 0.0], LIGHT_TURN, 0.0);
+                } else {
+                    self.ctx.scene.active_mut().transform.position[0] -= 0.1;
+                }
+            },
+            KeyCode::KeyS => {
+                if self.ctx.light_move {
+                    self.ctx.selected_light().translate([0.0, 0.0, -LIGHT_STEP]);
+                } else {
+                    self.ctx.scene.active_mut().transform.position[2] += 0.5;
+                }
+            },
+            KeyCode::KeyW => {
+                if self.ctx.light_move {
+                    self.ctx.selected_light().translate([0.0, 0.0, LIGHT_STEP]);
+                } else {
+                    self.ctx.scene.active_mut().transform.position[2] -= 0.5;
+                }
+            },
+            KeyCode::ArrowDown => {
+                if self.ctx.light_move {
+                    self.move_light([0.0, -LIGHT_STEP, 0.0], 0.0, -LIGHT_TURN);
+                } else {
+                    self.ctx.scene.active_mut().transform.position[1] += 0.1;
+                }
+            },
+            KeyCode::ArrowUp => {
+                if self.ctx.light_move {
+                    self.move_light([0.0, LIGHT_STEP, 0.0], 0.0, LIGHT_TURN);
+                } else {
+                    self.ctx.scene.active_mut().transform.position[1] -= 0.1;
+                }
+            },
+            KeyCode::ArrowLeft => {
+                let speed: f32 = self.ctx.speed_factor;
+                if speed < MAX_SPEED {
+                    self.ctx.speed_factor += 0.3;
+                } else {
+                    self.ctx.speed_factor = MAX_SPEED;
+                }
+            }
+            KeyCode::ArrowRight => {
+                let speed: f32 = self.ctx.speed_factor;
+                if speed > -MAX_SPEED {
+                    self.ctx.speed_factor -= 0.3;
+                } else {
+                    self.ctx.speed_factor = -MAX_SPEED;
+                }
+            }
+            KeyCode::PageUp => {
+                self.select_object(-1);
+            },
+            KeyCode::PageDown => {
+                self.select_object(1);
+            },
+            KeyCode::Delete => {
+                match self.remove_object(self.ctx.scene.active) {
+                    Ok(object) => self.hud.info(&format!("Removed {}", object.name())),
+                    Err(err) => self.hud.error(&err)
+                }
+            },
+            KeyCode::KeyK => {
+                self.ctx.clock.toggle_pause();
+            },
+            KeyCode::Minus => {
+                let scale = self.ctx.clock.time_scale / 2.0;
+                self.ctx.clock.set_time_scale(scale);
+            },
+            KeyCode::Equal => {
+                let scale = self.ctx.clock.time_scale * 2.0;
+                self.ctx.clock.set_time_scale(scale);
+            },
+            KeyCode::KeyF => {
+                self.ctx.clock.toggle_fixed_step();
+            },
+            KeyCode::KeyP => {
+                if self.modifiers.shift_key() {
+                    if let Err(err) = self.backend.export_shaders() {
+                        self.hud.error(&err);
+                    }
+                } else {
+                    self.next_shading();
+                }
+            },
+            KeyCode::KeyL => {
+                if self.modifiers.shift_key() {
+                    let light = self.ctx.selected_light();
+                    light.aimed = true;
+                    self.hud.info(&light.describe());
+                } else {
+                    self.ctx.light_move = !self.ctx.light_move
+                }
+            },
+            KeyCode::Tab => {
+                self.ctx.selected_light = (self.ctx.selected_light + 1) % self.ctx.lights.len();
+                self.hud.info(&format!("Light {}: {}", self.ctx.selected_light, self.ctx.lights[self.ctx.selected_light].describe()));
+            },
+            KeyCode::KeyJ => {
+                let light = self.ctx.selected_light();
+                light.kind = light.kind.next();
+                self.hud.info(&light.describe());
+            },
+            KeyCode::KeyO => {
+                let light = self.ctx.selected_light();
+                light.enabled = !light.enabled;
+                self.hud.info(&light.describe());
+            },
+            KeyCode::Comma => {
+                let light = self.ctx.selected_light();
+                light.intensity = (light.intensity - 0.1).max(0.0);
+                self.hud.info(&light.describe());
+            },
+            KeyCode::Period => {
+                let light = self.ctx.selected_light();
+                light.intensity += 0.1;
+                self.hud.info(&light.describe());
+            },
+            KeyCode::KeyU => {
+                if self.ctx.lights.len() < MAX_LIGHTS {
+                    let mut light = self.ctx.selected_light().clone();
+                    light.kind = LightKind::Point;
+                    light.translate([LIGHT_STEP, LIGHT_STEP, 0.0]);
+                    self.ctx.lights.push(light);
+                    self.ctx.selected_light = self.ctx.lights.len() - 1;
+                    self.hud.info(&format!("Light {} added", self.ctx.selected_light));
+                } else {
+                    self.hud.error(&format!("Error: No more than {} lights.", MAX_LIGHTS));
+                }
+            },
+            KeyCode::Backspace if self.ctx.lights.len() > 1 => {
+                self.ctx.lights.remove(self.ctx.selected_light);
+                self.hud.info(&format!("Light {} removed", self.ctx.selected_light));
+                self.ctx.selected_light = self.ctx.selected_light.saturating_sub(1);
+            },
+            KeyCode::KeyG => {
+                self.ctx.gizmos = !self.ctx.gizmos;
+            },
+            KeyCode::KeyC => {
+                self.ctx.shadows = !self.ctx.shadows;
+                self.hud.info(&format!("Shadows {}", if self.ctx.shadows { "on" } else { "off" }));
+            },
+            KeyCode::KeyR => {
+                let index = RESOLUTIONS.iter().position(|&r| r == self.ctx.shadow_resolution).unwrap_or(0);
+                self.ctx.shadow_resolution = RESOLUTIONS[(index + 1) % RESOLUTIONS.len()];
+                self.hud.info(&format!("Shadow map resolution: {}", self.ctx.shadow_resolution));
+            },
+            KeyCode::Digit9 => {
+                self.ctx.shadow_bias = (self.ctx.shadow_bias - 0.001).max(0.0);
+                self.hud.info(&format!("Shadow bias: {:.3}", self.ctx.shadow_bias));
+            },
+            KeyCode::Digit0 => {
+                self.ctx.shadow_bias += 0.001;
+                self.hud.info(&format!("Shadow bias: {:.3}", self.ctx.shadow_bias));
+            },
+            KeyCode::KeyY => {
+                self.ctx.ground = !self.ctx.ground;
+            },
+            KeyCode::KeyM => {
+                self.ctx.normal_mapping = !self.ctx.normal_mapping;
+                self.hud.info(&format!("Normal mapping: {}", if self.ctx.normal_mapping { "on" } else { "off" }));
+            },
+            KeyCode::Digit1 => {
+                self.ctx.toon_bands = (self.ctx.toon_bands - 1.0).max(1.0);
+                self.hud.info(&format!("Toon bands: {}", self.ctx.toon_bands));
+            },
+            KeyCode::Digit2 => {
+                self.ctx.toon_bands = (self.ctx.toon_bands + 1.0).min(16.0);
+                self.hud.info(&format!("Toon bands: {}", self.ctx.toon_bands));
+            },
+            // The outline width is a fraction of the object radius.
+            KeyCode::Digit3 => {
+                self.ctx.outline_width = (self.ctx.outline_width / 1.25).max(0.001);
+                self.hud.info(&format!("Outline width: {:.3}", self.ctx.outline_width));
+            },
+            KeyCode::Digit4 => {
+                self.ctx.outline_width = (self.ctx.outline_width * 1.25).min(0.2);
+                self.hud.info(&format!("Outline width: {:.3}", self.ctx.outline_width));
+            },
+            KeyCode::Digit5 => {
+                self.ctx.wireframe = !self.ctx.wireframe;
+            },
+            KeyCode::Digit6 => {
+                self.ctx.wireframe_polygons = !self.ctx.wireframe_polygons;
+                self.hud.info(&format!("Wireframe: {} edges", if self.ctx.wireframe_polygons { "polygon" } else { "triangle" }));
+            },
+            KeyCode::Semicolon => {
+                self.ctx.wireframe_width = (self.ctx.wireframe_width - 0.5).max(0.5);
+                self.hud.info(&format!("Wireframe width: {:.1}px", self.ctx.wireframe_width));
+            },
+            KeyCode::Quote => {
+                self.ctx.wireframe_width = (self.ctx.wireframe_width + 0.5).min(8.0);
+                self.hud.info(&format!("Wireframe width: {:.1}px", self.ctx.wireframe_width));
+            },
+            KeyCode::Backslash => {
+                self.ctx.wireframe_color = (self.ctx.wireframe_color + 1) % WIREFRAME_COLORS.len();
+            },
+            KeyCode::Backquote => {
+                self.ctx.debug_view = self.ctx.debug_view.next();
+                self.hud.info(&format!("Debug view: {:?}", self.ctx.debug_view));
+            },
+            KeyCode::Slash => {
+                self.ctx.normal_lines = !self.ctx.normal_lines;
+            },
+            // The normal length is a fraction of the object radius.
+            KeyCode::F1 => {
+                self.ctx.normal_length = (self.ctx.normal_length / 1.25).max(0.005);
+                self.hud.info(&format!("Normal length: {:.3}", self.ctx.normal_length));
+            },
+            KeyCode::F2 => {
+                self.ctx.normal_length = (self.ctx.normal_length * 1.25).min(1.0);
+                self.hud.info(&format!("Normal length: {:.3}", self.ctx.normal_length));
+            },
+            KeyCode::F7 => {
+                if self.recording.is_some() {
+                    self.stop_recording();
+                } else {
+                    let recording = Recording::from_window(&self.ctx);
+                    if let Some(dir) = recording.output.as_ref().and_then(|output| output.parent()) {
+                        self.hud.info(&format!("Recording {} frames to {}", recording.frames, dir.display()));
+                    }
+                    self.recording = Some(recording);
+                }
+            },
+            KeyCode::F8 => {
+                if self.modifiers.shift_key() {
+                    match self.save_scene() {
+                        Ok(filepath) => self.hud.info(&format!("Scene saved to {}", filepath.display())),
+                        Err(err) => self.hud.error(&err)
+                    }
+                } else if self.modifiers.control_key() {
+                    let result = match self.scene_file.clone() {
+                        Some(filepath) => self.open_scene(&filepath).map(|()| filepath),
+                        None => Err("Error: No scene file was opened or saved.".to_string())
+                    };
+                    match result {
+                        Ok(filepath) => self.hud.info(&format!("Scene reopened from {}", filepath.display())),
+                        Err(err) => self.hud.error(&err)
+                    }
+                } else {
+                    println!("{}", self.ctx.scene.tree());
+                    self.hud.info("Scene tree printed to the terminal");
+                }
+            },
+            KeyCode::Home => {
+                let object = self.ctx.scene.active_mut();
+                object.select_node(1);
+                let hidden = if object.nodes[object.selected_node].visible { "" } else { " (hidden)" };
+                self.hud.info(&format!("Node: {}{}", object.node_name(object.selected_node), hidden));
+            },
+            KeyCode::End => {
+                let object = self.ctx.scene.active_mut();
+                let node = &mut object.nodes[object.selected_node];
+                node.visible = !node.visible;
+                let state = if node.visible { "shown" } else { "hidden" };
+                self.hud.info(&format!("Node {}: {}", object.node_name(object.selected_node), state));
+            },
+            KeyCode::F9 => {
+                self.ctx.software = !self.ctx.software;
+                self.hud.info(&format!("Renderer: {}", if self.ctx.software { "software" } else { "OpenGL" }));
+                self.check_software_shading();
+            },
+            KeyCode::F6 => {
+                self.hud.visible = !self.hud.visible;
+            },
+            KeyCode::F12 => {
+                let extension = if self.modifiers.shift_key() { "ppm" } else { "png" };
+                let filepath = Ctx::screenshot_path(extension);
+                if let Err(err) = self.screenshot(&filepath) {
+                    self.hud.error(&err);
+                }
+            },
+            KeyCode::F11 => {
+                let index = SCREENSHOT_SCALES.iter().position(|&scale| scale == self.ctx.screenshot_scale).map_or(0, |index| index + 1);
+                self.ctx.screenshot_scale = SCREENSHOT_SCALES[index % SCREENSHOT_SCALES.len()];
+                self.hud.info(&format!("Screenshot supersampling: x{}", self.ctx.screenshot_scale));
+            },
+            KeyCode::F10 => {
+                self.ctx.screenshot_transparent = !self.ctx.screenshot_transparent;
+                self.hud.info(&format!("Screenshot background: {}", if self.ctx.screenshot_transparent { "transparent" } else { "opaque" }));
+            },
+            KeyCode::F3 => {
+                self.ctx.axis_gizmo = !self.ctx.axis_gizmo;
+            },
+            KeyCode::F4 => {
+                self.ctx.grid = !self.ctx.grid;
+            },
+            KeyCode::F5 => {
+                self.ctx.bbox = !self.ctx.bbox;
+            },
+            KeyCode::KeyZ => {
+                self.ctx.tangent_frames = !self.ctx.tangent_frames;
+            },
+            KeyCode::KeyI => {
+                self.ctx.environment = !self.ctx.environment;
+            },
+            KeyCode::KeyQ => {
+                self.ctx.env_rotation -= std::f32::consts::PI / 12.0;
+            },
+            KeyCode::KeyE => {
+                self.ctx.env_rotation += std::f32::consts::PI / 12.0;
+            },
+            KeyCode::Digit7 => {
+                self.ctx.exposure = (self.ctx.exposure / 1.25).max(0.01);
+                self.hud.info(&format!("Exposure: {:.2}", self.ctx.exposure));
+            },
+            KeyCode::Digit8 => {
+                self.ctx.exposure = (self.ctx.exposure * 1.25).min(100.0);
+                self.hud.info(&format!("Exposure: {:.2}", self.ctx.exposure));
+            },
+            KeyCode::KeyB => {
+                self.toggle_culling();
+            }
+            KeyCode::KeyV => {
+                self.next_polygon_mode();
+            },
+            KeyCode::KeyH => {
+                utils::print_help();
+            }
+            KeyCode::KeyX => {
+                self.ctx.speed_factor *= -1.0;
+            },
+            KeyCode::KeyT => {
+                self.ctx.texture = !self.ctx.texture;
+            },
+            KeyCode::KeyN => {
+                if let Err(err) = self.backend.cycle_texture(&mut self.ctx, 1) {
+                    self.hud.error(&err);
+                }
+            },
+            KeyCode::BracketLeft => {
+                let duration = self.backend.fade_duration() - 0.25;
+                self.backend.set_fade_duration(duration);
+            },
+            KeyCode::BracketRight => {
+                let duration = self.backend.fade_duration() + 0.25;
+                self.backend.set_fade_duration(duration);
+            },
+            _ => {}
+        }
+    }
+
+    // Models and textures dropped on the window, the modifiers choose what the file becomes.
+    pub fn drop_file(&mut self, path: &Path) {
+        let Some(filepath) = path.to_str() else {
+            self.hud.error("Error: Invalid file path.");
+            return;
+        };
+        let filepath_lower = filepath.to_lowercase();
+        let shift = self.modifiers.shift_key();
+        let control = self.modifiers.control_key();
+        let alt = self.modifiers.alt_key();
+        let scene = filepath_lower.ends_with(&format!(".{}", SCENE_EXTENSION));
+        if !scene && !filepath_lower.ends_with(".obj") && !filepath_lower.ends_with(".ppm") && !filepath_lower.ends_with(".hdr") {
+            self.hud.error("Error: Unsupported file extension.");
+            return;
+        }
+        let removal = control && filepath_lower.ends_with(".obj");
+        // Models and textures are read on the worker thread, reported once loaded.
+        let background = if filepath_lower.ends_with(".obj") && !control {
+            Some(if shift { LoadKind::Add } else { LoadKind::Replace })
+        } else if filepath_lower.ends_with(".ppm") && !shift && !alt && !control {
+            Some(LoadKind::Texture)
+        } else {
+            None
+        };
+        let ret: Result<(), String> = if let Some(kind) = background {
+            let target = (kind != LoadKind::Add).then_some(self.ctx.scene.active);
+            self.loader.send(kind, filepath, target);
+            Ok(())
+        } else if scene {
+            self.open_scene(path)
+        } else if filepath_lower.ends_with(".hdr") || (shift && filepath_lower.ends_with(".ppm")) {
+            self.backend.load_environment(filepath)
+        } else if alt && filepath_lower.ends_with(".ppm") {
+            self.backend.load_matcap(filepath)
+        } else if control && filepath_lower.ends_with(".ppm") {
+            self.backend.load_normal_map(filepath)
+        } else {
+            self.remove_objects(filepath)
+        };
+        match ret {
+            Ok(()) if background.is_some() => {},
+            Ok(()) if removal => self.hud.info(&format!("Removed {}", filepath)),
+            Ok(()) => self.hud.info(&format!("Loaded {}", filepath)),
+            Err(err) => self.hud.error(&format!("Error while parsing: {}", err))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::app::{backend::CpuBackend, scene::Transform};
+
+    const MODEL: &str = "./obj/monkey.obj";
+    const TEXTURE: &str = "./obj/Texture/metal.ppm";
+    const OTHER_TEXTURE: &str = "./obj/Texture/texture.ppm";
+
+    fn viewer() -> Viewer<CpuBackend> {
+        let mut ctx = Ctx::with_obj(obj_parser(MODEL).unwrap());
+        ctx.width = 64;
+        ctx.height = 48;
+        let object = ctx.scene.active_mut();
+        object.transform = Transform::new([0.0, 0.0, 3.0], 0.6);
+        object.texture = TEXTURE.to_string();
+        Viewer::new(ctx, CpuBackend::new()).unwrap()
+    }
+
+    // Pixels that are not the background color.
+    fn covered(image: &Image) -> usize {
+        let background = image.pixel(0, 0);
+        image.pixels.chunks_exact(4).filter(|pixel| *pixel != background).count()
+    }
+
+    fn press(viewer: &mut Viewer<CpuBackend>, key_code: KeyCode, modifiers: ModifiersState) {
+        viewer.modifiers = modifiers;
+        viewer.key(key_code);
+    }
+
+    fn last_notification(viewer: &Viewer<CpuBackend>) -> &str {
+        viewer.hud.notifications.last().map_or("", |notification| &notification.text)
+    }
+
+    #[test]
+    fn pipeline_state_follows_ctx() {
+        let mut viewer = viewer();
+        viewer.toggle_culling();
+        viewer.next_polygon_mode();
+        let state = PipelineState::from_ctx(&viewer.ctx, true);
+        assert_eq!(state, PipelineState {
+            shading: "gray".to_string(),
+            backface_culling: false,
+            polygon_mode: 1,
+            transparent: true,
+        });
+    }
+
+    #[test]
+    fn polygon_modes_cycle() {
+        let mut viewer = viewer();
+        let modes: Vec<i32> = (0..4).map(|_| {
+            viewer.next_polygon_mode();
+            viewer.ctx.polmode
+        }).collect();
+        assert_eq!(modes, [1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn shading_cycles_through_every_mode() {
+        let mut viewer = viewer();
+        let names: Vec<String> = viewer.backend.registry.names().iter().map(|name| name.to_string()).collect();
+        let mut visited = vec![viewer.ctx.shading.clone()];
+        for _ in 1..names.len() {
+            visited.push(viewer.next_shading().to_string());
+        }
+        assert_eq!(visited, names);
+        assert_eq!(viewer.next_shading(), names[0]);
+    }
+
+    #[test]
+    fn unknown_shading_keeps_the_current_one() {
+        let mut viewer = viewer();
+        viewer.select_shading("gouraud").unwrap();
+        assert!(viewer.select_shading("missing").is_err());
+        assert_eq!(viewer.ctx.shading, "gouraud");
+        assert_eq!(viewer.backend.registry.current().name, "gouraud");
+    }
+
+    #[test]
+    fn cpu_backend_rejects_gpu_only_shading() {
+        let mut viewer = viewer();
+        viewer.select_shading("pbr").unwrap();
+        assert!(viewer.render(1, false).is_err());
+        viewer.select_shading("blinn_phong").unwrap();
+        assert!(viewer.render(1, false).is_ok());
+    }
+
+    #[test]
+    fn render_size_follows_the_scale() {
+        let mut viewer = viewer();
+        let image = viewer.render(2, false).unwrap();
+        assert_eq!((image.width, image.height), (128, 96));
+        assert_eq!(image.pixels.len(), 128 * 96 * 4);
+    }
+
+    #[test]
+    fn opaque_render_has_the_background_color() {
+        let mut viewer = viewer();
+        let image = viewer.render(1, false).unwrap();
+        let corner = image.pixel(0, 0);
+        assert_eq!(corner[0], 0);
+        assert!(corner[2] > corner[1] && corner[1] > 0);
+        assert!(image.is_opaque());
+        assert!(covered(&image) > 0);
+    }
+
+    #[test]
+    fn transparent_render_keeps_the_model_only() {
+        let mut viewer = viewer();
+        let image = viewer.render(1, true).unwrap();
+        assert_eq!(image.pixel(0, 0), [0, 0, 0, 0]);
+        let opaque = image.pixels.chunks_exact(4).filter(|pixel| pixel[3] == 255).count();
+        assert_eq!(opaque, covered(&image));
+    }
+
+    #[test]
+    fn polygon_modes_draw_differently() {
+        let mut viewer = viewer();
+        let fill = viewer.render(1, false).unwrap();
+        viewer.next_polygon_mode();
+        let line = viewer.render(1, false).unwrap();
+        viewer.next_polygon_mode();
+        let point = viewer.render(1, false).unwrap();
+        assert_ne!(fill, line);
+        assert!(covered(&fill) > covered(&point) && covered(&point) > 0);
+    }
+
+    #[test]
+    fn culling_hides_back_faces() {
+        let mut viewer = viewer();
+        viewer.next_polygon_mode();
+        let culled = covered(&viewer.render(1, false).unwrap());
+        viewer.toggle_culling();
+        let all = covered(&viewer.render(1, false).unwrap());
+        assert!(all > culled);
+    }
+
+    #[test]
+    fn texture_changes_textured_shading() {
+        let mut viewer = viewer();
+        viewer.select_shading("gouraud").unwrap();
+        let before = viewer.render(1, false).unwrap();
+        viewer.load_texture(OTHER_TEXTURE).unwrap();
+        let after = viewer.render(1, false).unwrap();
+        assert_ne!(before, after);
+        assert_eq!(viewer.ctx.scene.active().texture, OTHER_TEXTURE);
+    }
+
+    #[test]
+    fn failed_loads_keep_the_scene() {
+        let mut viewer = viewer();
+        let before = viewer.render(1, false).unwrap();
+        assert!(viewer.load_obj("./obj/missing.obj").is_err());
+        assert!(viewer.load_texture("./obj/Texture/missing.ppm").is_err());
+        assert_eq!(viewer.render(1, false).unwrap(), before);
+    }
+
+    #[test]
+    fn loading_a_model_replaces_the_mesh() {
+        let mut viewer = viewer();
+        let triangles = viewer.backend.meshes[0].len();
+        viewer.load_obj("./obj/42.obj").unwrap();
+        assert_eq!(viewer.backend.meshes.len(), 1);
+        assert_ne!(viewer.backend.meshes[0].len(), triangles);
+        assert_eq!(viewer.ctx.scene.active().obj.vertexs.len(), obj_parser("./obj/42.obj").unwrap().vertexs.len());
+    }
+
+    #[test]
+    fn objects_are_added_and_removed() {
+        let mut viewer = viewer();
+        viewer.ctx.scene.active_mut().transform.position[2] = 6.0;
+        let alone = viewer.render(1, false).unwrap();
+        viewer.add_obj("./obj/42.obj").unwrap();
+        assert_eq!((viewer.backend.meshes.len(), viewer.ctx.scene.active), (2, 1));
+        let both = viewer.render(1, false).unwrap();
+        assert!(covered(&both) > covered(&alone));
+        assert!(viewer.add_obj("./obj/missing.obj").is_err());
+        viewer.select_object(1);
+        viewer.remove_object(viewer.ctx.scene.active).unwrap();
+        assert_eq!(viewer.ctx.scene.active().name(), "42");
+        assert!(viewer.remove_object(0).is_err());
+        assert_eq!(viewer.backend.meshes.len(), 1);
+    }
+
+    #[test]
+    fn alt_keys_transform_the_selected_node() {
+        let mut viewer = viewer();
+        let step = NODE_STEP * viewer.ctx.scene.active().obj.radius();
+        let position = viewer.ctx.scene.active().transform.position;
+        press(&mut viewer, KeyCode::KeyA, ModifiersState::ALT);
+        press(&mut viewer, KeyCode::KeyA, ModifiersState::ALT | ModifiersState::SHIFT);
+        press(&mut viewer, KeyCode::Equal, ModifiersState::ALT);
+        let object = viewer.ctx.scene.active();
+        assert_eq!(object.transform.position, position);
+        assert_eq!(object.nodes[0].translation, [step, 0.0, 0.0]);
+        assert_eq!(object.nodes[0].rotation, [0.0, NODE_TURN, 0.0]);
+        assert_eq!(object.nodes[0].scale, [NODE_SCALE; 3]);
+        assert!(last_notification(&viewer).contains("s 1.10,1.10,1.10"));
+        press(&mut viewer, KeyCode::Insert, ModifiersState::ALT);
+        assert!(viewer.ctx.scene.active().nodes[0].is_identity());
+    }
+
+    #[test]
+    fn hidden_nodes_are_not_drawn() {
+        let mut viewer = viewer();
+        viewer.ctx.scene.active_mut().nodes[0].visible = false;
+        assert_eq!(covered(&viewer.render(1, false).unwrap()), 0);
+    }
+
+    #[test]
+    fn read_back_needs_a_draw() {
+        let mut backend = CpuBackend::new();
+        assert!(backend.read_back().is_err());
+        let mut ctx = Ctx::with_obj(obj_parser(MODEL).unwrap());
+        assert!(backend.draw(&mut ctx, 1).is_err());
+    }
+
+    #[test]
+    fn keys_change_the_scene_through_the_backend() {
+        let mut viewer = viewer();
+        press(&mut viewer, KeyCode::KeyV, ModifiersState::empty());
+        press(&mut viewer, KeyCode::KeyB, ModifiersState::empty());
+        assert_eq!((viewer.ctx.polmode, viewer.ctx.backface), (1, false));
+        press(&mut viewer, KeyCode::KeyP, ModifiersState::empty());
+        assert_ne!(viewer.ctx.shading, "gray");
+        assert_eq!(viewer.backend.registry.current().name, viewer.ctx.shading);
+        assert_eq!(last_notification(&viewer), format!("Shading: {}", viewer.ctx.shading));
+        press(&mut viewer, KeyCode::KeyN, ModifiersState::empty());
+        assert_eq!(last_notification(&viewer), "Error: Texture folders need the OpenGL renderer.");
+    }
+
+    #[test]
+    fn delete_removes_the_active_object() {
+        let mut viewer = viewer();
+        viewer.add_obj("./obj/42.obj").unwrap();
+        press(&mut viewer, KeyCode::Delete, ModifiersState::empty());
+        assert_eq!(last_notification(&viewer), "Removed 42");
+        press(&mut viewer, KeyCode::Delete, ModifiersState::empty());
+        assert_eq!(last_notification(&viewer), "Error: The scene needs at least one object.");
+        assert_eq!((viewer.backend.meshes.len(), viewer.ctx.scene.active().name()), (1, "monkey".to_string()));
+    }
+
+    #[test]
+    fn shift_turns_the_selected_light() {
+        let mut viewer = viewer();
+        press(&mut viewer, KeyCode::KeyL, ModifiersState::empty());
+        let position = viewer.ctx.selected_light().position;
+        press(&mut viewer, KeyCode::KeyA, ModifiersState::SHIFT);
+        assert_eq!(viewer.ctx.selected_light().position, position);
+        assert!(!viewer.ctx.selected_light().aimed);
+        press(&mut viewer, KeyCode::KeyL, ModifiersState::SHIFT);
+        assert!(viewer.ctx.selected_light().aimed);
+    }
+
+    #[test]
+    fn saved_scenes_are_reopened() {
+        let mut viewer = viewer();
+        viewer.scene_file = Some(std::env::temp_dir().join("scop_viewer_keys.scene"));
+        press(&mut viewer, KeyCode::F8, ModifiersState::SHIFT);
+        assert!(last_notification(&viewer).starts_with("Scene saved to"));
+        viewer.ctx.scene.active_mut().transform.position[2] = 6.0;
+        press(&mut viewer, KeyCode::F8, ModifiersState::CONTROL);
+        assert!(last_notification(&viewer).starts_with("Scene reopened from"));
+        assert_eq!(viewer.ctx.scene.active().transform.position[2], 3.0);
+    }
+
+    #[test]
+    fn dropped_models_are_added_between_frames() {
+        let mut viewer = viewer();
+        viewer.modifiers = ModifiersState::SHIFT;
+        viewer.drop_file(Path::new("./obj/42.obj"));
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !viewer.poll() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(last_notification(&viewer), "Loaded ./obj/42.obj");
+        assert_eq!((viewer.backend.meshes.len(), viewer.ctx.scene.active), (2, 1));
+        assert!(!viewer.loader.is_busy());
+    }
+
+    // Polls until the files dropped so far are in the scene.
+    fn finish_loads(viewer: &mut Viewer<CpuBackend>) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while viewer.loader.is_busy() && Instant::now() < deadline {
+            viewer.poll();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn dropped_files_go_to_the_object_active_when_dropped() {
+        let mut viewer = viewer();
+        viewer.add_obj("./obj/42.obj").unwrap();
+        viewer.drop_file(Path::new(OTHER_TEXTURE));
+        viewer.select_object(1);
+        finish_loads(&mut viewer);
+        assert_eq!(viewer.ctx.scene.active, 0);
+        assert_eq!(viewer.ctx.scene.objects[1].texture, OTHER_TEXTURE);
+        assert_eq!(viewer.ctx.scene.objects[0].texture, TEXTURE);
+        // Removed before its new model is read, the object is not brought back.
+        viewer.drop_file(Path::new("./obj/teapot.obj"));
+        viewer.remove_object(0).unwrap();
+        finish_loads(&mut viewer);
+        assert_eq!(last_notification(&viewer), "Error while parsing: Error: The object it was dropped on was removed.");
+        assert_eq!(viewer.ctx.scene.active().name(), "42");
+    }
+
+    #[test]
+    fn dropped_files_the_backend_cannot_use_are_reported() {
+        let mut viewer = viewer();
+        viewer.drop_file(Path::new("./obj/monkey.mtl"));
+        assert_eq!(last_notification(&viewer), "Error: Unsupported file extension.");
+        viewer.drop_file(Path::new("./obj/sky.hdr"));
+        assert_eq!(last_notification(&viewer), "Error while parsing: Error: Environment maps need the OpenGL renderer.");
+    }
+
+    #[test]
+    fn redraws_are_presented_by_the_backend() {
+        let mut viewer = viewer();
+        viewer.redraw().unwrap();
+        assert!(covered(&viewer.backend.read_back().unwrap()) > 0);
+    }
+}
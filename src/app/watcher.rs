@@ -0,0 +1,66 @@
+use std::{
+    fs::metadata,
+    path::{Path, PathBuf},
+    time::SystemTime
+};
+
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<(SystemTime, u64)>,
+}
+
+// The size tells apart two writes within the resolution of the modification time.
+fn modified(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+// Polls the modification time and the size of a set of files. Missing files are watched too,
+// creating or deleting one counts as a change.
+#[derive(Default)]
+pub struct FileWatcher {
+    files: Vec<WatchedFile>,
+}
+
+impl FileWatcher {
+    pub fn new() -> Self {
+        Self {
+            files: Vec::new(),
+        }
+    }
+
+    pub fn watch(&mut self, path: &Path) {
+        if self.files.iter().all(|file| file.path != path) {
+            self.files.push(WatchedFile {
+                path: path.to_path_buf(),
+                modified: modified(path),
+            });
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.files.clear();
+    }
+
+    // Stops watching the files that are not in paths.
+    pub fn retain(&mut self, paths: &[PathBuf]) {
+        self.files.retain(|file| paths.contains(&file.path));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    // Files modified since the previous call.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for file in &mut self.files {
+            let time = modified(&file.path);
+            if time != file.modified {
+                file.modified = time;
+                changed.push(file.path.clone());
+            }
+        }
+        changed
+    }
+}
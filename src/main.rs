@@ -1,9 +1,22 @@
 #![allow(dead_code)]
 
 mod app;
-use app::app::App;
+use app::{app::App, cli::Args, headless};
 
 
 fn main() {
-    App::run();
-}
\ No newline at end of file
+    match Args::parse() {
+        Ok(args) => {
+            let result = if args.headless { headless::run(args) } else { App::run(args) };
+            if let Err(err) = result {
+                println!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        Err(err) => {
+            println!("{}", err);
+            app::cli::print_usage();
+            std::process::exit(1);
+        }
+    }
+}